@@ -0,0 +1,1257 @@
+//! Computations which combine data of multiple entities on the grid.
+//!
+//! These helpers operate on data which was already fetched from graphql, so they can be shared
+//! between the UI and the CLI.
+
+use crate::{
+    bill_report::{ContractBillReport, DiscountLevel},
+    consumption::NRUConsumption,
+    contract::{ContractEvent, ContractInfo, ContractState, NodeContract},
+    denomination::Denomination,
+    graphql::Contracts,
+    period::Period,
+    uptime::{downtime_windows, NodeState, NodeStateChange},
+};
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    time::Duration,
+};
+
+/// Amount of seconds in the 30 day month used for cost projections.
+const SECONDS_IN_MONTH: u128 = 30 * 24 * 60 * 60;
+
+/// Amount of seconds between 2 bills of a contract.
+const BILLING_INTERVAL: i64 = 60 * 60;
+
+/// Get the `n` twins which were billed the most, in descending order of the amount billed. Twins
+/// which were billed the same amount are ordered by ascending twin ID.
+pub fn top_spenders(billing: &BTreeMap<u32, u64>, n: usize) -> Vec<(u32, u64)> {
+    top_n(billing, n)
+}
+
+/// Get the `n` contracts which were billed the most in the given bill reports, in descending order
+/// of the amount billed. Contracts which were billed the same amount are ordered by ascending
+/// contract ID. If less than `n` contracts were billed, all of them are returned.
+pub fn top_contracts(reports: &[ContractBillReport], n: usize) -> Vec<(u64, u64)> {
+    let mut billing = BTreeMap::new();
+    for report in reports {
+        *billing.entry(report.contract_id).or_default() += report.amount_billed;
+    }
+    top_n(&billing, n)
+}
+
+/// Get the `n` entries with the highest value, in descending order. Entries with the same value
+/// are ordered by ascending key.
+fn top_n<K: Copy>(values: &BTreeMap<K, u64>, n: usize) -> Vec<(K, u64)> {
+    let mut top = values
+        .iter()
+        .map(|(&key, &value)| (key, value))
+        .collect::<Vec<_>>();
+    // The map iterates in ascending key order, and the sort is stable, so ties keep that order.
+    top.sort_by(|a, b| b.1.cmp(&a.1));
+    top.truncate(n);
+    top
+}
+
+/// Project the cost of a contract for a 30 day month, based on the average billing rate observed
+/// in the given bill reports. The reports don't need to be sorted.
+///
+/// Every bill report covers the time since the previous report, so the amount billed in the
+/// oldest report is not part of the observed span. As a result, at least 2 reports with different
+/// timestamps are needed, otherwise [`None`] is returned.
+///
+/// This does not account for the variance in TFT price. The projection is only valid as long as
+/// the TFT price (and thus the amount of TFT billed for the same resources) stays the same.
+pub fn project_monthly_cost(reports: &[ContractBillReport]) -> Option<u64> {
+    let oldest = reports.iter().min_by_key(|report| report.timestamp)?;
+    let newest = reports.iter().max_by_key(|report| report.timestamp)?;
+    let span = (newest.timestamp - oldest.timestamp) as u128;
+    if span == 0 {
+        return None;
+    }
+
+    let billed = reports
+        .iter()
+        .map(|report| report.amount_billed as u128)
+        .sum::<u128>()
+        - oldest.amount_billed as u128;
+
+    u64::try_from(billed * SECONDS_IN_MONTH / span).ok()
+}
+
+/// A contract which is currently in grace period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GracePeriodContract {
+    /// The id of the contract.
+    pub contract_id: u64,
+    /// The twin which owns the contract.
+    pub twin_id: u32,
+    /// The node the contract is deployed on, if this is a node or rent contract.
+    pub node_id: Option<u32>,
+    /// Estimated timestamp at which the contract entered grace period, if it could be determined.
+    pub grace_since: Option<i64>,
+}
+
+/// Collect all contracts which are in grace period, sorted by how long they have been in grace
+/// period, longest first.
+///
+/// The time at which a contract entered grace period can't be queried directly. Instead, it is
+/// estimated as the timestamp of the last bill report of the contract, since a contract in grace
+/// period is no longer billed. The passed bill reports should therefore cover the history of the
+/// contracts. Contracts without any bill report have an unknown grace period start, and are
+/// sorted first as they might have been in grace period for the longest time.
+pub fn grace_period_watchlist(
+    contracts: &Contracts,
+    bills: &[ContractBillReport],
+) -> Vec<GracePeriodContract> {
+    let last_billed = last_billed(bills);
+
+    let mut watchlist = contracts
+        .node_contracts
+        .iter()
+        .filter(|c| matches!(c.state, ContractState::GracePeriod))
+        .map(|c| (c.contract_id, c.twin_id, Some(c.node_id)))
+        .chain(
+            contracts
+                .name_contracts
+                .iter()
+                .filter(|c| matches!(c.state, ContractState::GracePeriod))
+                .map(|c| (c.contract_id, c.twin_id, None)),
+        )
+        .chain(
+            contracts
+                .rent_contracts
+                .iter()
+                .filter(|c| matches!(c.state, ContractState::GracePeriod))
+                .map(|c| (c.contract_id, c.twin_id, Some(c.node_id))),
+        )
+        .map(|(contract_id, twin_id, node_id)| GracePeriodContract {
+            contract_id,
+            twin_id,
+            node_id,
+            grace_since: last_billed.get(&contract_id).copied(),
+        })
+        .collect::<Vec<_>>();
+    // None sorts before Some, so contracts with unknown start are listed first.
+    watchlist.sort_by_key(|c| (c.grace_since, c.contract_id));
+
+    watchlist
+}
+
+/// Calculate the average lifetime of the deleted contracts, i.e. the time between the creation
+/// and the deletion of a contract. Contracts in any other state are skipped.
+///
+/// The deletion time of a contract is not known, so it is approximated by the timestamp of the
+/// last bill report of the contract. Since contracts are billed periodically, this underestimates
+/// the lifetime by at most one billing cycle. The passed bill reports should therefore cover the
+/// history of the contracts. Deleted contracts without any bill report, or whose last bill report
+/// is before their creation, are skipped as well. Returns [`None`] if no lifetime could be
+/// calculated.
+pub fn average_lifetime<C: ContractInfo>(
+    contracts: &[C],
+    bills: &[ContractBillReport],
+) -> Option<Duration> {
+    let last_billed = last_billed(bills);
+
+    let lifetimes = contracts
+        .iter()
+        .filter(|c| c.state() == ContractState::Deleted)
+        .filter_map(|c| {
+            let deleted_at = last_billed.get(&c.contract_id())?;
+            // The creation time of contracts is in milliseconds. A last bill before the creation
+            // means the data is inconsistent, so the lifetime is unknown.
+            u64::try_from(deleted_at - c.created_at() / 1000).ok()
+        })
+        .collect::<Vec<_>>();
+    if lifetimes.is_empty() {
+        return None;
+    }
+
+    Some(Duration::from_secs(
+        lifetimes.iter().sum::<u64>() / lifetimes.len() as u64,
+    ))
+}
+
+/// Reconstruct the state transitions of a contract from its bill reports, oldest first.
+///
+/// The state history of a contract can't be queried directly. Similar to
+/// [`grace_period_watchlist`], a contract is presumed to enter grace period at its last bill
+/// report before a gap of more than 2 billing cycles in its bill reports, and to be restored once
+/// it is billed again. If the contract is no longer in the [`Created`](ContractState::Created)
+/// state, it transitioned to its current state at its last bill report, or at its creation if it
+/// was never billed. Bill reports of other contracts are ignored.
+pub fn contract_events<C: ContractInfo>(
+    contract: &C,
+    bills: &[ContractBillReport],
+) -> Vec<ContractEvent> {
+    let mut billed = bills
+        .iter()
+        .filter(|bill| bill.contract_id == contract.contract_id())
+        .map(|bill| bill.timestamp)
+        .collect::<Vec<_>>();
+    billed.sort_unstable();
+
+    // The creation time of contracts is in milliseconds.
+    let created = contract.created_at() / 1000;
+    let mut events = vec![ContractEvent {
+        state: ContractState::Created,
+        timestamp: created,
+    }];
+    for window in billed.windows(2) {
+        if window[1] - window[0] > 2 * BILLING_INTERVAL {
+            events.push(ContractEvent {
+                state: ContractState::GracePeriod,
+                timestamp: window[0],
+            });
+            events.push(ContractEvent {
+                state: ContractState::Created,
+                timestamp: window[1],
+            });
+        }
+    }
+    if contract.state() != ContractState::Created {
+        events.push(ContractEvent {
+            state: contract.state(),
+            timestamp: billed.last().copied().unwrap_or(created),
+        });
+    }
+
+    events
+}
+
+/// Get the timestamp of the last bill report of every contract in the bill reports.
+fn last_billed(bills: &[ContractBillReport]) -> BTreeMap<u64, i64> {
+    let mut last_billed: BTreeMap<u64, i64> = BTreeMap::new();
+    for bill in bills {
+        let ts = last_billed
+            .entry(bill.contract_id)
+            .or_insert(bill.timestamp);
+        *ts = i64::max(*ts, bill.timestamp);
+    }
+    last_billed
+}
+
+/// The price of 1 TFT in USD at a given point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricePoint {
+    /// Timestamp at which the price was recorded.
+    pub timestamp: i64,
+    /// The value of 1 TFT in USD.
+    pub usd_per_tft: f64,
+}
+
+/// Convert the amount billed in each bill report to USD, using the price point closest in time to
+/// the bill report. The price points must be sorted in ascending timestamp order.
+///
+/// The graphql server does not expose a TFT price history, so prices need to be supplied by the
+/// caller. If no price points are given, the `fallback_usd_per_tft` price is used for every bill
+/// report instead. The returned values are in the same order as the bill reports.
+pub fn bills_in_usd(
+    reports: &[ContractBillReport],
+    prices: &[PricePoint],
+    fallback_usd_per_tft: f64,
+) -> Vec<f64> {
+    reports
+        .iter()
+        .map(|report| {
+            let usd_per_tft = nearest_price(prices, report.timestamp)
+                .map(|price| price.usd_per_tft)
+                .unwrap_or(fallback_usd_per_tft);
+            Denomination::TFT.to_tokens(report.amount_billed) * usd_per_tft
+        })
+        .collect()
+}
+
+/// Find the price point closest to the given timestamp in a sorted list of price points.
+fn nearest_price(prices: &[PricePoint], ts: i64) -> Option<&PricePoint> {
+    let idx = prices.partition_point(|price| price.timestamp < ts);
+    match (idx.checked_sub(1).map(|i| &prices[i]), prices.get(idx)) {
+        (Some(before), Some(after)) => {
+            if ts - before.timestamp <= after.timestamp - ts {
+                Some(before)
+            } else {
+                Some(after)
+            }
+        }
+        (before, after) => before.or(after),
+    }
+}
+
+/// Estimate the cost of the public IPs of a node contract in a [`Period`], given the price of a
+/// single public IP for an hour in the smallest on chain unit.
+///
+/// Bill reports only hold the total amount billed, so the IP part of a bill can't be separated
+/// exactly. Instead, the public IPs are presumed to be rented from the moment the contract was
+/// created, or the start of the period if that is later, until the end of the period. The
+/// graphql server does not expose the pricing policy, so the price needs to be supplied by the
+/// caller. Since the moment a contract was deleted is not known here, this overestimates the cost
+/// of contracts which were deleted during the period.
+pub fn ip_cost(contract: &NodeContract, period: &Period, ip_price_per_hour: u64) -> u64 {
+    let start = i64::max(period.start(), contract.created_at / 1000);
+    let seconds = u128::try_from(period.end() - start).unwrap_or_default();
+    let cost =
+        u128::from(contract.number_of_public_ips) * u128::from(ip_price_per_hour) * seconds / 3600;
+    u64::try_from(cost).unwrap_or(u64::MAX)
+}
+
+/// Reliability figures of a node over a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reliability {
+    /// The amount of times the node went offline in the period.
+    pub failure_count: usize,
+    /// Mean time between failures, i.e. the time the node was up in the period divided by the
+    /// amount of failures. [`None`] if the node did not fail in the period.
+    pub mtbf_seconds: Option<i64>,
+    /// The longest uninterrupted amount of time the node was up in the period, in seconds.
+    pub longest_uptime_streak: i64,
+}
+
+/// Calculate the [`Reliability`] of a node in the period from `start` to `end`, based on the
+/// state changes as returned by [`calculate_node_state_changes`](crate::uptime::calculate_node_state_changes)
+/// for the same period.
+///
+/// Every [`NodeState::Offline`] is counted as a failure, except for the one at `start` which only
+/// indicates the node was not up yet when the period started. A node which is powered down by the
+/// farmer did not fail, though this time is not counted as uptime either. Similarly, time in which
+/// the node state is unknown is presumed down.
+pub fn reliability(changes: &[NodeStateChange], start: i64, end: i64) -> Reliability {
+    let failure_count = changes
+        .iter()
+        .filter(|change| matches!(change.state(), NodeState::Offline(ts) if ts > start))
+        .count();
+
+    let mut uptime = 0;
+    let mut longest_uptime_streak = 0;
+    let mut up_since = start;
+    for (down_start, down_end) in downtime_windows(changes, end) {
+        let streak = down_start.clamp(start, end) - up_since;
+        uptime += streak;
+        longest_uptime_streak = i64::max(longest_uptime_streak, streak);
+        up_since = i64::max(up_since, down_end.clamp(start, end));
+    }
+    let streak = end - up_since;
+    uptime += streak;
+    longest_uptime_streak = i64::max(longest_uptime_streak, streak);
+
+    Reliability {
+        failure_count,
+        mtbf_seconds: (failure_count > 0).then(|| uptime / failure_count as i64),
+        longest_uptime_streak,
+    }
+}
+
+/// Summary of the uptime of a node over a period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UptimeSummary {
+    /// The fraction of the period the node was up, between 0 and 1.
+    pub availability: f64,
+    /// The total time the node was down in the period, in seconds.
+    pub downtime_seconds: i64,
+    /// The windows of time the node was down, as `(start, end)` tuples clamped to the period.
+    pub downtime_windows: Vec<(i64, i64)>,
+    /// The reliability figures of the node in the period.
+    pub reliability: Reliability,
+}
+
+/// Summarize the uptime of a node in the period from `start` to `end`, based on the state changes
+/// as returned by [`calculate_node_state_changes`](crate::uptime::calculate_node_state_changes)
+/// for the same period. As with [`reliability`], time in which the node was powered down or in an
+/// unknown state counts as down.
+pub fn uptime_summary(changes: &[NodeStateChange], start: i64, end: i64) -> UptimeSummary {
+    let downtime_windows = downtime_windows(changes, end)
+        .into_iter()
+        .map(|(down_start, down_end)| (down_start.clamp(start, end), down_end.clamp(start, end)))
+        .filter(|(down_start, down_end)| down_end > down_start)
+        .collect::<Vec<_>>();
+    let downtime_seconds = downtime_windows
+        .iter()
+        .map(|(down_start, down_end)| down_end - down_start)
+        .sum::<i64>();
+    let duration = end - start;
+
+    UptimeSummary {
+        availability: if duration > 0 {
+            1. - downtime_seconds as f64 / duration as f64
+        } else {
+            0.
+        },
+        downtime_seconds,
+        downtime_windows,
+        reliability: reliability(changes, start, end),
+    }
+}
+
+/// Clip time windows, e.g. as returned by [`downtime_windows`], to the period from `start` to
+/// `end`. Windows which don't overlap with the period are removed, windows which partially overlap
+/// are shortened to the overlap.
+pub fn clip_windows(windows: &[(i64, i64)], start: i64, end: i64) -> Vec<(i64, i64)> {
+    windows
+        .iter()
+        .map(|&(from, to)| (from.max(start), to.min(end)))
+        .filter(|(from, to)| from < to)
+        .collect()
+}
+
+/// Calculate the fraction of every hour in which a node was up in the period from `start` to
+/// `end`, based on the state changes as returned by
+/// [`calculate_node_state_changes`](crate::uptime::calculate_node_state_changes) for the same
+/// period. Every item is a `(hour_start, fraction)` tuple, with hours aligned to UTC. The first
+/// and last hour might only partially overlap with the period, in which case the fraction only
+/// covers that overlap. Time in which the node was down, powered down or in an unknown state
+/// counts as down.
+pub fn hourly_uptime(changes: &[NodeStateChange], start: i64, end: i64) -> Vec<(i64, f64)> {
+    const HOUR: i64 = 60 * 60;
+    let downtime = downtime_windows(changes, end);
+
+    let mut hours = Vec::new();
+    let mut hour = BillBucket::Hour.bucket_start(start);
+    while hour < end {
+        let from = i64::max(hour, start);
+        let to = i64::min(hour + HOUR, end);
+        let down = downtime
+            .iter()
+            .map(|&(down_start, down_end)| {
+                i64::max(0, i64::min(down_end, to) - i64::max(down_start, from))
+            })
+            .sum::<i64>();
+        hours.push((hour, 1. - down as f64 / (to - from) as f64));
+        hour += HOUR;
+    }
+
+    hours
+}
+
+/// Detect windows of time in which a node is "flapping", i.e. it rebooted more than `threshold`
+/// times within `window` seconds. Every returned item is a `(start, boot_count)` tuple, where the
+/// window starts at the first boot in it and lasts `window` seconds. Returned windows don't
+/// overlap, a burst of reboots longer than `window` is reported as multiple consecutive windows.
+///
+/// The state changes are expected in ascending timestamp order, as returned by
+/// [`calculate_node_state_changes`](crate::uptime::calculate_node_state_changes).
+pub fn detect_flapping(
+    changes: &[NodeStateChange],
+    window: i64,
+    threshold: usize,
+) -> Vec<(i64, usize)> {
+    let boots = changes
+        .iter()
+        .filter_map(|change| match change.state() {
+            NodeState::Booted(ts) => Some(ts),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut flapping = Vec::new();
+    let mut first = 0;
+    while first < boots.len() {
+        let window_start = boots[first];
+        // Boots are sorted, so all boots in the window are consecutive.
+        let count = boots[first..].partition_point(|&ts| ts < window_start + window);
+        if count > threshold {
+            flapping.push((window_start, count));
+            first += count;
+        } else {
+            first += 1;
+        }
+    }
+
+    flapping
+}
+
+/// Summary of the contracts on a single node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NodeSummary {
+    /// The amount of node and rent contracts on the node which are not deleted.
+    pub active_contracts: usize,
+    /// The total amount billed for all contracts on the node, in the smallest on chain unit.
+    pub billed: u64,
+}
+
+/// Summarize the contracts on the given nodes. Every requested node is present in the result, even
+/// if it has no contracts. Name contracts are not deployed on a node, and are thus ignored. The
+/// passed bill reports are matched to the contracts on the nodes, reports of other contracts are
+/// ignored.
+pub fn node_summaries(
+    node_ids: &[u32],
+    contracts: &Contracts,
+    bills: &[ContractBillReport],
+) -> BTreeMap<u32, NodeSummary> {
+    let mut summaries: BTreeMap<u32, NodeSummary> = node_ids
+        .iter()
+        .map(|&node_id| (node_id, NodeSummary::default()))
+        .collect();
+
+    // Maps every contract on the nodes to its node, and whether it is still active.
+    let contract_nodes: BTreeMap<u64, (u32, bool)> = contracts
+        .node_contracts
+        .iter()
+        .map(|c| {
+            (
+                c.contract_id,
+                (c.node_id, !matches!(c.state, ContractState::Deleted)),
+            )
+        })
+        .chain(contracts.rent_contracts.iter().map(|c| {
+            (
+                c.contract_id,
+                (c.node_id, !matches!(c.state, ContractState::Deleted)),
+            )
+        }))
+        .filter(|(_, (node_id, _))| summaries.contains_key(node_id))
+        .collect();
+
+    for (node_id, active) in contract_nodes.values() {
+        if *active {
+            summaries.entry(*node_id).or_default().active_contracts += 1;
+        }
+    }
+    for report in bills {
+        if let Some((node_id, _)) = contract_nodes.get(&report.contract_id) {
+            summaries.entry(*node_id).or_default().billed += report.amount_billed;
+        }
+    }
+
+    summaries
+}
+
+/// Size of the time buckets in which bills are aggregated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BillBucket {
+    /// Buckets of 1 hour.
+    #[default]
+    Hour,
+    /// Buckets of 1 day, starting at midnight UTC.
+    Day,
+    /// Buckets of 1 week, starting on monday at midnight UTC.
+    Week,
+    /// Buckets of 1 calendar month, starting on the first day of the month at midnight UTC.
+    Month,
+}
+
+impl BillBucket {
+    /// All bucket sizes, from small to large.
+    pub const ALL: [BillBucket; 4] = [Self::Hour, Self::Day, Self::Week, Self::Month];
+
+    /// Get the start of the bucket which contains the given timestamp.
+    pub fn bucket_start(self, timestamp: i64) -> i64 {
+        const HOUR: i64 = 60 * 60;
+        const DAY: i64 = 24 * HOUR;
+        match self {
+            Self::Hour => timestamp - timestamp.rem_euclid(HOUR),
+            Self::Day => timestamp - timestamp.rem_euclid(DAY),
+            Self::Week => {
+                // The unix epoch is on a thursday, so shift the day to make monday day 0.
+                let day = timestamp.div_euclid(DAY);
+                (day - (day + 3).rem_euclid(7)) * DAY
+            }
+            Self::Month => {
+                let Some(date) = Utc.timestamp_opt(timestamp, 0).single() else {
+                    return timestamp;
+                };
+                NaiveDate::from_ymd_opt(date.year(), date.month(), 1)
+                    .and_then(|first| first.and_hms_opt(0, 0, 0))
+                    .map(|first| first.and_utc().timestamp())
+                    .unwrap_or(timestamp)
+            }
+        }
+    }
+}
+
+impl fmt::Display for BillBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hour => f.write_str("Hour"),
+            Self::Day => f.write_str("Day"),
+            Self::Week => f.write_str("Week"),
+            Self::Month => f.write_str("Month"),
+        }
+    }
+}
+
+/// Aggregate the amount billed in the given bill reports per bucket. The result maps the start
+/// timestamp of every bucket, as returned by [`BillBucket::bucket_start`], to the total amount
+/// billed in the bucket. Buckets without bill reports are not present, buckets with only
+/// zero amount reports are present with a total of 0, see [`exclude_zero_amount`].
+pub fn aggregate_bills(reports: &[ContractBillReport], bucket: BillBucket) -> BTreeMap<i64, u64> {
+    let mut aggregated = BTreeMap::new();
+    for report in reports {
+        *aggregated
+            .entry(bucket.bucket_start(report.timestamp))
+            .or_default() += report.amount_billed;
+    }
+    aggregated
+}
+
+/// Count the bill reports in which nothing was billed, e.g. because the contract was fully
+/// discounted.
+pub fn count_zero_amount(reports: &[ContractBillReport]) -> usize {
+    reports
+        .iter()
+        .filter(|report| report.amount_billed == 0)
+        .count()
+}
+
+/// Get the bill reports in which something was billed, in the same order. Reports in which
+/// nothing was billed don't change totals, but do count towards averages per report and show up
+/// as empty points in charts. Use [`count_zero_amount`] to report how many were excluded.
+pub fn exclude_zero_amount(reports: &[ContractBillReport]) -> Vec<ContractBillReport> {
+    reports
+        .iter()
+        .filter(|report| report.amount_billed > 0)
+        .copied()
+        .collect()
+}
+
+/// Aggregate the amount billed in the given bill reports per bucket and per [`DiscountLevel`]. The
+/// buckets are the same as in [`aggregate_bills`]. Discount levels for which nothing was billed in a
+/// bucket are not present in the bucket.
+pub fn discount_distribution(
+    reports: &[ContractBillReport],
+    bucket: BillBucket,
+) -> BTreeMap<i64, BTreeMap<DiscountLevel, u64>> {
+    let mut distribution = BTreeMap::<i64, BTreeMap<DiscountLevel, u64>>::new();
+    for report in reports {
+        *distribution
+            .entry(bucket.bucket_start(report.timestamp))
+            .or_default()
+            .entry(report.discount_received)
+            .or_default() += report.amount_billed;
+    }
+    distribution
+}
+
+/// Get the total amount of public network used, in bytes, in the given NRU consumption reports.
+pub fn total_nru(consumptions: &[NRUConsumption]) -> u64 {
+    consumptions.iter().map(|consumption| consumption.nru).sum()
+}
+
+/// Count the contracts of all types per [`ContractState`]. States without contracts are not
+/// present in the result.
+pub fn state_distribution(contracts: &Contracts) -> HashMap<ContractState, usize> {
+    let mut distribution = HashMap::new();
+    for state in contracts
+        .node_contracts
+        .iter()
+        .map(|c| c.state)
+        .chain(contracts.name_contracts.iter().map(|c| c.state))
+        .chain(contracts.rent_contracts.iter().map(|c| c.state))
+    {
+        *distribution.entry(state).or_default() += 1;
+    }
+    distribution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        aggregate_bills, average_lifetime, bills_in_usd, clip_windows, contract_events,
+        count_zero_amount, detect_flapping, discount_distribution, exclude_zero_amount,
+        grace_period_watchlist, hourly_uptime, ip_cost, node_summaries, project_monthly_cost,
+        reliability, state_distribution, top_contracts, top_spenders, total_nru, uptime_summary,
+        BillBucket, NodeSummary, PricePoint, Reliability, UptimeSummary,
+    };
+    use crate::{
+        bill_report::{ContractBillReport, DiscountLevel},
+        consumption::NRUConsumption,
+        contract::{ContractEvent, ContractState, NameContract, NodeContract, RentContract},
+        graphql::Contracts,
+        period::Period,
+        uptime::{calculate_node_state_changes, UptimeEvent},
+    };
+    use std::{
+        collections::{BTreeMap, HashMap},
+        time::Duration,
+    };
+
+    #[test]
+    fn top_spenders_ranked_descending_with_ties_by_twin() {
+        let billing = BTreeMap::from([(7, 100), (3, 500), (5, 100), (1, 50), (9, 500)]);
+
+        assert_eq!(
+            top_spenders(&billing, 4),
+            [(3, 500), (9, 500), (5, 100), (7, 100)]
+        );
+        assert_eq!(top_spenders(&billing, 10).len(), 5);
+        assert!(top_spenders(&billing, 0).is_empty());
+    }
+
+    #[test]
+    fn top_contracts_sums_bills_per_contract() {
+        let bill = |contract_id, amount_billed| ContractBillReport {
+            contract_id,
+            ..report(0, amount_billed)
+        };
+        let reports = [
+            bill(1, 10),
+            bill(2, 15),
+            bill(1, 10),
+            bill(3, 5),
+            bill(4, 20),
+        ];
+
+        assert_eq!(top_contracts(&reports, 2), [(1, 20), (4, 20)]);
+        // Less contracts than requested.
+        assert_eq!(
+            top_contracts(&reports, 10),
+            [(1, 20), (4, 20), (2, 15), (3, 5)]
+        );
+        assert!(top_contracts(&[], 3).is_empty());
+    }
+
+    fn report(timestamp: i64, amount_billed: u64) -> ContractBillReport {
+        ContractBillReport {
+            amount_billed,
+            contract_id: 1,
+            timestamp,
+            discount_received: DiscountLevel::None,
+        }
+    }
+
+    #[test]
+    fn exclude_zero_amount_reports() {
+        let reports = [
+            report(3_600, 100),
+            report(3_600, 0),
+            report(7_200, 0),
+            report(10_800, 50),
+        ];
+
+        assert_eq!(count_zero_amount(&reports), 2);
+        let billed = exclude_zero_amount(&reports);
+        assert_eq!(
+            billed
+                .iter()
+                .map(|r| (r.timestamp, r.amount_billed))
+                .collect::<Vec<_>>(),
+            [(3_600, 100), (10_800, 50)]
+        );
+        assert_eq!(count_zero_amount(&billed), 0);
+        // The hour with only a zero amount report is no longer present.
+        assert_eq!(
+            aggregate_bills(&billed, BillBucket::Hour)
+                .into_keys()
+                .collect::<Vec<_>>(),
+            [3_600, 10_800]
+        );
+        assert_eq!(aggregate_bills(&reports, BillBucket::Hour)[&7_200], 0);
+    }
+
+    #[test]
+    fn project_monthly_cost_extrapolates_hourly_rate() {
+        // 3 hours covered after the first report, 10 units per hour.
+        let reports = [
+            report(10_800, 10),
+            report(0, 10),
+            report(7_200, 10),
+            report(3_600, 10),
+        ];
+
+        assert_eq!(project_monthly_cost(&reports), Some(10 * 24 * 30));
+    }
+
+    #[test]
+    fn project_monthly_cost_needs_a_span() {
+        assert_eq!(project_monthly_cost(&[]), None);
+        assert_eq!(project_monthly_cost(&[report(3_600, 10)]), None);
+        assert_eq!(
+            project_monthly_cost(&[report(3_600, 10), report(3_600, 10)]),
+            None
+        );
+    }
+
+    #[test]
+    fn grace_period_watchlist_longest_first() {
+        let name_contract = |contract_id, state| NameContract {
+            contract_id,
+            created_at: 0,
+            solution_provider_id: None,
+            state,
+            twin_id: 1,
+            name: String::new(),
+        };
+        let contracts = Contracts {
+            node_contracts: Vec::new(),
+            name_contracts: vec![
+                name_contract(1, ContractState::GracePeriod),
+                name_contract(2, ContractState::Created),
+                name_contract(3, ContractState::GracePeriod),
+            ],
+            rent_contracts: vec![RentContract {
+                contract_id: 4,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::GracePeriod,
+                twin_id: 2,
+            }],
+        };
+        let mut bills = vec![report(3_600, 10), report(7_200, 10), report(1_000, 10)];
+        bills[2].contract_id = 4;
+        bills.push(report(5_000, 10));
+        bills[3].contract_id = 2;
+
+        let watchlist = grace_period_watchlist(&contracts, &bills);
+
+        assert_eq!(
+            watchlist
+                .iter()
+                .map(|c| (c.contract_id, c.grace_since))
+                .collect::<Vec<_>>(),
+            [(3, None), (4, Some(1_000)), (1, Some(7_200))]
+        );
+        assert_eq!(watchlist[1].node_id, Some(10));
+    }
+
+    #[test]
+    fn bills_in_usd_uses_nearest_price() {
+        let prices = [
+            PricePoint {
+                timestamp: 1_000,
+                usd_per_tft: 0.5,
+            },
+            PricePoint {
+                timestamp: 2_000,
+                usd_per_tft: 0.25,
+            },
+        ];
+        let reports = [
+            report(0, 10_000_000),
+            report(1_400, 10_000_000),
+            report(1_600, 20_000_000),
+            report(5_000, 40_000_000),
+        ];
+
+        assert_eq!(bills_in_usd(&reports, &prices, 1.), [0.5, 0.5, 0.5, 1.]);
+        assert_eq!(bills_in_usd(&reports, &[], 2.), [2., 2., 4., 8.]);
+    }
+
+    #[test]
+    fn ip_cost_of_time_in_period() {
+        let period = Period::at_offset(60);
+        let contract = |created_at: i64, number_of_public_ips| NodeContract {
+            contract_id: 1,
+            created_at: created_at * 1000,
+            deployment_data: String::new(),
+            deployment_hash: String::new(),
+            grid_version: 3,
+            node_id: 1,
+            number_of_public_ips,
+            resources_used: None,
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 1,
+        };
+        // Created before the period, so the IPs are rented for the whole period.
+        assert_eq!(
+            ip_cost(&contract(0, 2), &period, 1_000),
+            2 * 1_000 * period.duration() / 3600
+        );
+        // Created 10 hours before the end of the period.
+        assert_eq!(
+            ip_cost(&contract(period.end() - 36_000, 1), &period, 1_000),
+            10_000
+        );
+        assert_eq!(ip_cost(&contract(period.end() + 1, 1), &period, 1_000), 0);
+        assert_eq!(ip_cost(&contract(0, 0), &period, 1_000), 0);
+    }
+
+    #[test]
+    fn reliability_of_stable_node() {
+        let ues = [
+            UptimeEvent::new(1_000, 10_000),
+            UptimeEvent::new(6_000, 15_000),
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 5_000);
+
+        assert_eq!(
+            reliability(&changes, 0, 5_000),
+            Reliability {
+                failure_count: 0,
+                mtbf_seconds: None,
+                longest_uptime_streak: 5_000,
+            }
+        );
+    }
+
+    #[test]
+    fn reliability_of_failing_node() {
+        let ues = [
+            // Booted at 500, after the start of the period, which is not a failure.
+            UptimeEvent::new(1_000, 500),
+            // Went down after 1_000, booted again at 1_500.
+            UptimeEvent::new(2_000, 500),
+            // Went down after 2_000, booted again at 2_100.
+            UptimeEvent::new(5_000, 2_900),
+            UptimeEvent::new(11_000, 8_900),
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 10_000);
+
+        // Up from 500 to 1_000, 1_500 to 2_000 and 2_100 to 10_000.
+        assert_eq!(
+            reliability(&changes, 0, 10_000),
+            Reliability {
+                failure_count: 2,
+                mtbf_seconds: Some((500 + 500 + 7_900) / 2),
+                longest_uptime_streak: 7_900,
+            }
+        );
+    }
+
+    #[test]
+    fn contract_events_from_bill_gaps() {
+        let contract = NameContract {
+            contract_id: 1,
+            created_at: 1_000_000,
+            solution_provider_id: None,
+            state: ContractState::Deleted,
+            twin_id: 1,
+            name: String::new(),
+        };
+        let bill = |contract_id, timestamp| ContractBillReport {
+            amount_billed: 1,
+            contract_id,
+            timestamp,
+            discount_received: DiscountLevel::None,
+        };
+        // Billed every hour, except for a 5 hour gap after 8_200, and a bill of another contract
+        // in the gap.
+        let bills = [
+            bill(1, 4_600),
+            bill(1, 8_200),
+            bill(2, 12_000),
+            bill(1, 26_200),
+            bill(1, 29_800),
+        ];
+        let event = |state, timestamp| ContractEvent { state, timestamp };
+
+        assert_eq!(
+            contract_events(&contract, &bills),
+            [
+                event(ContractState::Created, 1_000),
+                event(ContractState::GracePeriod, 8_200),
+                event(ContractState::Created, 26_200),
+                event(ContractState::Deleted, 29_800),
+            ]
+        );
+        // A deleted contract which was never billed is deleted at its creation.
+        assert_eq!(
+            contract_events(&contract, &[]),
+            [
+                event(ContractState::Created, 1_000),
+                event(ContractState::Deleted, 1_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_flapping_reboot_burst() {
+        let mut ues = vec![UptimeEvent::new(0, 100_000)];
+        // Reboots every 10 minutes for an hour, starting at 10_000.
+        ues.extend((1..=6).map(|i| UptimeEvent::new(10_000 + i * 600, 300)));
+        // Steady afterwards, with a single reboot at 40_000.
+        ues.push(UptimeEvent::new(30_000, 19_700));
+        ues.push(UptimeEvent::new(40_100, 100));
+        ues.push(UptimeEvent::new(50_000, 10_000));
+        let changes = calculate_node_state_changes(&ues, 0, 50_000);
+
+        assert_eq!(detect_flapping(&changes, 3_600, 3), [(10_300, 6)],);
+        // The burst is longer than a 30 minute window.
+        assert_eq!(
+            detect_flapping(&changes, 1_800, 2),
+            [(10_300, 3), (12_100, 3)],
+        );
+        assert!(detect_flapping(&changes, 3_600, 6).is_empty());
+    }
+
+    #[test]
+    fn node_summaries_of_farm() {
+        let node_contract = |contract_id, node_id, state| NodeContract {
+            contract_id,
+            created_at: 0,
+            deployment_data: String::new(),
+            deployment_hash: String::new(),
+            grid_version: 3,
+            node_id,
+            number_of_public_ips: 0,
+            resources_used: None,
+            solution_provider_id: None,
+            state,
+            twin_id: 1,
+        };
+        let contracts = Contracts {
+            node_contracts: vec![
+                node_contract(1, 10, ContractState::Created),
+                node_contract(2, 10, ContractState::Deleted),
+                node_contract(3, 20, ContractState::GracePeriod),
+            ],
+            name_contracts: Vec::new(),
+            rent_contracts: vec![RentContract {
+                contract_id: 4,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 2,
+            }],
+        };
+        let mut bills = vec![report(0, 10), report(3_600, 10)];
+        bills.push(ContractBillReport {
+            contract_id: 2,
+            ..report(0, 5)
+        });
+        bills.push(ContractBillReport {
+            contract_id: 4,
+            ..report(0, 100)
+        });
+        // Not a contract on the nodes.
+        bills.push(ContractBillReport {
+            contract_id: 5,
+            ..report(0, 1_000)
+        });
+
+        assert_eq!(
+            node_summaries(&[10, 30], &contracts, &bills),
+            BTreeMap::from([
+                (
+                    10,
+                    NodeSummary {
+                        active_contracts: 2,
+                        billed: 125,
+                    }
+                ),
+                (30, NodeSummary::default()),
+            ])
+        );
+    }
+
+    #[test]
+    fn aggregate_bills_per_bucket() {
+        // Monday 2023-07-03 00:00:00 UTC.
+        let monday = 1_688_342_400;
+        let reports = [
+            report(monday - 1, 1),
+            report(monday, 10),
+            report(monday + 1_800, 100),
+            report(monday + 3_600, 1_000),
+            report(monday + 2 * 86_400, 10_000),
+        ];
+
+        assert_eq!(
+            aggregate_bills(&reports, BillBucket::Hour),
+            BTreeMap::from([
+                (monday - 3_600, 1),
+                (monday, 110),
+                (monday + 3_600, 1_000),
+                (monday + 2 * 86_400, 10_000),
+            ])
+        );
+        assert_eq!(
+            aggregate_bills(&reports, BillBucket::Day),
+            BTreeMap::from([
+                (monday - 86_400, 1),
+                (monday, 1_110),
+                (monday + 2 * 86_400, 10_000)
+            ])
+        );
+        assert_eq!(
+            aggregate_bills(&reports, BillBucket::Week),
+            BTreeMap::from([(monday - 7 * 86_400, 1), (monday, 11_110)])
+        );
+        // Saturday 2023-07-01 00:00:00 UTC.
+        assert_eq!(
+            aggregate_bills(&reports, BillBucket::Month),
+            BTreeMap::from([(monday - 2 * 86_400, 11_111)])
+        );
+    }
+
+    #[test]
+    fn discount_distribution_per_bucket() {
+        let bill = |timestamp, amount_billed, discount_received| ContractBillReport {
+            discount_received,
+            ..report(timestamp, amount_billed)
+        };
+        let reports = [
+            bill(0, 10, DiscountLevel::Gold),
+            bill(1_800, 5, DiscountLevel::Gold),
+            bill(1_800, 20, DiscountLevel::Silver),
+            bill(3_600, 30, DiscountLevel::None),
+        ];
+
+        assert_eq!(
+            discount_distribution(&reports, BillBucket::Hour),
+            BTreeMap::from([
+                (
+                    0,
+                    BTreeMap::from([(DiscountLevel::Silver, 20), (DiscountLevel::Gold, 15)])
+                ),
+                (3_600, BTreeMap::from([(DiscountLevel::None, 30)])),
+            ])
+        );
+        assert_eq!(
+            discount_distribution(&reports, BillBucket::Day)[&0]
+                .values()
+                .sum::<u64>(),
+            65
+        );
+    }
+
+    #[test]
+    fn state_distribution_over_contract_types() {
+        let name_contract = |contract_id, state| NameContract {
+            contract_id,
+            created_at: 0,
+            solution_provider_id: None,
+            state,
+            twin_id: 1,
+            name: String::new(),
+        };
+        let mut contracts = Contracts {
+            node_contracts: Vec::new(),
+            name_contracts: vec![
+                name_contract(1, ContractState::Created),
+                name_contract(2, ContractState::Deleted),
+                name_contract(3, ContractState::Created),
+            ],
+            rent_contracts: vec![RentContract {
+                contract_id: 4,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 2,
+            }],
+        };
+
+        assert_eq!(
+            state_distribution(&contracts),
+            HashMap::from([(ContractState::Created, 3), (ContractState::Deleted, 1)])
+        );
+        contracts.name_contracts.clear();
+        contracts.rent_contracts.clear();
+        assert!(state_distribution(&contracts).is_empty());
+    }
+
+    #[test]
+    fn total_nru_of_consumption_reports() {
+        let consumption = |contract_id, nru| NRUConsumption {
+            contract_id,
+            window: 3_600,
+            nru,
+            timestamp: 0,
+        };
+
+        assert_eq!(total_nru(&[]), 0);
+        assert_eq!(
+            total_nru(&[
+                consumption(1, 1_000),
+                consumption(2, 2_500),
+                consumption(1, 500)
+            ]),
+            4_000
+        );
+    }
+
+    #[test]
+    fn clip_windows_to_period() {
+        let windows = [
+            (0, 500),
+            (900, 1_200),
+            (1_500, 1_800),
+            (2_800, 3_500),
+            (4_000, 5_000),
+        ];
+
+        assert_eq!(
+            clip_windows(&windows, 1_000, 3_000),
+            [(1_000, 1_200), (1_500, 1_800), (2_800, 3_000)]
+        );
+        assert!(clip_windows(&windows, 3_500, 4_000).is_empty());
+    }
+
+    #[test]
+    fn hourly_uptime_with_partial_hours() {
+        let ues = [
+            // Booted at 1_800, after the start of the period.
+            UptimeEvent::new(2_000, 200),
+            // Went down after 5_400, booted again at 6_300.
+            UptimeEvent::new(5_400, 3_600),
+            UptimeEvent::new(6_400, 100),
+            UptimeEvent::new(20_000, 13_700),
+        ];
+        // Starts and ends halfway an hour.
+        let changes = calculate_node_state_changes(&ues, 900, 9_000);
+
+        assert_eq!(
+            hourly_uptime(&changes, 900, 9_000),
+            [(0, 1. - 1. / 3.), (3_600, 0.75), (7_200, 1.)]
+        );
+    }
+
+    #[test]
+    fn uptime_summary_clamped_to_period() {
+        let ues = [
+            // Booted at 1_800, after the start of the period.
+            UptimeEvent::new(2_000, 200),
+            // Went down after 5_400, booted again at 6_300.
+            UptimeEvent::new(5_400, 3_600),
+            UptimeEvent::new(6_400, 100),
+            UptimeEvent::new(20_000, 13_700),
+        ];
+        let changes = calculate_node_state_changes(&ues, 900, 9_000);
+
+        assert_eq!(
+            uptime_summary(&changes, 900, 9_000),
+            UptimeSummary {
+                availability: 1. - 1_800. / 8_100.,
+                downtime_seconds: 1_800,
+                downtime_windows: vec![(900, 1_800), (5_400, 6_300)],
+                reliability: reliability(&changes, 900, 9_000),
+            }
+        );
+    }
+
+    #[test]
+    fn average_lifetime_of_deleted_contracts() {
+        let bill = |contract_id, timestamp| ContractBillReport {
+            amount_billed: 1,
+            contract_id,
+            timestamp,
+            discount_received: DiscountLevel::None,
+        };
+        let rent_contract = |contract_id, created_at, state| RentContract {
+            contract_id,
+            created_at,
+            node_id: 10,
+            solution_provider_id: None,
+            state,
+            twin_id: 1,
+        };
+        let contracts = [
+            // Lived 1_000 seconds.
+            rent_contract(1, 1_000_000, ContractState::Deleted),
+            // Lived 3_000 seconds.
+            rent_contract(2, 2_000_000, ContractState::Deleted),
+            // Still active, so skipped.
+            rent_contract(3, 0, ContractState::Created),
+            // Never billed, so skipped.
+            rent_contract(4, 0, ContractState::Deleted),
+            // Last billed before it was created, so skipped.
+            rent_contract(5, 9_000_000, ContractState::Deleted),
+        ];
+        let bills = [
+            bill(1, 1_500),
+            bill(1, 2_000),
+            bill(2, 5_000),
+            bill(3, 10_000),
+            bill(5, 8_000),
+        ];
+
+        assert_eq!(
+            average_lifetime(&contracts, &bills),
+            Some(Duration::from_secs(2_000))
+        );
+        assert_eq!(average_lifetime(&contracts[2..], &bills), None);
+    }
+}
@@ -1,14 +1,16 @@
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    borrow::Cow,
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
     str::FromStr,
 };
 
-use chrono::{Local, NaiveDate, TimeZone};
+use crate::charts::{self, SECONDS_IN_HOUR};
+use chrono::{Local, NaiveDate, TimeZone, Utc};
 use eframe::{
     egui::{
         self,
-        plot::{Legend, Line, Plot, PlotPoints},
-        Layout, Widget,
+        plot::{Legend, Line, MarkerShape, Plot, PlotPoints, Points, Polygon},
+        Color32, Layout, RichText, Widget,
     },
     emath::Align,
     App,
@@ -16,43 +18,237 @@ use eframe::{
 use egui_extras::{Column, TableBuilder};
 use poll_promise::Promise;
 use tfgrid_graphql::{
-    bill_report::ContractBillReport,
-    contract::{ContractState, NameContract, NodeContract, RentContract},
-    graphql::Contracts,
-    uptime::{calculate_node_state_changes, NodeState, NodeStateChange, UptimeEvent},
+    analytics::{
+        aggregate_bills, count_zero_amount, detect_flapping, discount_distribution,
+        exclude_zero_amount, grace_period_watchlist, ip_cost, node_summaries, top_contracts,
+        total_nru, uptime_summary, BillBucket, GracePeriodContract, NodeSummary, UptimeSummary,
+    },
+    bill_report::{merge_reports, ContractBillReport, DiscountLevel},
+    contract::{
+        parse_deployment_data, total_resources, ContractEvent, ContractState, NameContract,
+        NodeContract, RentContract, Resources,
+    },
+    denomination::{Denomination, FormatOptions},
+    farm::Farm,
+    graphql::{CancellationToken, ContractFilters, Contracts},
+    node::Node,
+    period::Period,
+    solution_provider::SolutionProvider,
+    twin::Twin,
+    uptime::{
+        calculate_node_state_changes, downtime_windows, jitter_series, missing_uptime, state_at,
+        MissingUptime, NodeState, NodeStateChange, UptimeEvent, ALLOWED_UPTIME_DRIFT,
+    },
+};
+use tfgrid_graphql::{
+    export::{self, ContractRef, ContractRow},
+    graphql::Client,
+    report,
 };
 
 pub struct UiState {
     client: tfgrid_graphql::graphql::Client,
     selected: MenuSelection,
     contract_overview: ContractOverviewPanel,
+    contract_details: ContractDetailsPanel,
     node_state: NodeStatePanel,
     total_billed_state: TotalBilledPanel,
+    grace_period_state: GracePeriodPanel,
+    farm_overview_state: FarmOverviewPanel,
+    twin_overview_state: TwinOverviewPanel,
+}
+
+/// State for the contract details panel
+struct ContractDetailsPanel {
+    contract_id_input: String,
+    contract_id_error: String,
+    contract_id: Option<u64>,
+    events_loading: Option<TimedLoad<Vec<ContractEvent>>>,
 }
 
+/// helper type to avoid overly complex expressions.
+type TimedLoad<T> = Promise<Timed<Result<T, String>>>;
+
 /// State for the contract overview panel
 struct ContractOverviewPanel {
     node_id_input: String,
     twin_id_input: String,
     contract_id_input: String,
+    deployment_hash_input: String,
+    solution_provider_input: String,
     node_ids: BTreeSet<u32>,
     twin_ids: BTreeSet<u32>,
     contract_ids: BTreeSet<u64>,
+    deployment_hashes: BTreeSet<String>,
+    solution_provider_ids: BTreeSet<u32>,
     node_id_error: String,
     twin_id_error: String,
     contract_id_error: String,
-    contract_loading: Option<Promise<Result<Contracts, String>>>,
-    node_nru_loads: Vec<Option<Promise<Result<u64, String>>>>,
-    name_nru_loads: Vec<Option<Promise<Result<u64, String>>>>,
-    node_price_loads: Vec<Option<Promise<Result<u64, String>>>>,
-    name_price_loads: Vec<Option<Promise<Result<u64, String>>>>,
-    rent_price_loads: Vec<Option<Promise<Result<u64, String>>>>,
-    trigger_loads: bool,
+    deployment_hash_error: String,
+    solution_provider_error: String,
+    contract_loading: Option<TimedLoad<Contracts>>,
+    /// Cancels the requests of the current search when a new search is started.
+    contract_cancel: CancellationToken,
+    /// Whether to only search contracts created between the created dates, inclusive.
+    filter_created: bool,
+    created_after: NaiveDate,
+    created_before: NaiveDate,
+    /// Whether the loaded contracts are filtered on solution provider.
+    provider_filtered: bool,
+    hide_inactive: bool,
+    /// Only show loaded contracts which contain this text, see [`search_visible`].
+    search: String,
+    /// Whether to show node and rent contracts in a separate table per node.
+    group_by_node: bool,
+    /// Whether to load the cost of every shown contract, rather than only on request.
+    load_costs: bool,
+    /// Whether to load the NRU of every shown contract, rather than only on request.
+    load_nru: bool,
+    min_cost_input: String,
+    min_cost_error: String,
+    min_cost: Option<u64>,
+    ip_price_input: String,
+    ip_price_error: String,
+    /// Price of a single public IP for an hour, to estimate the IP cost of node contracts.
+    ip_price_per_hour: Option<u64>,
+    min_mru_input: String,
+    min_mru_error: String,
+    /// Only show node contracts using at least this much memory, in bytes.
+    min_mru: Option<u64>,
+    min_sru_input: String,
+    min_sru_error: String,
+    /// Only show node contracts using at least this much SSD storage, in bytes.
+    min_sru: Option<u64>,
+    node_nru_loads: ContractLoads,
+    name_nru_loads: ContractLoads,
+    node_price_loads: ContractLoads,
+    name_price_loads: ContractLoads,
+    rent_price_loads: ContractLoads,
+    /// Solution providers of the contracts, by ID. These are kept between searches, as the same
+    /// few providers are set on most contracts.
+    provider_loads: ProviderLoads,
 }
 
-/// helper type to avoid overly complex expressions.
-// TODO: translate this to struct
-type NodeStateInfo = (Vec<UptimeEvent>, Vec<NodeStateChange>);
+/// Maximum amount of loads of a single [`ContractLoads`] which run at the same time.
+const MAX_CONTRACT_LOADS_IN_FLIGHT: usize = 8;
+
+/// Values which are loaded separately for every contract in a table, by contract ID.
+///
+/// Loads are only started for contracts in rows which are shown, and at most
+/// [`MAX_CONTRACT_LOADS_IN_FLIGHT`] of them run at the same time, so large results don't flood
+/// the endpoint with requests. Contracts which are scrolled out of view before their load is
+/// started are not loaded until they are shown again.
+#[derive(Default)]
+struct ContractLoads {
+    loads: BTreeMap<u64, Promise<Result<u64, String>>>,
+    /// Contracts shown in the current frame which don't have a load yet, in the order they were
+    /// shown.
+    wanted: Vec<u64>,
+    /// Contracts the user asked to load explicitly, which are not started yet.
+    user_requested: BTreeSet<u64>,
+}
+
+impl ContractLoads {
+    /// Get the load of a contract, if it is started.
+    fn get(&self, contract_id: u64) -> Option<&Promise<Result<u64, String>>> {
+        self.loads.get(&contract_id)
+    }
+
+    /// Get the load of a shown contract. If the load is not started yet, it is queued to be
+    /// started by the next call to [`ContractLoads::start_wanted`].
+    fn request(&mut self, contract_id: u64) -> Option<&Promise<Result<u64, String>>> {
+        if !self.loads.contains_key(&contract_id) {
+            self.wanted.push(contract_id);
+        }
+        self.loads.get(&contract_id)
+    }
+
+    /// Remember that the user asked to load a contract, even if loads are not requested for all
+    /// shown contracts.
+    fn request_by_user(&mut self, contract_id: u64) {
+        self.user_requested.insert(contract_id);
+    }
+
+    /// Check if the user asked to load a contract, or its load is started.
+    fn is_requested(&self, contract_id: u64) -> bool {
+        self.loads.contains_key(&contract_id) || self.user_requested.contains(&contract_id)
+    }
+
+    /// Start the loads of the contracts requested since the last call, as long as less than
+    /// [`MAX_CONTRACT_LOADS_IN_FLIGHT`] loads are running. Requests which can't be started yet
+    /// are dropped, they are requested again when their row is shown in the next frame.
+    fn start_wanted<L>(&mut self, loader: impl Fn(u64) -> L)
+    where
+        L: FnOnce() -> Promise<Result<u64, String>>,
+    {
+        let mut in_flight = self
+            .loads
+            .values()
+            .filter(|load| load.ready().is_none())
+            .count();
+        for contract_id in self.wanted.drain(..) {
+            if in_flight >= MAX_CONTRACT_LOADS_IN_FLIGHT {
+                break;
+            }
+            if let Entry::Vacant(entry) = self.loads.entry(contract_id) {
+                entry.insert(loader(contract_id)());
+                self.user_requested.remove(&contract_id);
+                in_flight += 1;
+            }
+        }
+    }
+
+    /// Get the sum of all successfully loaded values, and the amount of contracts they were
+    /// loaded for.
+    fn loaded_total(&self) -> (u64, usize) {
+        self.loads
+            .values()
+            .filter_map(|load| load.ready()?.as_ref().ok())
+            .fold((0, 0), |(total, count), value| (total + value, count + 1))
+    }
+
+    /// Get the sum of the successfully loaded values of the given contracts, and the amount of
+    /// contracts they were loaded for.
+    fn loaded_sum(&self, contract_ids: impl IntoIterator<Item = u64>) -> (u64, usize) {
+        contract_ids
+            .into_iter()
+            .filter_map(|contract_id| self.get(contract_id)?.ready()?.as_ref().ok())
+            .fold((0, 0), |(total, count), value| (total + value, count + 1))
+    }
+
+    /// Get the loaded value of a contract, if it is loaded successfully.
+    fn loaded(&self, contract_id: u64) -> Option<u64> {
+        self.get(contract_id)?.ready()?.as_ref().ok().copied()
+    }
+
+    /// Remove all loads, e.g. because a new search is started.
+    fn clear(&mut self) {
+        self.loads.clear();
+        self.wanted.clear();
+        self.user_requested.clear();
+    }
+}
+
+/// Solution providers which are loaded separately for every solution provider ID.
+type ProviderLoads = BTreeMap<u32, Promise<Result<Option<SolutionProvider>, String>>>;
+
+/// Node state information loaded for the node state panel.
+struct NodeStateInfo {
+    /// Why there are no uptime events, if there are none.
+    missing_uptime: Option<MissingUptime>,
+    uptime_events: Vec<UptimeEvent>,
+    state_changes: Vec<NodeStateChange>,
+    downtime_windows: Vec<(i64, i64)>,
+    /// Uptime of the node in the period.
+    summary: UptimeSummary,
+    /// State of the node at the end of the period.
+    end_state: NodeState,
+    flapping: Vec<(i64, usize)>,
+    /// Bill reports of the contracts on the node in the period.
+    bills: Vec<ContractBillReport>,
+    start: i64,
+    end: i64,
+}
 
 /// State for the node state panel
 struct NodeStatePanel {
@@ -61,7 +257,18 @@ struct NodeStatePanel {
     node_id: Option<u32>,
     range_start: chrono::NaiveDate,
     range_end: chrono::NaiveDate,
-    node_loading: Option<Promise<Result<NodeStateInfo, String>>>,
+    post_period_hours_input: String,
+    post_period_hours_error: String,
+    /// Hours after the end of the period of which uptime events are fetched, to determine the
+    /// state of the node at the end of the period.
+    post_period_hours: Option<u32>,
+    jitter_threshold_input: String,
+    jitter_threshold_error: String,
+    /// Jitter in seconds above which uptime events are highlighted in the jitter graph.
+    jitter_threshold: Option<u64>,
+    node_loading: Option<TimedLoad<NodeStateInfo>>,
+    /// Why the last export could not be saved, empty if it could.
+    export_error: String,
 }
 
 type BillHistory = Vec<ContractBillReport>;
@@ -71,9 +278,151 @@ struct TotalBilledPanel {
     hours_input: String,
     hours_error: String,
     hours: Option<usize>,
-    bills_loading: Option<Vec<Promise<Result<BillHistory, String>>>>,
+    bucket: BillBucket,
+    /// Whether to show the share of every discount level in the amount billed.
+    show_discounts: bool,
+    /// Whether to leave bill reports in which nothing was billed out of the graphs.
+    hide_zero_amount: bool,
+    bills_loading: Option<Vec<TimedLoad<BillHistory>>>,
+    /// The results of the loaded hours of the current calculation.
+    bill_results: BillResults,
+    /// Cancels the requests of the current calculation when a new calculation is started.
+    bills_cancel: CancellationToken,
+}
+
+/// Bill reports of the hours of a calculation which are loaded so far. The bills of every hour are
+/// folded in once when they are loaded, so the graphs are not rebuilt from all bills every frame.
+#[derive(Default)]
+struct BillResults {
+    /// Whether the bills of every hour are folded in, by index of the load of the hour.
+    folded: Vec<bool>,
+    /// The amount of hours which are folded in.
+    done: usize,
+    /// Total amount billed per hour.
+    hourly: BTreeMap<i64, u64>,
+    /// All loaded bill reports, in ascending timestamp order.
+    reports: Vec<ContractBillReport>,
+    /// The amount of loaded bill reports in which nothing was billed.
+    zero_amount: usize,
+    /// All hours are loaded at the same time, so the slowest hour determines the total time.
+    elapsed: chrono::Duration,
+    /// The data of the graphs, built when the graphs are first drawn after bills are folded in or
+    /// the graph settings change.
+    graphs: Option<BillGraphs>,
+}
+
+/// Data of the graphs of the total billed panel, for the bills which are loaded so far.
+struct BillGraphs {
+    /// Whether bill reports in which nothing was billed are left out.
+    hide_zero_amount: bool,
+    /// The bucket of the discount shares.
+    bucket: BillBucket,
+    /// Total amount billed per hour.
+    hourly: BTreeMap<i64, u64>,
+    /// The contracts which were billed the most, see [`top_contracts`].
+    top_contracts: Vec<(u64, u64)>,
+    /// The amount billed per bucket and discount level, see [`discount_distribution`].
+    discounts: BTreeMap<i64, BTreeMap<DiscountLevel, u64>>,
+}
+
+impl BillResults {
+    /// Create empty results for a calculation of the given amount of hours.
+    fn new(hours: usize) -> Self {
+        Self {
+            folded: vec![false; hours],
+            elapsed: chrono::Duration::zero(),
+            ..Self::default()
+        }
+    }
+
+    /// Fold the loaded bills of an hour in the results, unless they are already folded in.
+    fn fold(&mut self, idx: usize, bills: &[ContractBillReport], elapsed: chrono::Duration) {
+        if self.folded[idx] {
+            return;
+        }
+        self.folded[idx] = true;
+        self.done += 1;
+        for (hour, amount) in aggregate_bills(bills, BillBucket::Hour) {
+            *self.hourly.entry(hour).or_default() += amount;
+        }
+        self.zero_amount += count_zero_amount(bills);
+        // Hours are loaded concurrently, so they are not folded in in order.
+        merge_reports(&mut self.reports, bills);
+        self.elapsed = self.elapsed.max(elapsed);
+        self.graphs = None;
+    }
+
+    /// Get the data of the graphs of the loaded bills, building it if the bills or the given
+    /// settings changed since it was last built.
+    fn graphs(&mut self, hide_zero_amount: bool, bucket: BillBucket) -> &BillGraphs {
+        let current = self.graphs.as_ref().is_some_and(|graphs| {
+            graphs.hide_zero_amount == hide_zero_amount && graphs.bucket == bucket
+        });
+        if !current {
+            let (hourly, reports) = if hide_zero_amount {
+                // Hours in which nothing was billed only had zero amount reports.
+                let hourly = self
+                    .hourly
+                    .iter()
+                    .filter(|(_, amount)| **amount > 0)
+                    .map(|(hour, amount)| (*hour, *amount))
+                    .collect();
+                (hourly, Cow::Owned(exclude_zero_amount(&self.reports)))
+            } else {
+                (self.hourly.clone(), Cow::Borrowed(self.reports.as_slice()))
+            };
+            self.graphs = Some(BillGraphs {
+                hide_zero_amount,
+                bucket,
+                hourly,
+                top_contracts: top_contracts(&reports, TOP_CONTRACTS),
+                discounts: discount_distribution(&reports, bucket),
+            });
+        }
+        self.graphs.as_ref().expect("graphs are built above")
+    }
+}
+
+/// State for the grace period watchlist panel
+struct GracePeriodPanel {
+    node_id_input: String,
+    twin_id_input: String,
+    node_ids: BTreeSet<u32>,
+    twin_ids: BTreeSet<u32>,
+    node_id_error: String,
+    twin_id_error: String,
+    watchlist_loading: Option<TimedLoad<Vec<GracePeriodContract>>>,
+}
+
+/// State for the farm overview panel. Data is loaded in stages, every stage starts once the
+/// previous one finished.
+struct FarmOverviewPanel {
+    farm_id_input: String,
+    farm_id_error: String,
+    farm_id: Option<u32>,
+    farm_loading: Option<TimedLoad<Farm>>,
+    nodes_loading: Option<TimedLoad<Vec<Node>>>,
+    summaries_loading: Option<TimedLoad<BTreeMap<u32, NodeSummary>>>,
+    /// The amount of active node contracts per node, which loads faster than the summaries.
+    counts_loading: Option<TimedLoad<BTreeMap<u32, u64>>>,
+}
+
+/// State for the twin overview panel
+struct TwinOverviewPanel {
+    twin_id_input: String,
+    twin_id_error: String,
+    twin_id: Option<u32>,
+    twin_loading: Option<TimedLoad<Twin>>,
+    contracts_loading: Option<TimedLoad<Contracts>>,
+    billed_loading: Option<TimedLoad<u64>>,
+    report_loading: Option<ReportLoad>,
+    /// Why the last report could not be generated or saved, empty if it could.
+    report_error: String,
 }
 
+/// A report being generated, with the name of the file to save it to.
+type ReportLoad = Promise<Result<(&'static str, String), String>>;
+
 impl UiState {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         log::debug!("{:?}", cc.integration_info);
@@ -85,19 +434,53 @@ impl UiState {
                 node_id_input: String::new(),
                 twin_id_input: String::new(),
                 contract_id_input: String::new(),
+                deployment_hash_input: String::new(),
+                solution_provider_input: String::new(),
                 node_ids: BTreeSet::new(),
                 twin_ids: BTreeSet::new(),
                 contract_ids: BTreeSet::new(),
+                deployment_hashes: BTreeSet::new(),
+                solution_provider_ids: BTreeSet::new(),
                 node_id_error: String::new(),
                 twin_id_error: String::new(),
                 contract_id_error: String::new(),
+                deployment_hash_error: String::new(),
+                solution_provider_error: String::new(),
                 contract_loading: None,
-                node_nru_loads: Vec::new(),
-                name_nru_loads: Vec::new(),
-                node_price_loads: Vec::new(),
-                name_price_loads: Vec::new(),
-                rent_price_loads: Vec::new(),
-                trigger_loads: false,
+                contract_cancel: CancellationToken::new(),
+                filter_created: false,
+                created_after: Local::now().date_naive() - chrono::Duration::days(7),
+                created_before: Local::now().date_naive(),
+                provider_filtered: false,
+                hide_inactive: false,
+                search: String::new(),
+                group_by_node: false,
+                load_costs: false,
+                load_nru: false,
+                min_cost_input: String::new(),
+                min_cost_error: String::new(),
+                min_cost: None,
+                ip_price_input: String::new(),
+                ip_price_error: String::new(),
+                ip_price_per_hour: None,
+                min_mru_input: String::new(),
+                min_mru_error: String::new(),
+                min_mru: None,
+                min_sru_input: String::new(),
+                min_sru_error: String::new(),
+                min_sru: None,
+                node_nru_loads: ContractLoads::default(),
+                name_nru_loads: ContractLoads::default(),
+                node_price_loads: ContractLoads::default(),
+                name_price_loads: ContractLoads::default(),
+                rent_price_loads: ContractLoads::default(),
+                provider_loads: BTreeMap::new(),
+            },
+            contract_details: ContractDetailsPanel {
+                contract_id_input: String::new(),
+                contract_id_error: String::new(),
+                contract_id: None,
+                events_loading: None,
             },
             node_state: NodeStatePanel {
                 node_id_input: String::new(),
@@ -105,13 +488,53 @@ impl UiState {
                 node_id: None,
                 range_start: chrono::NaiveDate::default(),
                 range_end: chrono::NaiveDate::default(),
+                post_period_hours_input: format!("{}", POST_PERIOD_UPTIME_FETCH / SECONDS_IN_HOUR),
+                post_period_hours_error: String::new(),
+                post_period_hours: Some((POST_PERIOD_UPTIME_FETCH / SECONDS_IN_HOUR) as u32),
+                jitter_threshold_input: format!("{ALLOWED_UPTIME_DRIFT}"),
+                jitter_threshold_error: String::new(),
+                jitter_threshold: Some(ALLOWED_UPTIME_DRIFT as u64),
                 node_loading: None,
+                export_error: String::new(),
             },
             total_billed_state: TotalBilledPanel {
                 hours_input: String::new(),
                 hours_error: String::new(),
                 hours: None,
+                bucket: BillBucket::default(),
+                show_discounts: false,
+                hide_zero_amount: false,
                 bills_loading: None,
+                bill_results: BillResults::default(),
+                bills_cancel: CancellationToken::new(),
+            },
+            grace_period_state: GracePeriodPanel {
+                node_id_input: String::new(),
+                twin_id_input: String::new(),
+                node_ids: BTreeSet::new(),
+                twin_ids: BTreeSet::new(),
+                node_id_error: String::new(),
+                twin_id_error: String::new(),
+                watchlist_loading: None,
+            },
+            farm_overview_state: FarmOverviewPanel {
+                farm_id_input: String::new(),
+                farm_id_error: String::new(),
+                farm_id: None,
+                farm_loading: None,
+                nodes_loading: None,
+                summaries_loading: None,
+                counts_loading: None,
+            },
+            twin_overview_state: TwinOverviewPanel {
+                twin_id_input: String::new(),
+                twin_id_error: String::new(),
+                twin_id: None,
+                twin_loading: None,
+                contracts_loading: None,
+                billed_loading: None,
+                report_loading: None,
+                report_error: String::new(),
             },
         }
     }
@@ -123,8 +546,12 @@ impl App for UiState {
             client,
             selected,
             contract_overview,
+            contract_details,
             node_state,
             total_billed_state,
+            grace_period_state,
+            farm_overview_state,
+            twin_overview_state,
         } = self;
 
         #[cfg(not(target_arch = "wasm32"))] // no File->Quit on web pages!
@@ -160,6 +587,9 @@ impl App for UiState {
                 MenuSelection::ContractDetails,
                 MenuSelection::NodeState,
                 MenuSelection::TotalBilled,
+                MenuSelection::GracePeriod,
+                MenuSelection::FarmOverview,
+                MenuSelection::TwinOverview,
             ] {
                 if ui
                     .add(egui::SelectableLabel::new(selected == &me, me.to_string()))
@@ -177,19 +607,47 @@ impl App for UiState {
                         node_id_input,
                         twin_id_input,
                         contract_id_input,
+                        deployment_hash_input,
+                        solution_provider_input,
                         node_ids,
                         twin_ids,
                         contract_ids,
+                        deployment_hashes,
+                        solution_provider_ids,
                         node_id_error,
                         twin_id_error,
                         contract_id_error,
+                        deployment_hash_error,
+                        solution_provider_error,
                         contract_loading,
+                        contract_cancel,
+                        filter_created,
+                        created_after,
+                        created_before,
+                        provider_filtered,
+                        hide_inactive,
+                        search,
+                        group_by_node,
+                        load_costs,
+                        load_nru,
+                        min_cost_input,
+                        min_cost_error,
+                        min_cost,
+                        ip_price_input,
+                        ip_price_error,
+                        ip_price_per_hour,
+                        min_mru_input,
+                        min_mru_error,
+                        min_mru,
+                        min_sru_input,
+                        min_sru_error,
+                        min_sru,
                         node_nru_loads,
                         name_nru_loads,
                         node_price_loads,
                         name_price_loads,
                         rent_price_loads,
-                        trigger_loads,
+                        provider_loads,
                     } = contract_overview;
                     ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
                         // Input elements
@@ -202,47 +660,149 @@ impl App for UiState {
                             contract_id_input,
                             contract_ids,
                         );
-                        if ui.button("Search").clicked() {
-                            let loading = if let Some(promise) = contract_loading {
-                                promise.ready().is_none()
-                            } else {
-                                false
-                            };
-
-                            if !loading {
-                                let client = client.clone();
-                                let node_ids = node_ids.iter().copied().collect::<Vec<_>>();
-                                let twin_ids = twin_ids.iter().copied().collect::<Vec<_>>();
-                                let contract_ids = contract_ids.iter().copied().collect::<Vec<_>>();
-                                *contract_loading = Some(Promise::spawn_async(async move {
-                                    client
-                                        .contracts(
-                                            if node_ids.is_empty() {
-                                                None
-                                            } else {
-                                                Some(&node_ids)
-                                            },
-                                            // Static filter for now
-                                            &[
-                                                ContractState::Created,
-                                                ContractState::GracePeriod,
-                                                ContractState::OutOfFunds,
-                                            ],
-                                            if twin_ids.is_empty() {
-                                                None
-                                            } else {
-                                                Some(&twin_ids)
-                                            },
-                                            &contract_ids,
-                                            &[],
-                                        )
-                                        .await
-                                }));
-                                *trigger_loads = true;
+                        ui_multi_input(
+                            ui,
+                            "Deployment hash:",
+                            deployment_hash_error,
+                            deployment_hash_input,
+                            deployment_hashes,
+                        );
+                        ui_multi_input(
+                            ui,
+                            "Solution provider ID:",
+                            solution_provider_error,
+                            solution_provider_input,
+                            solution_provider_ids,
+                        );
+                        ui.horizontal(|ui| {
+                            ui.checkbox(filter_created, "Created between:");
+                            ui.add_enabled_ui(*filter_created, |ui| {
+                                egui_extras::DatePickerButton::new(created_after)
+                                    .id_source("created_after")
+                                    .ui(ui);
+                                ui.label("and");
+                                egui_extras::DatePickerButton::new(created_before)
+                                    .id_source("created_before")
+                                    .ui(ui);
+                            });
+                        });
+                        ui_tft_input(
+                            ui,
+                            "Min cost (TFT):",
+                            min_cost_error,
+                            min_cost_input,
+                            min_cost,
+                        );
+                        ui_tft_input(
+                            ui,
+                            "IP price (TFT/hour):",
+                            ip_price_error,
+                            ip_price_input,
+                            ip_price_per_hour,
+                        );
+                        ui_min_resource_input(
+                            ui,
+                            "Min MRU (GiB):",
+                            min_mru_error,
+                            min_mru_input,
+                            min_mru,
+                        );
+                        ui_min_resource_input(
+                            ui,
+                            "Min SRU (GiB):",
+                            min_sru_error,
+                            min_sru_input,
+                            min_sru,
+                        );
+                        ui.checkbox(hide_inactive, "Hide inactive").on_hover_text(
+                            "Remove inactive contracts from the current results without searching again",
+                        );
+                        ui.horizontal(|ui| {
+                            let label = ui.label("Search in results:");
+                            ui.text_edit_singleline(search)
+                                .labelled_by(label.id)
+                                .on_hover_text(
+                                    "Only show contracts of which the ID, deployment hash, deployment data, name or solution provider contains this text",
+                                );
+                        });
+                        ui.checkbox(group_by_node, "Group by node").on_hover_text(
+                            "Show the node and rent contracts in a separate table per node",
+                        );
+                        ui.horizontal(|ui| {
+                            ui.checkbox(load_costs, "Load costs").on_hover_text(
+                                "Load the total cost of every shown contract. This sends a request per contract",
+                            );
+                            ui.checkbox(load_nru, "Load NRU").on_hover_text(
+                                "Load the NRU of every shown contract. This sends a request per contract",
+                            );
+                        });
+                        if ui_search_button(ui, "Search", true, is_loading(contract_loading)) {
+                            // Stop fetching the pages of the previous search.
+                            contract_cancel.cancel();
+                            *contract_cancel = CancellationToken::new();
+                            let client = client.with_cancellation(contract_cancel.clone());
+                            let mut filters = ContractFilters::default()
+                                // Static filter for now
+                                .states([
+                                    ContractState::Created,
+                                    ContractState::GracePeriod,
+                                    ContractState::OutOfFunds,
+                                ])
+                                .contract_ids(contract_ids.iter().copied().collect::<Vec<_>>())
+                                .spids(solution_provider_ids.iter().copied().collect::<Vec<_>>());
+                            if !node_ids.is_empty() {
+                                filters = filters.nodes(node_ids.iter().copied().collect::<Vec<_>>());
+                            }
+                            if !twin_ids.is_empty() {
+                                filters = filters.twins(twin_ids.iter().copied().collect::<Vec<_>>());
+                            }
+                            if !deployment_hashes.is_empty() {
+                                filters = filters.deployment_hashes(
+                                    deployment_hashes.iter().cloned().collect::<Vec<_>>(),
+                                );
+                            }
+                            if *filter_created {
+                                filters = filters
+                                    .created_after(
+                                        created_after
+                                            .signed_duration_since(NaiveDate::default())
+                                            .num_seconds(),
+                                    )
+                                    // The end date is included, so the range ends right before
+                                    // the next day.
+                                    .created_before(
+                                        created_before
+                                            .signed_duration_since(NaiveDate::default())
+                                            .num_seconds()
+                                            + 24 * 60 * 60
+                                            - 1,
+                                    );
                             }
+                            *provider_filtered = !solution_provider_ids.is_empty();
+                            *contract_loading = Some(spawn_timed(async move {
+                                client.contracts(&filters).await.map_err(String::from)
+                            }));
+                            // Drop the loads of the previous results, they are loaded again
+                            // for the contracts in the new results.
+                            node_nru_loads.clear();
+                            name_nru_loads.clear();
+                            node_price_loads.clear();
+                            name_price_loads.clear();
+                            rent_price_loads.clear();
                         }
 
                         if let Some(cl) = contract_loading {
+                            // Filtered in place, so new results are filtered as soon as they are
+                            // loaded, and hidden contracts only return after a new search.
+                            if *hide_inactive {
+                                if let Some(Timed {
+                                    value: Ok(contracts),
+                                    ..
+                                }) = cl.ready_mut()
+                                {
+                                    contracts.retain_active();
+                                }
+                            }
                             match cl.ready() {
                                 // todo
                                 None => {
@@ -253,51 +813,76 @@ impl App for UiState {
                                         },
                                     );
                                 }
-                                Some(Err(err)) => {
+                                Some(Timed {
+                                    value: Err(err), ..
+                                }) => {
                                     ui.colored_label(ui.visuals().error_fg_color, err);
                                 }
-                                Some(Ok(contracts)) => {
-                                    if *trigger_loads {
-                                        *node_nru_loads =
-                                            Vec::with_capacity(contracts.node_contracts.len());
-                                        for _ in 0..contracts.node_contracts.len() {
-                                            node_nru_loads.push(None);
-                                        }
-                                        *name_nru_loads =
-                                            Vec::with_capacity(contracts.name_contracts.len());
-                                        for _ in 0..contracts.name_contracts.len() {
-                                            name_nru_loads.push(None);
-                                        }
-                                        *node_price_loads =
-                                            Vec::with_capacity(contracts.node_contracts.len());
-                                        for _ in 0..contracts.node_contracts.len() {
-                                            node_price_loads.push(None);
-                                        }
-                                        *name_price_loads =
-                                            Vec::with_capacity(contracts.name_contracts.len());
-                                        for _ in 0..contracts.name_contracts.len() {
-                                            name_price_loads.push(None);
-                                        }
-                                        *rent_price_loads =
-                                            Vec::with_capacity(contracts.rent_contracts.len());
-                                        for _ in 0..contracts.rent_contracts.len() {
-                                            rent_price_loads.push(None);
-                                        }
-                                        *trigger_loads = false;
-                                    }
+                                Some(Timed {
+                                    value: Ok(contracts),
+                                    elapsed,
+                                }) if contracts.is_empty() => {
+                                    ui.label(fmt_elapsed(*elapsed));
+                                    ui_no_results(ui, "No contracts found for this query");
+                                }
+                                Some(Timed {
+                                    value: Ok(contracts),
+                                    elapsed,
+                                }) => {
+                                    ui.label(fmt_elapsed(*elapsed));
                                     egui::ScrollArea::vertical().show(ui, |ui| {
                                         let nru_loader = |contract_id| {
                                             let client = client.clone();
                                             move || {
+                                                Promise::spawn_async(async move {
+                                                    Ok(total_nru(
+                                                        &client
+                                                            .nru_consumptions(
+                                                                None,
+                                                                None,
+                                                                Some(&[contract_id]),
+                                                            )
+                                                            .await?,
+                                                    ))
+                                                })
+                                            }
+                                        };
+                                        for spid in contracts
+                                            .node_contracts
+                                            .iter()
+                                            .map(|c| c.solution_provider_id)
+                                            .chain(
+                                                contracts
+                                                    .name_contracts
+                                                    .iter()
+                                                    .map(|c| c.solution_provider_id),
+                                            )
+                                            .chain(
+                                                contracts
+                                                    .rent_contracts
+                                                    .iter()
+                                                    .map(|c| c.solution_provider_id),
+                                            )
+                                            .flatten()
+                                        {
+                                            provider_loads.entry(spid).or_insert_with(|| {
+                                                let client = client.clone();
                                                 Promise::spawn_async(async move {
                                                     Ok(client
-                                                        .nru_consumptions(&[contract_id])
+                                                        .solution_providers(&[spid])
                                                         .await?
-                                                        .into_iter()
-                                                        .map(|nru_consumption| nru_consumption.nru)
-                                                        .sum())
+                                                        .pop())
                                                 })
-                                            }
+                                            });
+                                        }
+                                        let options = ContractTableOptions {
+                                            min_cost: *min_cost,
+                                            ip_price_per_hour: *ip_price_per_hour,
+                                            search: search.trim(),
+                                            highlight_provider: *provider_filtered,
+                                            providers: provider_loads,
+                                            load_costs: *load_costs,
+                                            load_nru: *load_nru,
                                         };
                                         let cost_loader = |contract_id| {
                                             let client = client.clone();
@@ -307,7 +892,8 @@ impl App for UiState {
                                                         .contract_bill_reports(
                                                             None,
                                                             None,
-                                                            &[contract_id],
+                                                            Some(&[contract_id]),
+                                                            None,
                                                         )
                                                         .await?
                                                         .into_iter()
@@ -316,15 +902,93 @@ impl App for UiState {
                                                 })
                                             }
                                         };
+                                        // Only contracts of which the NRU is loaded are counted.
+                                        let (node_nru, node_loaded) = node_nru_loads.loaded_total();
+                                        let (name_nru, name_loaded) = name_nru_loads.loaded_total();
+                                        if node_loaded + name_loaded > 0 {
+                                            ui.label(format!(
+                                                "Total network used: {} ({} contracts loaded)",
+                                                fmt_resources(node_nru + name_nru),
+                                                node_loaded + name_loaded,
+                                            ));
+                                        }
+                                        // Contracts without known resources can't be compared, so
+                                        // they are only shown without minimum.
+                                        let node_contracts: Vec<&NodeContract> =
+                                            if min_mru.is_none() && min_sru.is_none() {
+                                                contracts.node_contracts.iter().collect()
+                                            } else {
+                                                contracts.filter_node_resources(|r| {
+                                                    r.mru >= min_mru.unwrap_or_default()
+                                                        && r.sru >= min_sru.unwrap_or_default()
+                                                })
+                                            };
+                                        if ui
+                                            .button("Export view (CSV)")
+                                            .on_hover_text(
+                                                "Export the contracts as shown in the tables, with their cost and NRU if they are loaded",
+                                            )
+                                            .clicked()
+                                        {
+                                            let saved = save_export("contracts.csv", || {
+                                                Ok(export::contracts_csv(&contract_view_rows(
+                                                    &node_contracts,
+                                                    &contracts.name_contracts,
+                                                    &contracts.rent_contracts,
+                                                    ContractViewLoads {
+                                                        node_nru: node_nru_loads,
+                                                        name_nru: name_nru_loads,
+                                                        node_price: node_price_loads,
+                                                        name_price: name_price_loads,
+                                                        rent_price: rent_price_loads,
+                                                    },
+                                                    options,
+                                                    *group_by_node,
+                                                )))
+                                            });
+                                            if let Err(err) = saved {
+                                                log::error!("{err}");
+                                            }
+                                        }
+                                        ui.collapsing("Contract states", |ui| {
+                                            charts::graph_state_distribution(ui, contracts);
+                                        });
                                         ui.collapsing("Node contracts", |ui| {
-                                            ui_node_contracts(
-                                                ui,
-                                                &contracts.node_contracts,
-                                                node_nru_loads,
-                                                node_price_loads,
-                                                nru_loader,
-                                                cost_loader,
-                                            );
+                                            let node_contracts = node_contracts.clone();
+                                            if !*group_by_node {
+                                                ui_node_contracts(
+                                                    ui,
+                                                    &node_contracts,
+                                                    node_nru_loads,
+                                                    node_price_loads,
+                                                    options,
+                                                    nru_loader,
+                                                    cost_loader,
+                                                );
+                                                return;
+                                            }
+                                            for (node_id, node_contracts) in
+                                                group_by_node_id(node_contracts, |c| c.node_id)
+                                            {
+                                                node_group_header(
+                                                    "node_contracts",
+                                                    node_id,
+                                                    &node_contracts,
+                                                    node_price_loads,
+                                                    |c| c.contract_id,
+                                                )
+                                                .show(ui, |ui| {
+                                                    ui_node_contracts(
+                                                        ui,
+                                                        &node_contracts,
+                                                        node_nru_loads,
+                                                        node_price_loads,
+                                                        options,
+                                                        nru_loader,
+                                                        cost_loader,
+                                                    );
+                                                });
+                                            }
                                         });
                                         ui.collapsing("Name contracts", |ui| {
                                             ui_name_contracts(
@@ -332,17 +996,44 @@ impl App for UiState {
                                                 &contracts.name_contracts,
                                                 name_nru_loads,
                                                 name_price_loads,
+                                                options,
                                                 nru_loader,
                                                 cost_loader,
                                             );
                                         });
                                         ui.collapsing("Rent contracts", |ui| {
-                                            ui_rent_contracts(
-                                                ui,
-                                                &contracts.rent_contracts,
-                                                rent_price_loads,
-                                                cost_loader,
-                                            );
+                                            let rent_contracts =
+                                                contracts.rent_contracts.iter().collect::<Vec<_>>();
+                                            if !*group_by_node {
+                                                ui_rent_contracts(
+                                                    ui,
+                                                    &rent_contracts,
+                                                    rent_price_loads,
+                                                    options,
+                                                    cost_loader,
+                                                );
+                                                return;
+                                            }
+                                            for (node_id, rent_contracts) in
+                                                group_by_node_id(rent_contracts, |c| c.node_id)
+                                            {
+                                                node_group_header(
+                                                    "rent_contracts",
+                                                    node_id,
+                                                    &rent_contracts,
+                                                    rent_price_loads,
+                                                    |c| c.contract_id,
+                                                )
+                                                .show(ui, |ui| {
+                                                    ui_rent_contracts(
+                                                        ui,
+                                                        &rent_contracts,
+                                                        rent_price_loads,
+                                                        options,
+                                                        cost_loader,
+                                                    );
+                                                });
+                                            }
                                         });
                                     });
                                 }
@@ -350,6 +1041,48 @@ impl App for UiState {
                         }
                     });
                 }
+                MenuSelection::ContractDetails => {
+                    let ContractDetailsPanel {
+                        contract_id_input,
+                        contract_id_error,
+                        contract_id,
+                        events_loading,
+                    } = contract_details;
+                    ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+                        // Input elements
+                        ui_single_input(
+                            ui,
+                            "Contract ID:",
+                            contract_id_error,
+                            contract_id_input,
+                            contract_id,
+                        );
+                        // only enable button if the contract id input field contains something valid
+                        if ui_search_button(
+                            ui,
+                            "Search",
+                            contract_id.is_some(),
+                            is_loading(events_loading),
+                        ) {
+                            let contract_id = *contract_id.as_ref().unwrap();
+                            let client = client.clone();
+                            *events_loading = Some(spawn_timed(async move {
+                                client
+                                    .contract_events(contract_id)
+                                    .await
+                                    .map_err(String::from)
+                            }));
+                        }
+
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            ui.collapsing("State history", |ui| {
+                                ui_timed_section(ui, events_loading, |ui, events| {
+                                    ui_contract_timeline(ui, events);
+                                });
+                            });
+                        });
+                    });
+                }
                 MenuSelection::NodeState => {
                     let NodeStatePanel {
                         node_id_input,
@@ -357,7 +1090,14 @@ impl App for UiState {
                         node_id,
                         range_start,
                         range_end,
+                        post_period_hours_input,
+                        post_period_hours_error,
+                        post_period_hours,
+                        jitter_threshold_input,
+                        jitter_threshold_error,
+                        jitter_threshold,
                         node_loading,
+                        export_error,
                     } = node_state;
                     ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
                         // Input elements
@@ -374,34 +1114,88 @@ impl App for UiState {
                                 .id_source("end_range")
                                 .ui(ui);
                         });
-                        // only enable button if the node id input field contains something valid
-                        if ui
-                            .add_enabled(node_id.is_some(), egui::Button::new("Search"))
-                            .clicked()
-                        {
-                            let loading = if let Some(promise) = node_loading {
-                                promise.ready().is_none()
-                            } else {
-                                false
-                            };
-                            if !loading {
-                                let client = client.clone();
-                                let start = range_start
-                                    .signed_duration_since(NaiveDate::default())
-                                    .num_seconds();
-                                let end = range_end
-                                    .signed_duration_since(NaiveDate::default())
-                                    .num_seconds();
-                                // we can only reach here if the button is enabled, thus node_id
-                                // is set.
-                                let node_id = *node_id.as_ref().unwrap();
-                                *node_loading = Some(Promise::spawn_async(async move {
-                                    let uptimes = client.uptime_events(node_id, start, end).await?;
-                                    let node_states =
-                                        calculate_node_state_changes(&uptimes, start, end);
-                                    Ok((uptimes, node_states))
-                                }));
-                            }
+                        if let Some(preset) = ui_range_presets(ui) {
+                            (*range_start, *range_end) = preset.dates();
+                        }
+                        ui.collapsing("Advanced", |ui| {
+                            ui_single_input(
+                                ui,
+                                "Uptime lookahead (hours):",
+                                post_period_hours_error,
+                                post_period_hours_input,
+                                post_period_hours,
+                            );
+                            ui.label(
+                                "Uptime events this long after the end of the period are used to determine the state of the node at the end, like minting does",
+                            );
+                        });
+                        // only enable button if the node id and lookahead input fields contain
+                        // something valid
+                        if ui_search_button(
+                            ui,
+                            "Search",
+                            node_id.is_some() && post_period_hours.is_some(),
+                            is_loading(node_loading),
+                        ) {
+                            export_error.clear();
+                            let client = client.clone();
+                            let start = range_start
+                                .signed_duration_since(NaiveDate::default())
+                                .num_seconds();
+                            let end = range_end
+                                .signed_duration_since(NaiveDate::default())
+                                .num_seconds();
+                            // we can only reach here if the button is enabled, thus node_id
+                            // is set.
+                            let node_id = *node_id.as_ref().unwrap();
+                            let lookahead =
+                                i64::from(post_period_hours.unwrap_or_default()) * SECONDS_IN_HOUR;
+                            *node_loading = Some(spawn_timed(async move {
+                                let node_known = !client.nodes_by_id(&[node_id]).await?.is_empty();
+                                let mut uptimes = client
+                                    .uptime_events(node_id, start, end + lookahead)
+                                    .await?;
+                                let missing = missing_uptime(&uptimes, node_known);
+                                let node_states =
+                                    calculate_node_state_changes(&uptimes, start, end);
+                                // The events after the period are only needed for the state at
+                                // the end of the period.
+                                uptimes.retain(|ue| ue.timestamp() <= end);
+                                let downtime = downtime_windows(&node_states, end);
+                                let summary = uptime_summary(&node_states, start, end);
+                                let end_state = state_at(&node_states, end);
+                                let flapping = detect_flapping(
+                                    &node_states,
+                                    FLAPPING_WINDOW,
+                                    FLAPPING_THRESHOLD,
+                                );
+                                // Contracts which are deleted by now might still have been billed
+                                // in the period.
+                                let contract_ids = client
+                                    .contracts(&ContractFilters::default().nodes([node_id]))
+                                    .await?
+                                    .all_contract_ids();
+                                let bills = client
+                                    .contract_bill_reports(
+                                        Some(start),
+                                        Some(end),
+                                        Some(&contract_ids),
+                                        None,
+                                    )
+                                    .await?;
+                                Ok(NodeStateInfo {
+                                    missing_uptime: missing,
+                                    uptime_events: uptimes,
+                                    state_changes: node_states,
+                                    downtime_windows: downtime,
+                                    summary,
+                                    end_state,
+                                    flapping,
+                                    bills,
+                                    start,
+                                    end,
+                                })
+                            }));
                         }
 
                         if let Some(cl) = node_loading {
@@ -415,16 +1209,60 @@ impl App for UiState {
                                         },
                                     );
                                 }
-                                Some(Err(err)) => {
+                                Some(Timed {
+                                    value: Err(err), ..
+                                }) => {
                                     ui.colored_label(ui.visuals().error_fg_color, err);
                                 }
-                                Some(Ok((uptime_events, state_changes))) => {
+                                Some(Timed {
+                                    value: Ok(info),
+                                    elapsed,
+                                }) => {
+                                    ui.label(fmt_elapsed(*elapsed));
+                                    ui_node_state_badge(ui, info);
+                                    if let Some(missing) = info.missing_uptime {
+                                        let (emoji, msg) = missing_uptime_formatted(missing);
+                                        ui.label(format!("{emoji} {msg}"));
+                                    }
+                                    for (start, boots) in &info.flapping {
+                                        ui.colored_label(
+                                            ui.visuals().warn_fg_color,
+                                            format!(
+                                                "{WARNING_EMOJI} Node is flapping: booted {boots} times in {} minutes from {}",
+                                                FLAPPING_WINDOW / 60,
+                                                fmt_local_time(*start)
+                                            ),
+                                        );
+                                    }
+                                    ui_node_state_export(ui, info, export_error);
                                     egui::ScrollArea::vertical().show(ui, |ui| {
                                         ui.collapsing("Node state changes", |ui| {
-                                            ui_node_state_changes(ui, state_changes);
+                                            ui_node_state_changes(ui, &info.state_changes);
                                         });
                                         ui.collapsing("Uptime event jitter", |ui| {
-                                            ui_node_jitter_graph(ui, uptime_events);
+                                            ui_single_input(
+                                                ui,
+                                                "Jitter threshold (seconds):",
+                                                jitter_threshold_error,
+                                                jitter_threshold_input,
+                                                jitter_threshold,
+                                            );
+                                            ui_node_jitter_graph(ui, info, *jitter_threshold);
+                                        });
+                                        ui.collapsing("Uptime heatmap", |ui| {
+                                            charts::graph_uptime_heatmap(
+                                                ui,
+                                                &info.state_changes,
+                                                info.start,
+                                                info.end,
+                                            );
+                                        });
+                                        ui.collapsing("Billing and outages", |ui| {
+                                            charts::graph_bills_with_outages(
+                                                ui,
+                                                &info.bills,
+                                                &info.downtime_windows,
+                                            );
                                         });
                                     });
                                 }
@@ -437,129 +1275,484 @@ impl App for UiState {
                         hours_input,
                         hours_error,
                         hours,
+                        bucket,
+                        show_discounts,
+                        hide_zero_amount,
                         bills_loading,
+                        bill_results,
+                        bills_cancel,
                     } = total_billed_state;
                     ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
                         // Input elements
-                        ui_single_input(ui, "Hours to check:", hours_input, hours_error, hours);
-                        if ui
-                            .add_enabled(hours.is_some(), egui::Button::new("Calculate"))
-                            .clicked()
-                        {
-                            let loading = if let Some(promises) = bills_loading {
-                                promises.iter().any(|p| p.ready().is_none())
-                            } else {
-                                false
-                            };
-                            if !loading {
-                                let client = client.clone();
-
-                                let hours = *hours.as_ref().unwrap();
-                                let end = chrono::offset::Local::now().timestamp();
-                                //let start = end - hours as i64 * 3600;
-
-                                *bills_loading = Some({
-                                    // load bill reports individually per hour
-                                    let mut promises = Vec::with_capacity(hours + 1);
-                                    for i in 0..hours {
-                                        let client = client.clone();
-                                        promises.push(Promise::spawn_async(async move {
-                                            let bills = client
-                                                .clone()
-                                                .contract_bill_reports(
-                                                    Some(end - (3600 * (i + 1)) as i64),
-                                                    Some(end - (3600 * i) as i64),
-                                                    &[],
-                                                )
-                                                .await?;
-                                            Ok(bills)
-                                        }));
-                                    }
+                        ui_single_input(ui, "Hours to check:", hours_error, hours_input, hours);
+                        if let Some(preset) = ui_range_presets(ui) {
+                            let preset_hours = preset.hours();
+                            *hours_input = preset_hours.to_string();
+                            hours_error.clear();
+                            *hours = Some(preset_hours);
+                        }
+                        egui::ComboBox::from_label("Group bills per")
+                            .selected_text(bucket.to_string())
+                            .show_ui(ui, |ui| {
+                                for option in BillBucket::ALL {
+                                    ui.selectable_value(bucket, option, option.to_string());
+                                }
+                            });
+                        ui.checkbox(show_discounts, "Show discount levels").on_hover_text(
+                            "Show the share of every discount level in the amount billed. A twin which drops to a lower level is running low on funds",
+                        );
+                        ui.checkbox(hide_zero_amount, "Exclude zero-amount reports").on_hover_text(
+                            "Leave bill reports in which nothing was billed out of the graphs, e.g. for fully discounted contracts",
+                        );
+                        let calculating = bills_loading
+                            .as_ref()
+                            .is_some_and(|promises| promises.iter().any(|p| p.ready().is_none()));
+                        if ui_search_button(ui, "Calculate", hours.is_some(), calculating) {
+                            // Stop fetching the bills of the previous calculation.
+                            bills_cancel.cancel();
+                            *bills_cancel = CancellationToken::new();
+                            let client = client.with_cancellation(bills_cancel.clone());
 
-                                    promises
-                                });
-                            }
+                            let hours = *hours.as_ref().unwrap();
+                            let end = chrono::offset::Local::now().timestamp();
+                            //let start = end - hours as i64 * 3600;
+
+                            *bill_results = BillResults::new(hours);
+                            *bills_loading = Some({
+                                // load bill reports individually per hour
+                                let mut promises = Vec::with_capacity(hours + 1);
+                                for i in 0..hours {
+                                    let client = client.clone();
+                                    promises.push(spawn_timed(async move {
+                                        let bills = client
+                                            .clone()
+                                            .contract_bill_reports(
+                                                Some(end - (3600 * (i + 1)) as i64),
+                                                Some(end - (3600 * i) as i64),
+                                                None,
+                                                None,
+                                            )
+                                            .await?;
+                                        Ok(bills)
+                                    }));
+                                }
+
+                                promises
+                            });
                         }
 
                         if let Some(promises) = bills_loading {
                             let mut err = None;
-                            let mut one_ready = false;
-                            let mut ready_vals = Vec::with_capacity(promises.len());
-                            for promise in promises {
+                            for (idx, promise) in promises.iter().enumerate() {
                                 match promise.ready() {
                                     None => {
                                         continue;
                                     }
-                                    Some(Err(e)) => {
+                                    Some(Timed { value: Err(e), .. }) => {
                                         err = Some(e);
                                         break;
                                     }
-                                    Some(Ok(value)) => {
-                                        one_ready = true;
-                                        ready_vals.push(value);
+                                    Some(Timed {
+                                        value: Ok(value),
+                                        elapsed,
+                                    }) => {
+                                        bill_results.fold(idx, value, *elapsed);
                                     }
                                 }
                             }
 
                             if let Some(e) = err {
                                 ui.colored_label(ui.visuals().error_fg_color, e);
-                            } else if !one_ready {
-                                ui.with_layout(
-                                    Layout::centered_and_justified(egui::Direction::TopDown),
-                                    |ui| {
-                                        ui.spinner();
-                                    },
-                                );
                             } else {
-                                egui::ScrollArea::vertical().show(ui, |ui| {
-                                    ui_bill_graph(
-                                        ui,
-                                        &ready_vals
-                                            .into_iter()
-                                            .flatten()
-                                            .copied()
-                                            .collect::<Vec<ContractBillReport>>(),
+                                let (done, total) = (bill_results.done, promises.len());
+                                if done == total {
+                                    ui.label(fmt_elapsed(bill_results.elapsed));
+                                } else {
+                                    ui.add(
+                                        egui::ProgressBar::new(done as f32 / total as f32)
+                                            .text(format!("{done}/{total} hours loaded")),
                                     );
-                                });
+                                }
+                                // Render partial results as soon as some are in.
+                                if done == total && bill_results.reports.is_empty() {
+                                    ui_no_results(ui, "No bills found in this period");
+                                } else if done > 0 {
+                                    if *hide_zero_amount && bill_results.zero_amount > 0 {
+                                        ui.label(format!(
+                                            "{} zero-amount reports excluded",
+                                            bill_results.zero_amount
+                                        ));
+                                    }
+                                    let graphs = bill_results.graphs(*hide_zero_amount, *bucket);
+                                    egui::ScrollArea::vertical().show(ui, |ui| {
+                                        ui_bill_graph(ui, &graphs.hourly, *bucket);
+                                        if *show_discounts {
+                                            charts::graph_discount_shares(
+                                                ui,
+                                                &graphs.discounts,
+                                                *bucket,
+                                            );
+                                        }
+                                        ui.collapsing("Top billed contracts", |ui| {
+                                            charts::graph_top_contracts(ui, &graphs.top_contracts);
+                                        });
+                                    });
+                                }
                             }
                         }
                     });
                 }
-                _ => (),
-            }
-        });
-    }
-}
+                MenuSelection::GracePeriod => {
+                    let GracePeriodPanel {
+                        node_id_input,
+                        twin_id_input,
+                        node_ids,
+                        twin_ids,
+                        node_id_error,
+                        twin_id_error,
+                        watchlist_loading,
+                    } = grace_period_state;
+                    ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+                        // Input elements
+                        ui_multi_input(ui, "Node ID:", node_id_error, node_id_input, node_ids);
+                        ui_multi_input(ui, "Twin ID:", twin_id_error, twin_id_input, twin_ids);
+                        if ui_search_button(ui, "Search", true, is_loading(watchlist_loading)) {
+                            let client = client.clone();
+                            let mut filters =
+                                ContractFilters::default().states([ContractState::GracePeriod]);
+                            if !node_ids.is_empty() {
+                                filters = filters.nodes(node_ids.iter().copied().collect::<Vec<_>>());
+                            }
+                            if !twin_ids.is_empty() {
+                                filters = filters.twins(twin_ids.iter().copied().collect::<Vec<_>>());
+                            }
+                            *watchlist_loading = Some(spawn_timed(async move {
+                                let contracts = client.contracts(&filters).await?;
+                                let contract_ids = contracts.all_contract_ids();
+                                let bills = client
+                                    .contract_bill_reports(None, None, Some(&contract_ids), None)
+                                    .await?;
+                                Ok(grace_period_watchlist(&contracts, &bills))
+                            }));
+                        }
 
-fn ui_node_contracts<C, N>(
-    ui: &mut egui::Ui,
-    node_contracts: &[NodeContract],
-    nru_loads: &mut [Option<Promise<Result<u64, String>>>],
-    node_price_loads: &mut [Option<Promise<Result<u64, String>>>],
-    nru_loader: impl Fn(u64) -> N,
-    cost_loader: impl Fn(u64) -> C,
-) where
-    N: FnOnce() -> Promise<Result<u64, String>>,
-    C: FnOnce() -> Promise<Result<u64, String>>,
-{
-    egui::ScrollArea::horizontal().show(ui, |ui| {
-        TableBuilder::new(ui)
-            .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
-            .columns(Column::auto().resizable(true).clip(false), 14)
-            .column(Column::remainder().clip(false).at_most(100.))
-            .striped(true)
-            .header(50.0, |mut header| {
-                for title in [
-                    "Contract ID",
-                    "Node ID",
-                    "Twin ID",
-                    "Solution Provider ID",
+                        if let Some(wl) = watchlist_loading {
+                            match wl.ready() {
+                                None => {
+                                    ui.with_layout(
+                                        Layout::centered_and_justified(egui::Direction::TopDown),
+                                        |ui| {
+                                            ui.spinner();
+                                        },
+                                    );
+                                }
+                                Some(Timed {
+                                    value: Err(err), ..
+                                }) => {
+                                    ui.colored_label(ui.visuals().error_fg_color, err);
+                                }
+                                Some(Timed {
+                                    value: Ok(watchlist),
+                                    elapsed,
+                                }) => {
+                                    ui.label(fmt_elapsed(*elapsed));
+                                    egui::ScrollArea::vertical().show(ui, |ui| {
+                                        ui_grace_period_watchlist(ui, watchlist);
+                                    });
+                                }
+                            }
+                        }
+                    });
+                }
+                MenuSelection::FarmOverview => {
+                    let FarmOverviewPanel {
+                        farm_id_input,
+                        farm_id_error,
+                        farm_id,
+                        farm_loading,
+                        nodes_loading,
+                        summaries_loading,
+                        counts_loading,
+                    } = farm_overview_state;
+                    ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+                        // Input elements
+                        ui_single_input(ui, "Farm ID:", farm_id_error, farm_id_input, farm_id);
+                        // only enable button if the farm id input field contains something valid
+                        let loading = is_loading(farm_loading)
+                            || is_loading(nodes_loading)
+                            || is_loading(summaries_loading)
+                            || is_loading(counts_loading);
+                        if ui_search_button(ui, "Search", farm_id.is_some(), loading) {
+                            let client = client.clone();
+                            let farm_id = *farm_id.as_ref().unwrap();
+                            *nodes_loading = None;
+                            *summaries_loading = None;
+                            *counts_loading = None;
+                            *farm_loading = Some(spawn_timed(async move {
+                                client
+                                    .farms(&[farm_id])
+                                    .await?
+                                    .pop()
+                                    .ok_or_else(|| format!("Farm {farm_id} not found"))
+                            }));
+                        }
+
+                        // Start the next stage once the previous one is loaded.
+                        if let (
+                            Some(Timed {
+                                value: Ok(farm), ..
+                            }),
+                            None,
+                        ) = (farm_loading.as_ref().and_then(|p| p.ready()), &nodes_loading)
+                        {
+                            let client = client.clone();
+                            let farm_id = farm.farm_id;
+                            *nodes_loading =
+                                Some(spawn_timed(
+                                    async move { client.nodes(&[farm_id]).await.map_err(String::from) },
+                                ));
+                        }
+                        if let (
+                            Some(Timed {
+                                value: Ok(nodes), ..
+                            }),
+                            None,
+                        ) = (nodes_loading.as_ref().and_then(|p| p.ready()), &summaries_loading)
+                        {
+                            let node_ids = nodes.iter().map(|n| n.node_id).collect::<Vec<_>>();
+                            let counts_client = client.clone();
+                            let counts_node_ids = node_ids.clone();
+                            *counts_loading = Some(spawn_timed(async move {
+                                counts_client
+                                    .node_contract_counts(&counts_node_ids)
+                                    .await
+                                    .map_err(String::from)
+                            }));
+                            let client = client.clone();
+                            *summaries_loading = Some(spawn_timed(async move {
+                                // An empty node filter would fetch the contracts on all nodes.
+                                if node_ids.is_empty() {
+                                    return Ok(BTreeMap::new());
+                                }
+                                let contracts = client
+                                    .contracts(&ContractFilters::default().nodes(node_ids.as_slice()))
+                                    .await?;
+                                let contract_ids = contracts
+                                    .node_contracts
+                                    .iter()
+                                    .map(|c| c.contract_id)
+                                    .chain(contracts.rent_contracts.iter().map(|c| c.contract_id))
+                                    .collect::<Vec<_>>();
+                                let bills = client
+                                    .contract_bill_reports(None, None, Some(&contract_ids), None)
+                                    .await?;
+                                Ok(node_summaries(&node_ids, &contracts, &bills))
+                            }));
+                        }
+
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            ui.collapsing("Farm", |ui| {
+                                ui_timed_section(ui, farm_loading, |ui, farm| {
+                                    ui.label(format!("Name: {}", farm.name));
+                                    ui.horizontal(|ui| {
+                                        ui.label("Farmer twin ID:");
+                                        ui_copyable_label(
+                                            ui,
+                                            &farm.twin_id.to_string(),
+                                            "Click to copy the twin ID",
+                                        );
+                                    });
+                                    if let Some(Timed {
+                                        value: Ok(summaries),
+                                        ..
+                                    }) = summaries_loading.as_ref().and_then(|p| p.ready())
+                                    {
+                                        ui.label(format!(
+                                            "Total revenue: {}",
+                                            fmt_tft(summaries.values().map(|s| s.billed).sum())
+                                        ));
+                                    }
+                                });
+                            });
+                            ui.collapsing("Nodes", |ui| {
+                                ui_timed_section(ui, nodes_loading, |ui, nodes| {
+                                    ui_timed_section(ui, counts_loading, |ui, counts| {
+                                        let summaries = summaries_loading
+                                            .as_ref()
+                                            .and_then(|p| p.ready())
+                                            .map(|timed| &timed.value);
+                                        if let Some(Err(e)) = summaries {
+                                            ui.colored_label(ui.visuals().error_fg_color, e);
+                                        }
+                                        ui_farm_nodes(ui, nodes, counts, summaries);
+                                    });
+                                });
+                            });
+                        });
+                    });
+                }
+                MenuSelection::TwinOverview => {
+                    let TwinOverviewPanel {
+                        twin_id_input,
+                        twin_id_error,
+                        twin_id,
+                        twin_loading,
+                        contracts_loading,
+                        billed_loading,
+                        report_loading,
+                        report_error,
+                    } = twin_overview_state;
+                    ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+                        // Input elements
+                        ui_single_input(ui, "Twin ID:", twin_id_error, twin_id_input, twin_id);
+                        // only enable button if the twin id input field contains something valid
+                        let loading = is_loading(twin_loading)
+                            || is_loading(contracts_loading)
+                            || is_loading(billed_loading)
+                            || report_loading.is_some();
+                        if ui_search_button(ui, "Search", twin_id.is_some(), loading) {
+                            let twin_id = *twin_id.as_ref().unwrap();
+                            let twin_client = client.clone();
+                            *twin_loading = Some(spawn_timed(async move {
+                                twin_client
+                                    .twins(&[twin_id])
+                                    .await?
+                                    .pop()
+                                    .ok_or_else(|| format!("Twin {twin_id} not found"))
+                            }));
+                            let contracts_client = client.clone();
+                            *contracts_loading = Some(spawn_timed(async move {
+                                contracts_client
+                                    .contracts(&ContractFilters::default().twins([twin_id]))
+                                    .await
+                                    .map_err(String::from)
+                            }));
+                            let billed_client = client.clone();
+                            *billed_loading = Some(spawn_timed(async move {
+                                Ok(billed_client
+                                    .billing_by_twin(&[twin_id], None, None)
+                                    .await?
+                                    .remove(&twin_id)
+                                    .unwrap_or_default())
+                            }));
+                        }
+                        ui_twin_report_export(ui, client, *twin_id, report_loading, report_error);
+
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            ui.collapsing("Account", |ui| {
+                                ui_timed_section(ui, twin_loading, |ui, twin| {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Account:");
+                                        ui_copyable_label(
+                                            ui,
+                                            &twin.account_id,
+                                            "Click to copy the account address",
+                                        );
+                                    });
+                                    ui.label(format!(
+                                        "Relay: {}",
+                                        twin.relay.as_deref().unwrap_or("-")
+                                    ));
+                                });
+                            });
+                            ui.collapsing("Total billed", |ui| {
+                                ui_timed_section(ui, billed_loading, |ui, billed| {
+                                    ui.label(fmt_tft(*billed));
+                                });
+                            });
+                            ui.collapsing("Contracts", |ui| {
+                                ui_timed_section(ui, contracts_loading, |ui, contracts| {
+                                    ui_twin_contracts(ui, contracts);
+                                });
+                            });
+                        });
+                    });
+                }
+            }
+        });
+    }
+}
+
+/// Show a resource used by a node contract. Contracts which don't use any resources are shown as
+/// idle, contracts of which the resources are unknown are shown as a dash.
+fn ui_used_resource(
+    ui: &mut egui::Ui,
+    resources: Option<&Resources>,
+    format: impl Fn(&Resources) -> String,
+) {
+    match resources {
+        None => {
+            ui.label("-")
+                .on_hover_text("The resources used by this contract are unknown");
+        }
+        Some(res) if res.is_zero() => {
+            ui.weak("idle")
+                .on_hover_text("This contract does not use any resources");
+        }
+        Some(res) => {
+            ui.label(format(res));
+        }
+    }
+}
+
+fn ui_node_contracts<C, N>(
+    ui: &mut egui::Ui,
+    node_contracts: &[&NodeContract],
+    nru_loads: &mut ContractLoads,
+    node_price_loads: &mut ContractLoads,
+    options: ContractTableOptions,
+    nru_loader: impl Fn(u64) -> N,
+    cost_loader: impl Fn(u64) -> C,
+) where
+    N: FnOnce() -> Promise<Result<u64, String>>,
+    C: FnOnce() -> Promise<Result<u64, String>>,
+{
+    let visible = node_contracts
+        .iter()
+        .copied()
+        .filter(|contract| {
+            contract_visible(
+                node_price_loads.get(contract.contract_id),
+                contract.matches_text(options.search),
+                contract.solution_provider_id,
+                options,
+            )
+        })
+        .collect::<Vec<_>>();
+    let totals = total_resources(visible.iter().copied());
+    ui.label(format!(
+        "Total used: {} CRU, {} MRU, {} SRU, {} HRU",
+        totals.used.cru,
+        fmt_resources(totals.used.mru),
+        fmt_resources(totals.used.sru),
+        fmt_resources(totals.used.hru),
+    ));
+    if totals.idle > 0 || totals.unknown > 0 {
+        ui.label(format!(
+            "{} contracts are idle, the resources of {} contracts are unknown and not counted",
+            totals.idle, totals.unknown
+        ));
+    }
+    let period = Period::current();
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        TableBuilder::new(ui)
+            .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
+            .columns(Column::auto().resizable(true).clip(false), 15)
+            .column(Column::remainder().clip(false).at_most(100.))
+            .striped(true)
+            .header(50.0, |mut header| {
+                for title in [
+                    "Contract ID",
+                    "Node ID",
+                    "Twin ID",
+                    SOLUTION_PROVIDER_COLUMN,
                     "Cru",
                     "Mru",
                     "Sru",
                     "Hru",
                     "Nru",
                     "Public IPs",
+                    "IP Cost",
                     "Total Cost",
                     "Deployment Hash",
                     "Deployment Data",
@@ -567,295 +1760,1090 @@ fn ui_node_contracts<C, N>(
                     "State",
                 ] {
                     header.col(|ui| {
-                        ui.heading(title);
+                        ui_contract_heading(ui, title, options);
                     });
                 }
             })
             .body(|body| {
-                body.rows(30.0, node_contracts.len(), |row_idx, mut row| {
-                    let contract = &node_contracts[row_idx];
+                body.rows(30.0, visible.len(), |row_idx, mut row| {
+                    let contract = visible[row_idx];
                     row.col(|ui| {
-                        ui.label(format!("{}", contract.contract_id));
+                        let contract_id = format!("{}", contract.contract_id);
+                        ui_copyable_label(ui, &contract_id, "Click to copy the contract ID");
                     });
                     row.col(|ui| {
                         ui.label(format!("{}", contract.node_id));
                     });
                     row.col(|ui| {
-                        if ui.label(format!("{}", contract.twin_id)).hovered() {
-                            egui::show_tooltip(
-                                ui.ctx(),
-                                egui::Id::new("contract_twin_id_tooltip"),
-                                |ui| {
-                                    ui.label(format!(
-                                        "This contract is created and owned by twin {}",
-                                        contract.twin_id
-                                    ));
-                                },
-                            );
-                        };
+                        ui_copyable_label(
+                            ui,
+                            &format!("{}", contract.twin_id),
+                            &format!(
+                                "This contract is created and owned by twin {}, click to copy",
+                                contract.twin_id
+                            ),
+                        );
                     });
                     row.col(|ui| {
-                        ui.label(format!("{}", contract.solution_provider_id.unwrap_or(0)));
+                        ui_solution_provider(ui, contract.solution_provider_id, options);
                     });
                     row.col(|ui| {
-                        ui.label(if let Some(ref res) = contract.resources_used {
+                        ui_used_resource(ui, contract.resources_used.as_ref(), |res| {
                             format!("{}", res.cru)
-                        } else {
-                            "-".to_string()
                         });
                     });
                     row.col(|ui| {
-                        ui.label(if let Some(ref res) = contract.resources_used {
+                        ui_used_resource(ui, contract.resources_used.as_ref(), |res| {
                             fmt_resources(res.mru)
-                        } else {
-                            "-".to_string()
                         });
                     });
                     row.col(|ui| {
-                        ui.label(if let Some(ref res) = contract.resources_used {
+                        ui_used_resource(ui, contract.resources_used.as_ref(), |res| {
                             fmt_resources(res.sru)
-                        } else {
-                            "-".to_string()
                         });
                     });
                     row.col(|ui| {
-                        ui.label(if let Some(ref res) = contract.resources_used {
+                        ui_used_resource(ui, contract.resources_used.as_ref(), |res| {
                             fmt_resources(res.hru)
+                        });
+                    });
+                    row.col(|ui| {
+                        ui_contract_load(
+                            ui,
+                            nru_loads,
+                            contract.contract_id,
+                            options.load_nru,
+                            fmt_resources,
+                        );
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{}", contract.number_of_public_ips));
+                    });
+                    row.col(|ui| {
+                        match options.ip_price_per_hour {
+                            Some(price) if contract.state != ContractState::Deleted => ui
+                                .label(fmt_tft_compact(ip_cost(contract, &period, price)))
+                                .on_hover_text(
+                                    "Estimated cost of the public IPs in the current period, based on the IP price",
+                                ),
+                            _ => ui.label("\u{2014}").on_hover_text(
+                                "Set an IP price to estimate the cost of the public IPs of active contracts",
+                            ),
+                        };
+                    });
+                    row.col(|ui| {
+                        ui_contract_load(
+                            ui,
+                            node_price_loads,
+                            contract.contract_id,
+                            options.load_costs,
+                            fmt_tft_compact,
+                        );
+                    });
+                    row.col(|ui| {
+                        ui_copyable_label(
+                            ui,
+                            &contract.deployment_hash,
+                            "Click to copy the deployment hash",
+                        );
+                    });
+                    row.col(|ui| {
+                        if ui
+                            .label(
+                                if let Some(data) = parse_deployment_data(&contract.deployment_data)
+                                {
+                                    data.to_string()
+                                } else {
+                                    contract.deployment_data.chars().take(30).collect()
+                                },
+                            )
+                            .hovered()
+                        {
+                            egui::show_tooltip(
+                                ui.ctx(),
+                                egui::Id::new("contract_deployment_data_tooltip"),
+                                |ui| {
+                                    ui.label(if contract.deployment_data.is_empty() {
+                                        "No contract data set on chain for this contract"
+                                    } else {
+                                        &contract.deployment_data
+                                    });
+                                },
+                            );
+                        };
+                    });
+                    row.col(|ui| {
+                        ui.label(fmt_local_time(contract.created_at));
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{}", contract.state));
+                    });
+                });
+            });
+    });
+    nru_loads.start_wanted(&nru_loader);
+    node_price_loads.start_wanted(&cost_loader);
+}
+
+fn ui_name_contracts<C, N>(
+    ui: &mut egui::Ui,
+    name_contracts: &[NameContract],
+    nru_loads: &mut ContractLoads,
+    name_price_loads: &mut ContractLoads,
+    options: ContractTableOptions,
+    nru_loader: impl Fn(u64) -> N,
+    cost_loader: impl Fn(u64) -> C,
+) where
+    C: FnOnce() -> Promise<Result<u64, String>>,
+    N: FnOnce() -> Promise<Result<u64, String>>,
+{
+    let visible = name_contracts
+        .iter()
+        .filter(|contract| {
+            contract_visible(
+                name_price_loads.get(contract.contract_id),
+                contract.matches_text(options.search),
+                contract.solution_provider_id,
+                options,
+            )
+        })
+        .collect::<Vec<_>>();
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        TableBuilder::new(ui)
+            .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
+            .columns(Column::auto().resizable(true).clip(false), 7)
+            .column(Column::remainder().clip(false).at_most(100.))
+            .striped(true)
+            .header(50.0, |mut header| {
+                for title in [
+                    "Contract ID",
+                    "Twin ID",
+                    SOLUTION_PROVIDER_COLUMN,
+                    "Name",
+                    "Nru",
+                    "Total Cost",
+                    "Created",
+                    "State",
+                ] {
+                    header.col(|ui| {
+                        ui_contract_heading(ui, title, options);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(30.0, visible.len(), |row_idx, mut row| {
+                    let contract = visible[row_idx];
+                    row.col(|ui| {
+                        let contract_id = format!("{}", contract.contract_id);
+                        ui_copyable_label(ui, &contract_id, "Click to copy the contract ID");
+                    });
+                    row.col(|ui| {
+                        ui_copyable_label(
+                            ui,
+                            &format!("{}", contract.twin_id),
+                            &format!(
+                                "This contract is created and owned by twin {}, click to copy",
+                                contract.twin_id
+                            ),
+                        );
+                    });
+                    row.col(|ui| {
+                        ui_solution_provider(ui, contract.solution_provider_id, options);
+                    });
+                    row.col(|ui| {
+                        ui.label(&contract.name);
+                    });
+                    row.col(|ui| {
+                        ui_contract_load(
+                            ui,
+                            nru_loads,
+                            contract.contract_id,
+                            options.load_nru,
+                            fmt_resources,
+                        );
+                    });
+                    row.col(|ui| {
+                        ui_contract_load(
+                            ui,
+                            name_price_loads,
+                            contract.contract_id,
+                            options.load_costs,
+                            fmt_tft_compact,
+                        );
+                    });
+                    row.col(|ui| {
+                        ui.label(fmt_local_time(contract.created_at));
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{}", contract.state));
+                    });
+                });
+            });
+    });
+    nru_loads.start_wanted(&nru_loader);
+    name_price_loads.start_wanted(&cost_loader);
+}
+
+fn ui_rent_contracts<C>(
+    ui: &mut egui::Ui,
+    rent_contracts: &[&RentContract],
+    rent_price_loads: &mut ContractLoads,
+    options: ContractTableOptions,
+    cost_loader: impl Fn(u64) -> C,
+) where
+    C: FnOnce() -> Promise<Result<u64, String>>,
+{
+    let visible = rent_contracts
+        .iter()
+        .copied()
+        .filter(|contract| {
+            contract_visible(
+                rent_price_loads.get(contract.contract_id),
+                contract.matches_text(options.search),
+                contract.solution_provider_id,
+                options,
+            )
+        })
+        .collect::<Vec<_>>();
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        TableBuilder::new(ui)
+            .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
+            .columns(Column::auto().resizable(true).clip(false), 6)
+            .column(Column::remainder().clip(false).at_most(100.))
+            .striped(true)
+            .header(50.0, |mut header| {
+                for title in [
+                    "Contract ID",
+                    "Node ID",
+                    "Twin ID",
+                    SOLUTION_PROVIDER_COLUMN,
+                    "Total Cost",
+                    "Created",
+                    "State",
+                ] {
+                    header.col(|ui| {
+                        ui_contract_heading(ui, title, options);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(30.0, visible.len(), |row_idx, mut row| {
+                    let contract = visible[row_idx];
+                    row.col(|ui| {
+                        let contract_id = format!("{}", contract.contract_id);
+                        ui_copyable_label(ui, &contract_id, "Click to copy the contract ID");
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{}", contract.node_id));
+                    });
+                    row.col(|ui| {
+                        ui_copyable_label(
+                            ui,
+                            &format!("{}", contract.twin_id),
+                            &format!(
+                                "This contract is created and owned by twin {}, click to copy",
+                                contract.twin_id
+                            ),
+                        );
+                    });
+                    row.col(|ui| {
+                        ui_solution_provider(ui, contract.solution_provider_id, options);
+                    });
+                    row.col(|ui| {
+                        ui_contract_load(
+                            ui,
+                            rent_price_loads,
+                            contract.contract_id,
+                            options.load_costs,
+                            fmt_tft_compact,
+                        );
+                    });
+                    row.col(|ui| {
+                        ui.label(fmt_local_time(contract.created_at));
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{}", contract.state));
+                    });
+                });
+            });
+    });
+    rent_price_loads.start_wanted(&cost_loader);
+}
+
+/// Group contracts by the node they are on, in order of node ID. The order of the contracts on a
+/// node is kept.
+fn group_by_node_id<T>(contracts: Vec<T>, node_id: impl Fn(&T) -> u32) -> BTreeMap<u32, Vec<T>> {
+    let mut groups = BTreeMap::<u32, Vec<T>>::new();
+    for contract in contracts {
+        groups.entry(node_id(&contract)).or_default().push(contract);
+    }
+    groups
+}
+
+/// Collapsible header for the contracts on a single node, with the amount of contracts and the
+/// summed cost of the contracts for which the cost is loaded.
+fn node_group_header<T>(
+    table: &str,
+    node_id: u32,
+    contracts: &[T],
+    price_loads: &ContractLoads,
+    contract_id: impl Fn(&T) -> u64,
+) -> egui::CollapsingHeader {
+    let (cost, loaded) = price_loads.loaded_sum(contracts.iter().map(contract_id));
+    let cost = match loaded {
+        0 => String::from("cost not loaded"),
+        n if n == contracts.len() => fmt_tft(cost),
+        n => format!("{} for {n} loaded", fmt_tft(cost)),
+    };
+    egui::CollapsingHeader::new(format!(
+        "Node {node_id}: {} contracts, {cost}",
+        contracts.len()
+    ))
+    .id_source((table, node_id))
+}
+
+fn ui_twin_contracts(ui: &mut egui::Ui, contracts: &Contracts) {
+    if contracts.is_empty() {
+        ui_no_results(ui, "No contracts found for this twin");
+        return;
+    }
+    // (contract ID, contract type, node ID, created at, state)
+    let rows = contracts
+        .node_contracts
+        .iter()
+        .map(|c| {
+            (
+                c.contract_id,
+                "Node",
+                Some(c.node_id),
+                c.created_at,
+                &c.state,
+            )
+        })
+        .chain(
+            contracts
+                .name_contracts
+                .iter()
+                .map(|c| (c.contract_id, "Name", None, c.created_at, &c.state)),
+        )
+        .chain(contracts.rent_contracts.iter().map(|c| {
+            (
+                c.contract_id,
+                "Rent",
+                Some(c.node_id),
+                c.created_at,
+                &c.state,
+            )
+        }))
+        .collect::<Vec<_>>();
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        TableBuilder::new(ui)
+            .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
+            .columns(Column::auto().resizable(true).clip(false), 4)
+            .column(Column::remainder().clip(false).at_most(100.))
+            .striped(true)
+            .header(50.0, |mut header| {
+                for title in ["Contract ID", "Type", "Node ID", "Created", "State"] {
+                    header.col(|ui| {
+                        ui.heading(title);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(30.0, rows.len(), |row_idx, mut row| {
+                    let (contract_id, contract_type, node_id, created_at, state) = rows[row_idx];
+                    row.col(|ui| {
+                        let contract_id = format!("{contract_id}");
+                        ui_copyable_label(ui, &contract_id, "Click to copy the contract ID");
+                    });
+                    row.col(|ui| {
+                        ui.label(contract_type);
+                    });
+                    row.col(|ui| {
+                        ui.label(if let Some(node_id) = node_id {
+                            format!("{node_id}")
                         } else {
                             "-".to_string()
                         });
                     });
                     row.col(|ui| {
-                        let nru_load =
-                            nru_loads[row_idx].get_or_insert_with(nru_loader(contract.contract_id));
-                        match nru_load.ready() {
-                            Some(Ok(nru)) => ui.label(fmt_resources(*nru)),
-                            Some(Err(err)) => ui.colored_label(ui.visuals().error_fg_color, err),
-                            None => ui.spinner(),
-                        };
+                        ui.label(fmt_local_time(created_at));
                     });
                     row.col(|ui| {
-                        ui.label(format!("{}", contract.number_of_public_ips));
+                        ui.label(format!("{state}"));
+                    });
+                });
+            });
+    });
+}
+
+/// Display options shared by the contract tables in the contract overview.
+#[derive(Clone, Copy)]
+struct ContractTableOptions<'a> {
+    /// Hide contracts which cost less than this amount, once their cost is loaded.
+    min_cost: Option<u64>,
+    /// Price of a single public IP for an hour. The IP cost of node contracts is only estimated
+    /// if this is set.
+    ip_price_per_hour: Option<u64>,
+    /// Only show contracts which contain this text.
+    search: &'a str,
+    /// Highlight the solution provider column, since the contracts are filtered on it.
+    highlight_provider: bool,
+    /// Solution providers to show instead of their IDs, once loaded.
+    providers: &'a ProviderLoads,
+    /// Load the cost of every shown contract. Otherwise costs are only loaded on request.
+    load_costs: bool,
+    /// Load the NRU of every shown contract. Otherwise NRU is only loaded on request.
+    load_nru: bool,
+}
+
+/// Show a value which is loaded separately for every contract, e.g. the cost. If `load_all` is not
+/// set, the value is only loaded once the user requests it with the button shown in its place.
+fn ui_contract_load(
+    ui: &mut egui::Ui,
+    loads: &mut ContractLoads,
+    contract_id: u64,
+    load_all: bool,
+    format: impl Fn(u64) -> String,
+) {
+    if !load_all && !loads.is_requested(contract_id) {
+        ui.horizontal(|ui| {
+            ui.label("\u{2014}");
+            if ui.small_button("Load").clicked() {
+                loads.request_by_user(contract_id);
+            }
+        });
+        return;
+    }
+    match loads.request(contract_id).and_then(Promise::ready) {
+        Some(Ok(value)) => ui.label(format(*value)),
+        Some(Err(err)) => ui.colored_label(ui.visuals().error_fg_color, err),
+        None => ui.spinner(),
+    };
+}
+
+/// Title of the solution provider column in the contract tables.
+const SOLUTION_PROVIDER_COLUMN: &str = "Solution Provider ID";
+
+fn ui_contract_heading(ui: &mut egui::Ui, title: &str, options: ContractTableOptions) {
+    if options.highlight_provider && title == SOLUTION_PROVIDER_COLUMN {
+        ui.heading(
+            RichText::new(title)
+                .color(ui.visuals().strong_text_color())
+                .underline(),
+        );
+    } else {
+        ui.heading(title);
+    }
+}
+
+/// Show the name of a solution provider once it is loaded, with the ID on hover. Until then, or if
+/// the provider can't be loaded, the ID is shown instead. Contracts without a solution provider
+/// show a dash, so they are not mistaken for contracts of provider 0.
+fn ui_solution_provider(ui: &mut egui::Ui, spid: Option<u32>, options: ContractTableOptions) {
+    let Some(spid) = spid else {
+        ui.label("\u{2014}")
+            .on_hover_text("This contract has no solution provider");
+        return;
+    };
+    let provider = options
+        .providers
+        .get(&spid)
+        .and_then(|load| load.ready())
+        .and_then(|provider| provider.as_ref().ok())
+        .and_then(|provider| provider.as_ref());
+    let text = RichText::new(match provider {
+        Some(provider) => provider.description.clone(),
+        None => format!("{spid}"),
+    });
+    let response = ui.label(if options.highlight_provider {
+        text.strong()
+    } else {
+        text
+    });
+    if let Some(provider) = provider {
+        response.on_hover_text(format!(
+            "Solution provider {}\n{}",
+            provider.solution_provider_id, provider.link
+        ));
+    }
+}
+
+/// The values loaded separately for every contract in the contract overview.
+struct ContractViewLoads<'a> {
+    node_nru: &'a ContractLoads,
+    name_nru: &'a ContractLoads,
+    node_price: &'a ContractLoads,
+    name_price: &'a ContractLoads,
+    rent_price: &'a ContractLoads,
+}
+
+/// The rows of the contract tables as shown with the given options, node contracts first, then
+/// name contracts, then rent contracts, with their cost and NRU if they are loaded. If the
+/// contracts are grouped by node, node and rent contracts are ordered by node like their tables.
+fn contract_view_rows<'a>(
+    node_contracts: &[&'a NodeContract],
+    name_contracts: &'a [NameContract],
+    rent_contracts: &'a [RentContract],
+    loads: ContractViewLoads,
+    options: ContractTableOptions,
+    group_by_node: bool,
+) -> Vec<ContractRow<'a>> {
+    let mut node_contracts = node_contracts
+        .iter()
+        .copied()
+        .filter(|c| {
+            contract_visible(
+                loads.node_price.get(c.contract_id),
+                c.matches_text(options.search),
+                c.solution_provider_id,
+                options,
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut rent_contracts = rent_contracts
+        .iter()
+        .filter(|c| {
+            contract_visible(
+                loads.rent_price.get(c.contract_id),
+                c.matches_text(options.search),
+                c.solution_provider_id,
+                options,
+            )
+        })
+        .collect::<Vec<_>>();
+    if group_by_node {
+        node_contracts.sort_by_key(|c| c.node_id);
+        rent_contracts.sort_by_key(|c| c.node_id);
+    }
+    let node_rows = node_contracts.into_iter().map(|c| ContractRow {
+        contract: ContractRef::Node(c),
+        cost: loads.node_price.loaded(c.contract_id),
+        nru: loads.node_nru.loaded(c.contract_id),
+    });
+    let name_rows = name_contracts
+        .iter()
+        .filter(|c| {
+            contract_visible(
+                loads.name_price.get(c.contract_id),
+                c.matches_text(options.search),
+                c.solution_provider_id,
+                options,
+            )
+        })
+        .map(|c| ContractRow {
+            contract: ContractRef::Name(c),
+            cost: loads.name_price.loaded(c.contract_id),
+            nru: loads.name_nru.loaded(c.contract_id),
+        });
+    let rent_rows = rent_contracts.into_iter().map(|c| ContractRow {
+        contract: ContractRef::Rent(c),
+        cost: loads.rent_price.loaded(c.contract_id),
+        nru: None,
+    });
+    node_rows.chain(name_rows).chain(rent_rows).collect()
+}
+
+/// Check if a contract is shown in its table with the given options, given its (possibly still
+/// loading) cost, see [`cost_visible`], and whether its fields match the search text, see
+/// [`search_visible`].
+fn contract_visible(
+    cost_load: Option<&Promise<Result<u64, String>>>,
+    fields_match: bool,
+    spid: Option<u32>,
+    options: ContractTableOptions,
+) -> bool {
+    cost_visible(cost_load, options.min_cost) && search_visible(fields_match, spid, options)
+}
+
+/// Check if a contract should be shown given the search text of the options, based on whether
+/// its own fields match the text, or the description of its solution provider once loaded.
+fn search_visible(fields_match: bool, spid: Option<u32>, options: ContractTableOptions) -> bool {
+    fields_match
+        || spid
+            .and_then(|spid| options.providers.get(&spid))
+            .and_then(|load| load.ready())
+            .and_then(|provider| provider.as_ref().ok())
+            .and_then(|provider| provider.as_ref())
+            .is_some_and(|provider| {
+                provider
+                    .description
+                    .to_lowercase()
+                    .contains(&options.search.to_lowercase())
+            })
+}
+
+/// Check if a contract should be shown given its (possibly still loading) cost and the minimum
+/// cost to show. Contracts of which the cost is not known yet remain visible.
+fn cost_visible(cost_load: Option<&Promise<Result<u64, String>>>, min_cost: Option<u64>) -> bool {
+    match (min_cost, cost_load.and_then(|load| load.ready())) {
+        (Some(min_cost), Some(Ok(cost))) => *cost >= min_cost,
+        _ => true,
+    }
+}
+
+/// Input for an amount in TFT, e.g. the minimum cost of contracts to show. An empty input clears
+/// the amount.
+fn ui_tft_input(
+    ui: &mut egui::Ui,
+    label_text: &str,
+    error_text: &mut String,
+    buffer: &mut String,
+    amount: &mut Option<u64>,
+) {
+    ui.horizontal(|ui| {
+        let label = ui.label(label_text);
+        ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+            let input_response = ui.text_edit_singleline(buffer).labelled_by(label.id);
+            if input_response.changed() {
+                error_text.clear();
+                *amount = None;
+                if !buffer.trim().is_empty() {
+                    match buffer.trim().parse::<f64>() {
+                        Ok(tft) if tft.is_finite() && tft >= 0. => {
+                            *amount = Some(Denomination::TFT.from_tokens(tft));
+                        }
+                        Ok(_) => *error_text = "Amount must be positive".to_string(),
+                        Err(e) => *error_text = e.to_string(),
+                    }
+                }
+            }
+            ui.colored_label(ui.visuals().error_fg_color, error_text);
+        });
+    });
+}
+
+/// Input for the minimum amount of a resource used by node contracts to show, in GiB. An empty
+/// input shows all contracts.
+fn ui_min_resource_input(
+    ui: &mut egui::Ui,
+    label: &str,
+    error_text: &mut String,
+    buffer: &mut String,
+    min_bytes: &mut Option<u64>,
+) {
+    ui.horizontal(|ui| {
+        let label = ui.label(label);
+        ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+            let input_response = ui.text_edit_singleline(buffer).labelled_by(label.id);
+            if input_response.changed() {
+                error_text.clear();
+                *min_bytes = None;
+                if !buffer.trim().is_empty() {
+                    match buffer.trim().parse::<f64>() {
+                        Ok(gib) if gib.is_finite() && gib >= 0. => {
+                            *min_bytes = Some((gib * GIB as f64).round() as u64);
+                        }
+                        Ok(_) => *error_text = "Size must be a positive amount".to_string(),
+                        Err(e) => *error_text = e.to_string(),
+                    }
+                }
+            }
+            ui.colored_label(ui.visuals().error_fg_color, error_text);
+        });
+    });
+}
+
+/// Show the nodes of a farm with their amount of active node contracts. The amount billed is shown
+/// once the summaries are loaded, which takes longer as all bills on the nodes are fetched. The
+/// summaries are [`None`] while they are loading.
+fn ui_farm_nodes(
+    ui: &mut egui::Ui,
+    nodes: &[Node],
+    counts: &BTreeMap<u32, u64>,
+    summaries: Option<&Result<BTreeMap<u32, NodeSummary>, String>>,
+) {
+    if nodes.is_empty() {
+        ui_no_results(ui, "No nodes found in this farm");
+        return;
+    }
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        TableBuilder::new(ui)
+            .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
+            .columns(Column::auto().resizable(true).clip(false), 4)
+            .column(Column::remainder().clip(false).at_most(100.))
+            .striped(true)
+            .header(50.0, |mut header| {
+                for title in ["Node ID", "Twin ID", "Location", "Node contracts", "Billed"] {
+                    header.col(|ui| {
+                        ui.heading(title);
                     });
+                }
+            })
+            .body(|body| {
+                body.rows(30.0, nodes.len(), |row_idx, mut row| {
+                    let node = &nodes[row_idx];
                     row.col(|ui| {
-                        let cost_load = node_price_loads[row_idx]
-                            .get_or_insert_with(cost_loader(contract.contract_id));
-                        match cost_load.ready() {
-                            Some(Ok(cost)) => ui.label(fmt_tft(*cost)),
-                            Some(Err(err)) => ui.colored_label(ui.visuals().error_fg_color, err),
-                            None => ui.spinner(),
-                        };
+                        ui.label(format!("{}", node.node_id));
                     });
                     row.col(|ui| {
-                        ui.label(&contract.deployment_hash);
+                        ui_copyable_label(
+                            ui,
+                            &node.twin_id.to_string(),
+                            "Click to copy the twin ID",
+                        );
                     });
                     row.col(|ui| {
-                        if ui
-                            .label(if contract.deployment_data.len() <= 30 {
-                                contract.deployment_data.clone()
-                            } else {
-                                let mut dd = contract.deployment_data.clone();
-                                dd.truncate(30);
-                                dd
-                            })
-                            .hovered()
-                        {
-                            egui::show_tooltip(
-                                ui.ctx(),
-                                egui::Id::new("contract_deployment_data_tooltip"),
-                                |ui| {
-                                    ui.label(if contract.deployment_data.is_empty() {
-                                        "No contract data set on chain for this contract"
-                                    } else {
-                                        &contract.deployment_data
-                                    });
-                                },
-                            );
-                        };
+                        ui.label(match (&node.city, &node.country) {
+                            (Some(city), Some(country)) => format!("{city}, {country}"),
+                            (None, Some(location)) | (Some(location), None) => location.clone(),
+                            (None, None) => "-".to_string(),
+                        });
                     });
                     row.col(|ui| {
-                        ui.label(fmt_local_time(contract.created_at));
+                        ui.label(format!(
+                            "{}",
+                            counts.get(&node.node_id).copied().unwrap_or_default()
+                        ));
                     });
-                    row.col(|ui| {
-                        ui.label(format!("{}", contract.state));
+                    row.col(|ui| match summaries {
+                        Some(Ok(summaries)) => {
+                            let summary = summaries.get(&node.node_id).copied().unwrap_or_default();
+                            ui.label(fmt_tft(summary.billed));
+                        }
+                        Some(Err(_)) => {
+                            ui.label("-");
+                        }
+                        None => {
+                            ui.spinner();
+                        }
                     });
                 });
             });
     });
 }
 
-fn ui_name_contracts<C, N>(
-    ui: &mut egui::Ui,
-    name_contracts: &[NameContract],
-    nru_loads: &mut [Option<Promise<Result<u64, String>>>],
-    name_price_loads: &mut [Option<Promise<Result<u64, String>>>],
-    nru_loader: impl Fn(u64) -> N,
-    cost_loader: impl Fn(u64) -> C,
-) where
-    C: FnOnce() -> Promise<Result<u64, String>>,
-    N: FnOnce() -> Promise<Result<u64, String>>,
-{
+/// Show the state transitions of a contract as a timeline, with the time every state was entered
+/// and left.
+fn ui_contract_timeline(ui: &mut egui::Ui, events: &[ContractEvent]) {
+    if events.is_empty() {
+        ui_no_results(ui, "No state changes found for this contract");
+        return;
+    }
+    ui.label("Transitions are estimated from the bill reports of the contract");
     egui::ScrollArea::horizontal().show(ui, |ui| {
         TableBuilder::new(ui)
             .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
-            .columns(Column::auto().resizable(true).clip(false), 7)
+            .columns(Column::auto().resizable(true).clip(false), 2)
             .column(Column::remainder().clip(false).at_most(100.))
             .striped(true)
             .header(50.0, |mut header| {
-                for title in [
-                    "Contract ID",
-                    "Twin ID",
-                    "Solution Provider ID",
-                    "Name",
-                    "Nru",
-                    "Total Cost",
-                    "Created",
-                    "State",
-                ] {
+                for title in ["State", "Since", "Until"] {
                     header.col(|ui| {
                         ui.heading(title);
                     });
                 }
             })
             .body(|body| {
-                body.rows(30.0, name_contracts.len(), |row_idx, mut row| {
-                    let contract = &name_contracts[row_idx];
-                    row.col(|ui| {
-                        ui.label(format!("{}", contract.contract_id));
-                    });
-                    row.col(|ui| {
-                        if ui.label(format!("{}", contract.twin_id)).hovered() {
-                            egui::show_tooltip(
-                                ui.ctx(),
-                                egui::Id::new("contract_twin_id_tooltip"),
-                                |ui| {
-                                    ui.label(format!(
-                                        "This contract is created and owned by twin {}",
-                                        contract.twin_id
-                                    ));
-                                },
-                            );
-                        };
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", contract.solution_provider_id.unwrap_or(0)));
-                    });
-                    row.col(|ui| {
-                        ui.label(&contract.name);
-                    });
-                    row.col(|ui| {
-                        let nru_load =
-                            nru_loads[row_idx].get_or_insert_with(nru_loader(contract.contract_id));
-                        match nru_load.ready() {
-                            Some(Ok(nru)) => ui.label(fmt_resources(*nru)),
-                            Some(Err(err)) => ui.colored_label(ui.visuals().error_fg_color, err),
-                            None => ui.spinner(),
-                        };
-                    });
+                body.rows(30.0, events.len(), |row_idx, mut row| {
+                    let event = &events[row_idx];
                     row.col(|ui| {
-                        let cost_load = name_price_loads[row_idx]
-                            .get_or_insert_with(cost_loader(contract.contract_id));
-                        match cost_load.ready() {
-                            Some(Ok(cost)) => ui.label(fmt_tft(*cost)),
-                            Some(Err(err)) => ui.colored_label(ui.visuals().error_fg_color, err),
-                            None => ui.spinner(),
-                        };
+                        if event.state == ContractState::Created {
+                            ui.label(event.state.to_string());
+                        } else {
+                            ui.colored_label(ui.visuals().error_fg_color, event.state.to_string());
+                        }
                     });
                     row.col(|ui| {
-                        ui.label(fmt_local_time(contract.created_at));
+                        ui.label(fmt_local_time(event.timestamp));
                     });
                     row.col(|ui| {
-                        ui.label(format!("{}", contract.state));
+                        ui.label(match events.get(row_idx + 1) {
+                            Some(next) => fmt_local_time(next.timestamp),
+                            // A deleted contract can't transition anymore.
+                            None if event.state == ContractState::Deleted => "-".to_string(),
+                            None => "Now".to_string(),
+                        });
                     });
                 });
             });
     });
 }
 
-fn ui_rent_contracts<C>(
-    ui: &mut egui::Ui,
-    rent_contracts: &[RentContract],
-    rent_price_loads: &mut [Option<Promise<Result<u64, String>>>],
-    cost_loader: impl Fn(u64) -> C,
-) where
-    C: FnOnce() -> Promise<Result<u64, String>>,
-{
+fn ui_grace_period_watchlist(ui: &mut egui::Ui, watchlist: &[GracePeriodContract]) {
+    if watchlist.is_empty() {
+        ui_no_results(ui, "No contracts found in grace period");
+        return;
+    }
     egui::ScrollArea::horizontal().show(ui, |ui| {
         TableBuilder::new(ui)
             .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
-            .columns(Column::auto().resizable(true).clip(false), 6)
+            .columns(Column::auto().resizable(true).clip(false), 3)
             .column(Column::remainder().clip(false).at_most(100.))
             .striped(true)
             .header(50.0, |mut header| {
-                for title in [
-                    "Contract ID",
-                    "Node ID",
-                    "Twin ID",
-                    "Solution Provider ID",
-                    "Total Cost",
-                    "Created",
-                    "State",
-                ] {
+                for title in ["Contract ID", "Node ID", "Twin ID", "In grace period since"] {
                     header.col(|ui| {
                         ui.heading(title);
                     });
                 }
             })
             .body(|body| {
-                body.rows(30.0, rent_contracts.len(), |row_idx, mut row| {
-                    let contract = &rent_contracts[row_idx];
+                body.rows(30.0, watchlist.len(), |row_idx, mut row| {
+                    let contract = &watchlist[row_idx];
                     row.col(|ui| {
-                        ui.label(format!("{}", contract.contract_id));
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("{}", contract.contract_id),
+                        );
                     });
                     row.col(|ui| {
-                        ui.label(format!("{}", contract.node_id));
+                        ui.label(if let Some(node_id) = contract.node_id {
+                            format!("{node_id}")
+                        } else {
+                            "-".to_string()
+                        });
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{}", contract.twin_id));
                     });
                     row.col(|ui| {
-                        if ui.label(format!("{}", contract.twin_id)).hovered() {
+                        if let Some(since) = contract.grace_since {
+                            ui.colored_label(ui.visuals().error_fg_color, fmt_local_time(since));
+                        } else if ui
+                            .colored_label(ui.visuals().error_fg_color, "Unknown")
+                            .hovered()
+                        {
                             egui::show_tooltip(
                                 ui.ctx(),
-                                egui::Id::new("contract_twin_id_tooltip"),
+                                egui::Id::new("grace_period_unknown_tooltip"),
                                 |ui| {
-                                    ui.label(format!(
-                                        "This contract is created and owned by twin {}",
-                                        contract.twin_id
-                                    ));
+                                    ui.label("This contract has never been billed");
                                 },
                             );
                         };
                     });
-                    row.col(|ui| {
-                        ui.label(format!("{}", contract.solution_provider_id.unwrap_or(0)));
-                    });
-                    row.col(|ui| {
-                        let cost_load = rent_price_loads[row_idx]
-                            .get_or_insert_with(cost_loader(contract.contract_id));
-                        match cost_load.ready() {
-                            Some(Ok(cost)) => ui.label(fmt_tft(*cost)),
-                            Some(Err(err)) => ui.colored_label(ui.visuals().error_fg_color, err),
-                            None => ui.spinner(),
-                        };
-                    });
-                    row.col(|ui| {
-                        ui.label(fmt_local_time(contract.created_at));
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", contract.state));
-                    });
                 });
             });
     });
 }
 
+/// Buttons to export the uptime events and node state changes, see [`save_export`]. If an export
+/// can't be saved, the error is shown until another export is started.
+fn ui_node_state_export(ui: &mut egui::Ui, info: &NodeStateInfo, export_error: &mut String) {
+    ui.horizontal(|ui| {
+        ui.label("Export:");
+        let mut saved = None;
+        if ui.button("Uptime events (CSV)").clicked() {
+            saved = Some(save_export("uptime_events.csv", || {
+                Ok(export::uptime_events_csv(&info.uptime_events))
+            }));
+        }
+        if ui.button("Uptime events (JSON)").clicked() {
+            saved = Some(save_export("uptime_events.json", || {
+                export::to_json(&info.uptime_events)
+            }));
+        }
+        if ui.button("State changes (CSV)").clicked() {
+            saved = Some(save_export("state_changes.csv", || {
+                Ok(export::state_changes_csv(&info.state_changes))
+            }));
+        }
+        if ui.button("State changes (JSON)").clicked() {
+            saved = Some(save_export("state_changes.json", || {
+                export::to_json(&info.state_changes)
+            }));
+        }
+        match saved {
+            Some(Ok(())) => export_error.clear(),
+            Some(Err(err)) => *export_error = err,
+            None => {}
+        }
+        if !export_error.is_empty() {
+            ui.colored_label(ui.visuals().error_fg_color, export_error.as_str());
+        }
+    });
+}
+
+/// Buttons to generate a markdown report or an HTML dashboard of a twin, and save it once it is
+/// generated, see [`save_export`]. If the report can't be generated or saved, the error is shown
+/// until another report is generated.
+fn ui_twin_report_export(
+    ui: &mut egui::Ui,
+    client: &Client,
+    twin_id: Option<u32>,
+    report_loading: &mut Option<ReportLoad>,
+    report_error: &mut String,
+) {
+    type Generate = fn(u32, &Contracts, &[ContractBillReport]) -> String;
+    let exports: [(&str, &'static str, Generate); 2] = [
+        (
+            "Export report",
+            "twin_report.md",
+            |twin_id, contracts, bills| {
+                report::twin_report_markdown(twin_id, contracts, bills, &BTreeMap::new())
+            },
+        ),
+        (
+            "Export dashboard",
+            "twin_dashboard.html",
+            |twin_id, contracts, bills| {
+                report::dashboard_html(twin_id, contracts, bills, BillBucket::Day)
+            },
+        ),
+    ];
+    ui.horizontal(|ui| {
+        let generating = report_loading.is_some();
+        for (label, file_name, generate) in exports {
+            if ui
+                .add_enabled(twin_id.is_some() && !generating, egui::Button::new(label))
+                .clicked()
+            {
+                let twin_id = twin_id.unwrap();
+                let client = client.clone();
+                report_error.clear();
+                *report_loading = Some(Promise::spawn_async(async move {
+                    let contracts = client
+                        .contracts(&ContractFilters::default().twins([twin_id]))
+                        .await?;
+                    let contract_ids = contracts.all_contract_ids();
+                    let bills = client
+                        .contract_bill_reports(None, None, Some(&contract_ids), None)
+                        .await?;
+                    Ok((file_name, generate(twin_id, &contracts, &bills)))
+                }));
+            }
+        }
+        if generating {
+            ui.spinner();
+        }
+    });
+    if let Some(load) = report_loading.take() {
+        match load.try_take() {
+            Ok(Ok((file_name, report))) => {
+                if let Err(err) = save_export(file_name, || Ok(report)) {
+                    *report_error = err;
+                }
+            }
+            Ok(Err(err)) => *report_error = format!("Could not generate twin report: {err}"),
+            Err(load) => *report_loading = Some(load),
+        }
+    }
+    if !report_error.is_empty() {
+        ui.colored_label(ui.visuals().error_fg_color, report_error.as_str());
+    }
+}
+
+/// Save an export. On native, the user is asked where to save the export, and the export is only
+/// generated if the user picked a file. On the web there is no file system, so the export is
+/// downloaded by the browser instead. Not picking a file is not an error.
+fn save_export(
+    file_name: &str,
+    generate: impl FnOnce() -> Result<String, String>,
+) -> Result<(), String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let saved = {
+        let extension = file_name.rsplit('.').next().unwrap_or_default();
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name(file_name)
+            .add_filter(&extension.to_uppercase(), &[extension])
+            .save_file()
+        else {
+            return Ok(());
+        };
+        generate()
+            .and_then(|data| std::fs::write(&path, data).map_err(|e| e.to_string()))
+            .map_err(|err| format!("Could not export to {}: {err}", path.display()))
+    };
+    #[cfg(target_arch = "wasm32")]
+    let saved = generate()
+        .and_then(|data| download_bytes(file_name, mime_type(file_name), data.as_bytes()))
+        .map_err(|err| format!("Could not export {file_name}: {err}"));
+    saved
+}
+
+/// Let the browser download the given bytes as a file with the given name, by clicking a link to
+/// an object URL holding the bytes.
+#[cfg(target_arch = "wasm32")]
+fn download_bytes(file_name: &str, mime: &str, bytes: &[u8]) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+
+    let js_err = |e: wasm_bindgen::JsValue| format!("{e:?}");
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(
+        &js_sys::Array::of1(&js_sys::Uint8Array::from(bytes)),
+        &options,
+    )
+    .map_err(js_err)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_err)?;
+    let anchor = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("No document to download the file from")?
+        .create_element("a")
+        .map_err(js_err)?
+        .unchecked_into::<web_sys::HtmlAnchorElement>();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    // The download might not have started yet when the click returns, so the object URL is only
+    // released afterwards.
+    gloo_timers::callback::Timeout::new(0, move || {
+        if let Err(e) = web_sys::Url::revoke_object_url(&url) {
+            log::warn!("Could not release download URL: {e:?}");
+        }
+    })
+    .forget();
+    Ok(())
+}
+
+/// The MIME type of an export, based on the extension of its file name.
+#[cfg(target_arch = "wasm32")]
+fn mime_type(file_name: &str) -> &'static str {
+    match file_name.rsplit('.').next().unwrap_or_default() {
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "md" => "text/markdown",
+        "html" => "text/html",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Show a verdict on the node in the period, colored green if the node was up the whole period,
+/// yellow if it had outages or its state at the end of the period is not known to be up, and red
+/// if it was down at the end of the period or had a low availability.
+fn ui_node_state_badge(ui: &mut egui::Ui, info: &NodeStateInfo) {
+    let availability = info.summary.availability;
+    let outages = info.summary.reliability.failure_count;
+    let (trailing, trailing_ok) = match info.end_state {
+        NodeState::Booted(_) => ("up at the end", true),
+        NodeState::Offline(_) => ("down at the end", false),
+        NodeState::PoweredDown(_) => ("powered down at the end", false),
+        _ => ("trailing state unknown", false),
+    };
+    let color =
+        if matches!(info.end_state, NodeState::Offline(_)) || availability < LOW_AVAILABILITY {
+            ui.visuals().error_fg_color
+        } else if outages > 0 || !trailing_ok {
+            ui.visuals().warn_fg_color
+        } else {
+            // egui has no semantic color for success.
+            Color32::GREEN
+        };
+    let text = format!(
+        "Up {:.1}% · {outages} outage{} · {trailing}",
+        availability * 100.,
+        if outages == 1 { "" } else { "s" },
+    );
+    egui::Frame::none()
+        .stroke(egui::Stroke::new(1., color))
+        .rounding(4.)
+        .inner_margin(4.)
+        .show(ui, |ui| {
+            ui.label(RichText::new(text).strong().color(color));
+        });
+}
+
 fn ui_node_state_changes(ui: &mut egui::Ui, state_changes: &[NodeStateChange]) {
     egui::ScrollArea::horizontal().show(ui, |ui| {
         TableBuilder::new(ui)
             .cell_layout(Layout::centered_and_justified(egui::Direction::LeftToRight))
-            .columns(Column::auto().resizable(true).clip(false), 2)
+            .columns(Column::auto().resizable(true).clip(false), 3)
             .column(Column::remainder().clip(false).at_most(100.))
             .striped(true)
             .header(50.0, |mut header| {
-                for title in ["", "Event", "Event detected"] {
+                for title in ["", "Event", "Happened at", "Detected at"] {
                     header.col(|ui| {
                         ui.heading(title);
                     });
@@ -874,28 +2862,55 @@ fn ui_node_state_changes(ui: &mut egui::Ui, state_changes: &[NodeStateChange]) {
                         });
                     });
                     row.col(|ui| {
-                        ui.label(fmt_local_time(state_change.timestamp()));
+                        // Drift has no moment at which it happened.
+                        match state_change.state().occurred_at() {
+                            Some(ts) => ui.label(fmt_local_time(ts)),
+                            None => ui.label("-"),
+                        };
+                    });
+                    row.col(|ui| {
+                        ui.label(fmt_local_time(state_change.timestamp()))
+                            .on_hover_text(
+                                "When the uptime event which showed this change was received",
+                            );
                     });
                 });
             });
     });
 }
 
-fn ui_node_jitter_graph(ui: &mut egui::Ui, uptime_events: &[UptimeEvent]) {
-    let jitter_data: PlotPoints = uptime_events
-        .windows(2)
-        .map(|window| {
-            [
-                window[1].timestamp() as f64,
-                if window[1].uptime() > (window[1].timestamp() - window[0].timestamp()) as u64 {
-                    ((window[1].uptime() - window[0].uptime()) as i64
-                        - (window[1].timestamp() - window[0].timestamp()))
-                        as f64
-                } else {
-                    0.
-                },
-            ]
+/// Graph the jitter and spacing of the uptime events of a node. Events whose jitter exceeds the
+/// threshold, in either direction, are highlighted.
+fn ui_node_jitter_graph(ui: &mut egui::Ui, info: &NodeStateInfo, threshold: Option<u64>) {
+    let uptime_events = &info.uptime_events;
+    let jitter = jitter_series(uptime_events);
+    let anomalies = threshold
+        .map(|threshold| {
+            jitter
+                .iter()
+                .filter(|(_, jitter)| jitter.unsigned_abs() > threshold)
+                .map(|&(ts, jitter)| [ts as f64, jitter as f64])
+                .collect::<Vec<_>>()
         })
+        .unwrap_or_default();
+    if let Some(threshold) = threshold {
+        let color = if anomalies.is_empty() {
+            ui.visuals().text_color()
+        } else {
+            ui.visuals().warn_fg_color
+        };
+        ui.colored_label(
+            color,
+            format!(
+                "{} of {} events exceed a jitter of {threshold} seconds",
+                anomalies.len(),
+                jitter.len()
+            ),
+        );
+    }
+    let jitter_data: PlotPoints = jitter
+        .iter()
+        .map(|&(ts, jitter)| [ts as f64, jitter as f64])
         .collect();
     let delay_data: PlotPoints = uptime_events
         .windows(2)
@@ -910,11 +2925,52 @@ fn ui_node_jitter_graph(ui: &mut egui::Ui, uptime_events: &[UptimeEvent]) {
             ]
         })
         .collect();
+    // Offline bands span the full height of the plotted data.
+    let (y_min, y_max) = jitter_data
+        .points()
+        .iter()
+        .chain(delay_data.points())
+        .fold((0., 0.), |(min, max): (f64, f64), p| {
+            (min.min(p.y), max.max(p.y))
+        });
+    let offline_bands = info
+        .downtime_windows
+        .iter()
+        .map(|&(start, end)| {
+            Polygon::new(PlotPoints::new(vec![
+                [start as f64, y_min],
+                [end as f64, y_min],
+                [end as f64, y_max],
+                [start as f64, y_max],
+            ]))
+            .color(Color32::RED)
+            .fill_alpha(0.2)
+            .name("offline")
+        })
+        .collect::<Vec<_>>();
+    let boot_points: PlotPoints = info
+        .state_changes
+        .iter()
+        .filter_map(|change| match change.state() {
+            NodeState::Booted(ts) => Some([ts as f64, 0.]),
+            _ => None,
+        })
+        .collect();
+    let boot_markers = Points::new(boot_points)
+        .color(Color32::GREEN)
+        .shape(MarkerShape::Diamond)
+        .radius(5.)
+        .name("boot");
+    let anomaly_markers = Points::new(PlotPoints::new(anomalies))
+        .color(ui.visuals().warn_fg_color)
+        .shape(MarkerShape::Circle)
+        .radius(4.)
+        .name("jitter anomaly");
     let jitter_line = Line::new(jitter_data).name("jitter");
     let delay_line = Line::new(delay_data).name("uptime spacing");
     Plot::new("jitter_plot")
         .label_formatter(|name, value| {
-            if name == "jitter" {
+            if name == "jitter" || name == "jitter anomaly" {
                 format!(
                     "{}: {} seconds jitter",
                     fmt_local_time(value.x as i64),
@@ -927,6 +2983,8 @@ fn ui_node_jitter_graph(ui: &mut egui::Ui, uptime_events: &[UptimeEvent]) {
                     fmt_local_time(value.x as i64),
                     value.y / 60.,
                 )
+            } else if name == "boot" {
+                format!("Node booted at {}", fmt_local_time(value.x as i64))
             } else {
                 "".to_string()
             }
@@ -934,35 +2992,101 @@ fn ui_node_jitter_graph(ui: &mut egui::Ui, uptime_events: &[UptimeEvent]) {
         .x_axis_formatter(|value, _range| fmt_local_time(value as i64))
         .legend(Legend::default())
         .show(ui, |plot_ui| {
+            // Bands are drawn first so they render below the lines.
+            for band in offline_bands {
+                plot_ui.polygon(band);
+            }
+            plot_ui.points(boot_markers);
             plot_ui.line(jitter_line);
+            plot_ui.points(anomaly_markers);
             plot_ui.line(delay_line);
         });
 }
 
-fn ui_bill_graph(ui: &mut egui::Ui, bill_reports: &[ContractBillReport]) {
-    let mut hourly_cost = BTreeMap::new();
-    for bill_report in bill_reports {
-        *hourly_cost.entry(bill_report.timestamp / 3600).or_insert(0) += bill_report.amount_billed;
+/// Graph the amount billed per bucket, from the amount billed per hour.
+fn ui_bill_graph(ui: &mut egui::Ui, hourly: &BTreeMap<i64, u64>, bucket: BillBucket) {
+    // Buckets are aligned on hours, so all bills of an hour are in the same bucket.
+    let mut bucket_cost = BTreeMap::<i64, u64>::new();
+    for (hour, amount) in hourly {
+        *bucket_cost.entry(bucket.bucket_start(*hour)).or_default() += amount;
     }
-    let bill_data: PlotPoints = hourly_cost
+    let bill_data: PlotPoints = bucket_cost
+        .iter()
+        .map(|(k, v)| [*k as f64, *v as f64])
+        .collect();
+    let cumulative_data: PlotPoints = bucket_cost
         .into_iter()
-        .map(|(k, v)| [(k * 3600) as f64, v as f64])
+        .scan(0, |total, (k, v)| {
+            *total += v;
+            Some([k as f64, *total as f64])
+        })
         .collect();
     let bill_cost_line = Line::new(bill_data).name("bill cost");
+    let cumulative_line = Line::new(cumulative_data).name("cumulative");
+    // Both plots share the time axis, but are drawn separately so the cumulative value scales
+    // independently of the hourly cost.
+    let axis_group = egui::Id::new("bill_plots");
     Plot::new("bill_cost_plot")
-        .label_formatter(|_, value| {
+        .view_aspect(3.)
+        .link_axis(axis_group, true, false)
+        .label_formatter(move |_, value| {
             format!(
-                "{}: {:.7} TFT",
-                fmt_local_time(value.x as i64),
-                value.y / 10_000_000.,
+                "{}: {}",
+                fmt_bucket(value.x as i64, bucket),
+                fmt_tft(value.y.max(0.) as u64),
             )
         })
-        .x_axis_formatter(|value, _range| fmt_local_time(value as i64))
-        .y_axis_formatter(|value, _range| format!("{} TFT", value as u64 / 10_000_000))
+        .x_axis_formatter(move |value, _range| fmt_bucket(value as i64, bucket))
+        .y_axis_formatter(|value, _range| fmt_whole_tft(value.max(0.) as u64))
         .legend(Legend::default())
         .show(ui, |plot_ui| {
             plot_ui.line(bill_cost_line);
         });
+    Plot::new("bill_cumulative_plot")
+        .view_aspect(3.)
+        .link_axis(axis_group, true, false)
+        .label_formatter(move |_, value| {
+            format!(
+                "{}: {} billed in total",
+                fmt_bucket(value.x as i64, bucket),
+                fmt_tft(value.y.max(0.) as u64),
+            )
+        })
+        .x_axis_formatter(move |value, _range| fmt_bucket(value as i64, bucket))
+        .y_axis_formatter(|value, _range| fmt_whole_tft(value.max(0.) as u64))
+        .legend(Legend::default())
+        .show(ui, |plot_ui| {
+            plot_ui.line(cumulative_line);
+        });
+}
+
+/// Amount of contracts shown in the top billed contracts chart.
+const TOP_CONTRACTS: usize = 10;
+
+/// Amount of seconds the confirmation is shown after copying a value to the clipboard.
+const COPIED_TOOLTIP_DURATION: f64 = 1.;
+
+/// Show a label which copies its text to the clipboard when clicked. The hover text is shown as
+/// tooltip, except for a short while after the text has been copied, when a confirmation is shown
+/// instead.
+fn ui_copyable_label(ui: &mut egui::Ui, text: &str, hover_text: &str) -> egui::Response {
+    let response = ui.add(egui::Label::new(text).sense(egui::Sense::click()));
+    let now = ui.input(|i| i.time);
+    if response.clicked() {
+        ui.output_mut(|o| o.copied_text = text.to_string());
+        ui.data_mut(|d| d.insert_temp(response.id, now));
+    }
+    let recently_copied = ui
+        .data(|d| d.get_temp::<f64>(response.id))
+        .map(|copied_at| now - copied_at < COPIED_TOOLTIP_DURATION)
+        .unwrap_or(false);
+    if recently_copied {
+        // Make sure the confirmation disappears, even without user input.
+        ui.ctx().request_repaint();
+        response.on_hover_text("Copied!")
+    } else {
+        response.on_hover_text(hover_text)
+    }
 }
 
 fn ui_multi_input<T>(
@@ -1035,12 +3159,182 @@ fn ui_single_input<T>(
     });
 }
 
+/// The result of an asynchronous computation, together with the time it took to complete.
+struct Timed<T> {
+    value: T,
+    elapsed: chrono::Duration,
+}
+
+/// Spawn a future as [`Promise`], measuring the time it takes for the future to complete. Time is
+/// measured with [`Utc::now`] rather than [`std::time::Instant`], since the latter is not
+/// available on web.
+///
+/// Starting a new search replaces the [`Promise`] of the previous search, so results of a
+/// superseded search are never shown. Dropping the [`Promise`] discards its result, but does not
+/// necessarily abort the request. In particular on web, the future keeps running in the browser
+/// until the request completes, after which its result is silently ignored.
+fn spawn_timed<T: Send + 'static>(
+    future: impl std::future::Future<Output = T> + 'static,
+) -> Promise<Timed<T>> {
+    Promise::spawn_async(async move {
+        let start = Utc::now();
+        let value = future.await;
+        Timed {
+            value,
+            elapsed: Utc::now() - start,
+        }
+    })
+}
+
+/// Show the result of a [`TimedLoad`]. A spinner is shown while loading, and an error if loading
+/// failed. Otherwise, the time it took to load is shown, followed by the content rendered by
+/// `add_contents`. Nothing is shown if nothing is being loaded.
+fn ui_timed_section<T>(
+    ui: &mut egui::Ui,
+    load: &Option<TimedLoad<T>>,
+    add_contents: impl FnOnce(&mut egui::Ui, &T),
+) where
+    T: Send,
+{
+    match load.as_ref().map(|p| p.ready()) {
+        None => {}
+        Some(None) => {
+            ui.spinner();
+        }
+        Some(Some(Timed {
+            value: Err(err), ..
+        })) => {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+        }
+        Some(Some(Timed {
+            value: Ok(value),
+            elapsed,
+        })) => {
+            ui.label(fmt_elapsed(*elapsed));
+            add_contents(ui, value);
+        }
+    }
+}
+
+/// Show that a search finished successfully, but did not find anything, so an empty result is
+/// not mistaken for a search which is still loading or failed.
+fn ui_no_results(ui: &mut egui::Ui, text: &str) {
+    ui.label(egui::RichText::new(text).italics());
+}
+
+/// Show the button which starts the search of a panel, returning if the search should start.
+/// Besides clicking the button, the search can be started with Ctrl+Enter (Cmd+Enter on mac). The
+/// shortcut is ignored while the previous search is still loading, so holding it doesn't restart
+/// the search every frame.
+fn ui_search_button(ui: &mut egui::Ui, text: &str, enabled: bool, loading: bool) -> bool {
+    let clicked = ui.add_enabled(enabled, egui::Button::new(text)).clicked();
+    let shortcut = enabled
+        && !loading
+        && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Enter));
+    clicked || shortcut
+}
+
+/// Check if a load is started and not finished yet.
+fn is_loading<T: Send>(load: &Option<Promise<T>>) -> bool {
+    load.as_ref().is_some_and(|load| load.ready().is_none())
+}
+
+/// Format the time a query took.
+fn fmt_elapsed(elapsed: chrono::Duration) -> String {
+    format!(
+        "fetched in {:.1}s",
+        elapsed.num_milliseconds() as f64 / 1000.
+    )
+}
+
+/// Show a button for every [`RangePreset`], returning the preset which was clicked, if any.
+fn ui_range_presets(ui: &mut egui::Ui) -> Option<RangePreset> {
+    ui.horizontal(|ui| {
+        ui.label("Presets:");
+        let mut selected = None;
+        for preset in [
+            RangePreset::LastDay,
+            RangePreset::LastWeek,
+            RangePreset::LastMonth,
+            RangePreset::MintingPeriod,
+        ] {
+            if ui.button(preset.to_string()).clicked() {
+                selected = Some(preset);
+            }
+        }
+        selected
+    })
+    .inner
+}
+
+/// Predefined time ranges which can be used to fill in the range inputs of a panel.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum RangePreset {
+    LastDay,
+    LastWeek,
+    LastMonth,
+    MintingPeriod,
+}
+
+impl RangePreset {
+    /// The start timestamp of the range. The range always ends now.
+    fn start(&self) -> i64 {
+        let now = Utc::now().timestamp();
+        match self {
+            Self::LastDay => now - 24 * 3600,
+            Self::LastWeek => now - 7 * 24 * 3600,
+            Self::LastMonth => now - 30 * 24 * 3600,
+            Self::MintingPeriod => Period::current().start(),
+        }
+    }
+
+    /// The range expressed in (started) hours up to now.
+    fn hours(&self) -> usize {
+        let seconds = Utc::now().timestamp() - self.start();
+        ((seconds + 3599) / 3600) as usize
+    }
+
+    /// The range expressed as start and end date. Since dates don't carry a time, the range ends
+    /// at the start of today, so only full days are covered, and the start date is the day on
+    /// which the range starts.
+    fn dates(&self) -> (NaiveDate, NaiveDate) {
+        let date_of = |ts| {
+            Utc.timestamp_opt(ts, 0)
+                .single()
+                .expect("UTC time from timestamp is unambiguous")
+                .date_naive()
+        };
+        let end = date_of(Utc::now().timestamp());
+        let start = match self {
+            Self::LastDay => end - chrono::Duration::days(1),
+            Self::LastWeek => end - chrono::Duration::days(7),
+            Self::LastMonth => end - chrono::Duration::days(30),
+            Self::MintingPeriod => date_of(self.start()),
+        };
+        (start, end)
+    }
+}
+
+impl std::fmt::Display for RangePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LastDay => f.write_str("Last 24h"),
+            Self::LastWeek => f.write_str("Last 7d"),
+            Self::LastMonth => f.write_str("Last 30d"),
+            Self::MintingPeriod => f.write_str("This minting period"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 enum MenuSelection {
     ContractOverview,
     ContractDetails,
     NodeState,
     TotalBilled,
+    GracePeriod,
+    FarmOverview,
+    TwinOverview,
 }
 
 impl std::fmt::Display for MenuSelection {
@@ -1050,6 +3344,9 @@ impl std::fmt::Display for MenuSelection {
             Self::ContractDetails => f.write_str("Contract details"),
             Self::NodeState => f.write_str("Node state history"),
             Self::TotalBilled => f.write_str("Total billed on chain"),
+            Self::GracePeriod => f.write_str("Grace period watchlist"),
+            Self::FarmOverview => f.write_str("Farm overview"),
+            Self::TwinOverview => f.write_str("Twin overview"),
         }
     }
 }
@@ -1063,7 +3360,7 @@ const GIB: u64 = 1 << 30;
 /// Value of 1 TiB.
 const TIB: u64 = 1 << 40;
 
-fn fmt_resources(value: u64) -> String {
+pub(crate) fn fmt_resources(value: u64) -> String {
     match value {
         v if v > TIB => format!("{:.2} TiB", value as f64 / TIB as f64),
         v if v > GIB => format!("{:.2} GiB", value as f64 / GIB as f64),
@@ -1078,19 +3375,37 @@ fn fmt_resources(value: u64) -> String {
 const UP_ARROW_EMOJI: char = '⬆';
 /// Emoji for node going offline.
 const DOWN_ARROW_EMOJI: char = '⬇';
+/// Emoji for node being powered down.
+const PLUG_EMOJI: char = '🔌';
 /// Emoji for impossible reboot.
 const BOOM_EMOJI: char = '☢';
 /// Emoji for node uptime drift.
 const CLOCK_EMOJI: char = '🕑';
 /// Emoji for unknown state.
 const QUESTION_MARK_EMOJI: char = '？';
+/// Emoji for warnings about node behavior.
+const WARNING_EMOJI: char = '⚠';
+
+/// Window in seconds in which boots are counted to detect a flapping node.
+const FLAPPING_WINDOW: i64 = 60 * 60;
+/// Amount of boots in [`FLAPPING_WINDOW`] above which a node is considered to be flapping.
+const FLAPPING_THRESHOLD: usize = 3;
+/// Default amount of time after a period of which uptime events are fetched, to determine the
+/// state of a node at the end of the period, like minting does.
+pub(crate) const POST_PERIOD_UPTIME_FETCH: i64 = 3 * 60 * 60;
+/// Availability of a node in a period below which it is flagged as unhealthy.
+const LOW_AVAILABILITY: f64 = 0.9;
 
-fn node_state_formatted(state: NodeState) -> (char, String) {
+pub(crate) fn node_state_formatted(state: NodeState) -> (char, String) {
     match state {
         NodeState::Offline(ts) => (
             DOWN_ARROW_EMOJI,
             format!("Node went down at {}", fmt_local_time(ts)),
         ),
+        NodeState::PoweredDown(ts) => (
+            PLUG_EMOJI,
+            format!("Node was powered down at {}", fmt_local_time(ts)),
+        ),
         NodeState::Booted(ts) => (
             UP_ARROW_EMOJI,
             format!("Node booted at {}", fmt_local_time(ts),),
@@ -1116,7 +3431,20 @@ fn node_state_formatted(state: NodeState) -> (char, String) {
     }
 }
 
-fn fmt_local_time(ts: i64) -> String {
+fn missing_uptime_formatted(missing: MissingUptime) -> (char, String) {
+    match missing {
+        MissingUptime::DownEntirePeriod => (
+            DOWN_ARROW_EMOJI,
+            "No uptime events found, node is down for the entire period".to_string(),
+        ),
+        MissingUptime::UnknownNode => (
+            QUESTION_MARK_EMOJI,
+            "Unknown node ID, this node does not exist on this network".to_string(),
+        ),
+    }
+}
+
+pub(crate) fn fmt_local_time(ts: i64) -> String {
     Local
         .timestamp_opt(ts, 0)
         .single()
@@ -1125,11 +3453,32 @@ fn fmt_local_time(ts: i64) -> String {
         .to_string()
 }
 
-/// Amount of the smallest on chain currency unit which equate 1 TFT. In other words, 1 TFT can be
-/// split up in this many pieces.
-const UNITS_PER_TFT: u64 = 10_000_000;
+/// Format the start of a bill bucket. Hour buckets show the full local time, larger buckets only
+/// show the date of the bucket start in UTC, since that is where these buckets are aligned.
+pub(crate) fn fmt_bucket(ts: i64, bucket: BillBucket) -> String {
+    let format = match bucket {
+        BillBucket::Hour => return fmt_local_time(ts),
+        BillBucket::Day | BillBucket::Week => "%d/%m",
+        BillBucket::Month => "%m/%Y",
+    };
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|date| date.format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// Format an amount as a whole amount of TFT, for axis labels.
+fn fmt_whole_tft(amount: u64) -> String {
+    let tft = Denomination::TFT;
+    format!("{} {}", amount / tft.units_per_token, tft.symbol)
+}
 
 /// Format an amount as value in TFT
-fn fmt_tft(amount: u64) -> String {
-    format!("{}.{} TFT", amount / UNITS_PER_TFT, amount % UNITS_PER_TFT)
+pub(crate) fn fmt_tft(amount: u64) -> String {
+    Denomination::TFT.format(amount)
+}
+
+/// Format an amount as value in TFT with at most 2 decimals, for tables with many amounts.
+fn fmt_tft_compact(amount: u64) -> String {
+    Denomination::TFT.format_with(amount, FormatOptions::COMPACT)
 }
@@ -1,20 +1,24 @@
-use crate::compat::{de_i64, de_u64};
+use crate::compat::{de_i64, de_u64, ser_i64, ser_u64};
 use serde::{Deserialize, Serialize};
 
 /// A contract bill report on the grid.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub struct ContractBillReport {
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     pub amount_billed: u64,
-    #[serde(rename = "contractID", deserialize_with = "de_u64")]
+    #[serde(
+        rename = "contractID",
+        deserialize_with = "de_u64",
+        serialize_with = "ser_u64"
+    )]
     pub contract_id: u64,
-    #[serde(deserialize_with = "de_i64")]
+    #[serde(deserialize_with = "de_i64", serialize_with = "ser_i64")]
     pub timestamp: i64,
     pub discount_received: DiscountLevel,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Level of discount applied for a contract bill.
 pub enum DiscountLevel {
     None,
@@ -23,3 +27,122 @@ pub enum DiscountLevel {
     Silver,
     Gold,
 }
+
+impl DiscountLevel {
+    /// All discount levels, from no discount to the highest discount.
+    pub const ALL: [DiscountLevel; 5] = [
+        Self::None,
+        Self::Default,
+        Self::Bronze,
+        Self::Silver,
+        Self::Gold,
+    ];
+}
+
+/// Sorts a series of [`ContractBillReport`]s in ascending timestamp order. Reports with the same
+/// timestamp keep their relative order. The client returns bill reports in this order, but
+/// reports combined from multiple requests need to be sorted again.
+pub fn sort_reports(reports: &mut [ContractBillReport]) {
+    reports.sort_by_key(|report| report.timestamp);
+}
+
+/// Merges the bill reports of `new` into `reports`, which must already be in ascending timestamp
+/// order as returned by [`sort_reports`]. This avoids sorting all reports again when the reports
+/// of multiple requests come in one by one. Reports with the same timestamp keep their relative
+/// order, with the reports which were already present first.
+pub fn merge_reports(reports: &mut Vec<ContractBillReport>, new: &[ContractBillReport]) {
+    let mut new = new.to_vec();
+    sort_reports(&mut new);
+    let old = std::mem::replace(reports, Vec::with_capacity(reports.len() + new.len()));
+    let (mut old, mut new) = (old.into_iter().peekable(), new.into_iter().peekable());
+    while let (Some(o), Some(n)) = (old.peek(), new.peek()) {
+        if o.timestamp <= n.timestamp {
+            reports.extend(old.next());
+        } else {
+            reports.extend(new.next());
+        }
+    }
+    reports.extend(old);
+    reports.extend(new);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_reports, sort_reports, ContractBillReport, DiscountLevel};
+    use crate::analytics::{aggregate_bills, BillBucket};
+
+    #[test]
+    fn bill_report_round_trip() {
+        let report = ContractBillReport {
+            amount_billed: 1_234_567,
+            contract_id: 42,
+            timestamp: 1663850262,
+            discount_received: DiscountLevel::Gold,
+        };
+
+        let raw = serde_json::to_value(report).expect("Can serialize a bill report");
+        assert_eq!(raw["amountBilled"], "1234567");
+        assert_eq!(raw["contractID"], "42");
+        assert_eq!(raw["timestamp"], "1663850262");
+
+        let decoded: ContractBillReport =
+            serde_json::from_value(raw).expect("Can deserialize a serialized bill report");
+        assert_eq!(decoded.amount_billed, report.amount_billed);
+        assert_eq!(decoded.contract_id, report.contract_id);
+        assert_eq!(decoded.timestamp, report.timestamp);
+        assert!(matches!(decoded.discount_received, DiscountLevel::Gold));
+    }
+
+    #[test]
+    fn sort_unsorted_reports() {
+        let report = |contract_id, timestamp| ContractBillReport {
+            amount_billed: 10,
+            contract_id,
+            timestamp,
+            discount_received: DiscountLevel::None,
+        };
+        // As if the bills of 2 hours were fetched concurrently, and the later hour finished first.
+        let mut reports = vec![
+            report(1, 7_300),
+            report(2, 7_200),
+            report(1, 3_700),
+            report(3, 3_600),
+            report(2, 3_600),
+        ];
+        let aggregated = aggregate_bills(&reports, BillBucket::Hour);
+
+        sort_reports(&mut reports);
+
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| (report.contract_id, report.timestamp))
+                .collect::<Vec<_>>(),
+            [(3, 3_600), (2, 3_600), (1, 3_700), (2, 7_200), (1, 7_300)]
+        );
+        // Charts aggregate bills in ordered buckets, so they don't depend on the input order.
+        assert_eq!(aggregate_bills(&reports, BillBucket::Hour), aggregated);
+    }
+
+    #[test]
+    fn merge_reports_in_order() {
+        let report = |contract_id, timestamp| ContractBillReport {
+            amount_billed: 10,
+            contract_id,
+            timestamp,
+            discount_received: DiscountLevel::None,
+        };
+        let mut reports = vec![report(1, 3_600), report(1, 10_800)];
+
+        merge_reports(&mut reports, &[report(2, 10_800), report(2, 7_200)]);
+        merge_reports(&mut reports, &[report(3, 0)]);
+
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| (report.contract_id, report.timestamp))
+                .collect::<Vec<_>>(),
+            [(3, 0), (1, 3_600), (2, 7_200), (1, 10_800), (2, 10_800)]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Caching of graphql responses.
+//!
+//! The [`Client`](crate::graphql::Client) caches responses in a [`ResponseStore`]. By default this
+//! is the in-memory [`ResponseCache`], but embedders can plug in their own store, e.g. to persist
+//! responses on disk between runs.
+
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, time::Duration};
+
+/// Key of a cached response, the name of the operation and the serialized variables it was
+/// executed with. Requests with the same key get the same response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    operation_name: String,
+    variables: String,
+}
+
+impl CacheKey {
+    /// Create a new key for the operation with the given serialized variables.
+    pub fn new(operation_name: &str, variables: &str) -> Self {
+        Self {
+            operation_name: operation_name.to_string(),
+            variables: variables.to_string(),
+        }
+    }
+
+    /// The name of the operation of the request.
+    pub fn operation_name(&self) -> &str {
+        &self.operation_name
+    }
+
+    /// The variables of the request, serialized as JSON.
+    pub fn variables(&self) -> &str {
+        &self.variables
+    }
+}
+
+/// A store of decoded response data, used by the [`Client`](crate::graphql::Client) to cache
+/// responses. The store decides which data it keeps and for how long.
+pub trait ResponseStore: Send {
+    /// Get the data stored for the key at `now`, if any.
+    fn get(&mut self, key: &CacheKey, now: DateTime<Utc>) -> Option<serde_json::Value>;
+
+    /// Store the data of a response which was received at `now`.
+    fn insert(&mut self, key: CacheKey, data: serde_json::Value, now: DateTime<Utc>);
+
+    /// Remove all stored data.
+    fn clear(&mut self);
+}
+
+struct CacheEntry {
+    data: serde_json::Value,
+    inserted_at: DateTime<Utc>,
+    /// Value of the use counter of the cache when this entry was last used.
+    last_used: u64,
+}
+
+/// A cache of decoded response data with a maximum amount of entries, and a time to live for every
+/// entry. If the cache is full, the least recently used entry is evicted.
+pub struct ResponseCache {
+    capacity: usize,
+    ttl: chrono::Duration,
+    entries: HashMap<CacheKey, CacheEntry>,
+    uses: u64,
+}
+
+impl ResponseCache {
+    /// Create a new cache holding at most `capacity` entries, which are valid for `ttl`.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl: chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+            entries: HashMap::with_capacity(capacity),
+            uses: 0,
+        }
+    }
+}
+
+impl ResponseStore for ResponseCache {
+    /// Get the data cached for the key, if it is present and has not expired at `now`.
+    fn get(&mut self, key: &CacheKey, now: DateTime<Utc>) -> Option<serde_json::Value> {
+        let entry = self.entries.get_mut(key)?;
+        if now - entry.inserted_at >= self.ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        self.uses += 1;
+        entry.last_used = self.uses;
+        Some(entry.data.clone())
+    }
+
+    /// Insert data in the cache at time `now`, evicting expired entries first, and the least
+    /// recently used entry if the cache is still full.
+    fn insert(&mut self, key: CacheKey, data: serde_json::Value, now: DateTime<Utc>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, entry| now - entry.inserted_at < ttl);
+        }
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            if let Some(lru) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            {
+                self.entries.remove(&lru);
+            }
+        }
+        self.uses += 1;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                data,
+                inserted_at: now,
+                last_used: self.uses,
+            },
+        );
+    }
+
+    /// Remove all entries from the cache.
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheKey, ResponseCache, ResponseStore};
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn cache_entries_expire() {
+        let mut cache = ResponseCache::new(10, Duration::from_secs(60));
+        let now = Utc.timestamp_opt(1_000, 0).unwrap();
+        let key = CacheKey::new("contracts", r#"{"offset":0}"#);
+        cache.insert(key.clone(), json!([1, 2]), now);
+
+        assert_eq!(
+            cache.get(&key, now + chrono::Duration::seconds(59)),
+            Some(json!([1, 2]))
+        );
+        assert_eq!(
+            cache.get(&CacheKey::new("contracts", r#"{"offset":1000}"#), now),
+            None
+        );
+        assert_eq!(cache.get(&key, now + chrono::Duration::seconds(60)), None);
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used() {
+        let mut cache = ResponseCache::new(2, Duration::from_secs(60));
+        let now = Utc.timestamp_opt(1_000, 0).unwrap();
+        let key = |offset: usize| CacheKey::new("contracts", &offset.to_string());
+        cache.insert(key(0), json!(0), now);
+        cache.insert(key(1), json!(1), now);
+        // Use the oldest entry, so the second one is now the least recently used.
+        assert_eq!(cache.get(&key(0), now), Some(json!(0)));
+        cache.insert(key(2), json!(2), now);
+
+        assert_eq!(cache.get(&key(0), now), Some(json!(0)));
+        assert_eq!(cache.get(&key(1), now), None);
+        assert_eq!(cache.get(&key(2), now), Some(json!(2)));
+
+        cache.clear();
+        assert_eq!(cache.get(&key(0), now), None);
+    }
+}
@@ -0,0 +1,347 @@
+//! Charts of grid data, drawn as plots in the UI.
+
+use std::collections::BTreeMap;
+
+use chrono::{TimeZone, Utc};
+use eframe::egui::{
+    self,
+    plot::{Bar, BarChart, Legend, Plot, PlotPoints, Polygon},
+    Color32,
+};
+use tfgrid_graphql::{
+    analytics::{aggregate_bills, clip_windows, hourly_uptime, state_distribution, BillBucket},
+    bill_report::{ContractBillReport, DiscountLevel},
+    contract::ContractState,
+    graphql::{Contracts, ALL_CONTRACT_STATES},
+    uptime::NodeStateChange,
+};
+
+use crate::app::{fmt_bucket, fmt_tft};
+
+/// Draw a horizontal bar chart of the contracts which were billed the most, as returned by
+/// [`top_contracts`](tfgrid_graphql::analytics::top_contracts), with the contract billed the most
+/// at the top.
+pub fn graph_top_contracts(ui: &mut egui::Ui, top: &[(u64, u64)]) {
+    if top.is_empty() {
+        ui.label("No contracts were billed");
+        return;
+    }
+
+    // Bars are drawn from the bottom up, so reverse the ranking to put the top contract on top.
+    let contract_ids = top.iter().rev().map(|(id, _)| *id).collect::<Vec<_>>();
+    let bars = top
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(position, (contract_id, billed))| {
+            Bar::new(position as f64, *billed as f64).name(format!("Contract {contract_id}"))
+        })
+        .collect();
+    let chart = BarChart::new(bars)
+        .horizontal()
+        .name("billed")
+        .element_formatter(Box::new(|bar, _| {
+            format!("{}: {}", bar.name, fmt_tft(bar.value as u64))
+        }));
+
+    Plot::new("top_contracts_plot")
+        .view_aspect(2.)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_x(0.)
+        .x_axis_formatter(|value, _range| fmt_tft(value.max(0.) as u64))
+        .y_axis_formatter(move |value, _range| {
+            // Only label the positions of the bars.
+            if value.fract() != 0. || value < 0. {
+                return String::new();
+            }
+            contract_ids
+                .get(value as usize)
+                .map(|id| id.to_string())
+                .unwrap_or_default()
+        })
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(chart);
+        });
+}
+
+/// Draw a bar chart of the amount of contracts of all types in every [`ContractState`]. Every
+/// state has a bar, even if there are no contracts in that state.
+pub fn graph_state_distribution(ui: &mut egui::Ui, contracts: &Contracts) {
+    let distribution = state_distribution(contracts);
+    let bars = ALL_CONTRACT_STATES
+        .iter()
+        .enumerate()
+        .map(|(position, state)| {
+            Bar::new(
+                position as f64,
+                distribution.get(state).copied().unwrap_or_default() as f64,
+            )
+            .name(state)
+            .fill(state_color(*state))
+        })
+        .collect();
+    let chart = BarChart::new(bars)
+        .name("contracts")
+        .element_formatter(Box::new(|bar, _| {
+            format!("{}: {} contracts", bar.name, bar.value)
+        }));
+
+    Plot::new("state_distribution_plot")
+        .view_aspect(3.)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_y(0.)
+        .x_axis_formatter(|value, _range| {
+            // Only label the positions of the bars.
+            if value.fract() != 0. || value < 0. {
+                return String::new();
+            }
+            ALL_CONTRACT_STATES
+                .get(value as usize)
+                .map(|state| state.to_string())
+                .unwrap_or_default()
+        })
+        .y_axis_formatter(|value, _range| {
+            if value.fract() == 0. {
+                format!("{value}")
+            } else {
+                String::new()
+            }
+        })
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(chart);
+        });
+}
+
+/// Color to use for a [`ContractState`] in charts.
+fn state_color(state: ContractState) -> Color32 {
+    match state {
+        ContractState::Created => Color32::DARK_GREEN,
+        ContractState::GracePeriod => Color32::GOLD,
+        ContractState::OutOfFunds => Color32::DARK_RED,
+        ContractState::Deleted => Color32::GRAY,
+    }
+}
+
+/// Amount of seconds in an hour.
+pub(crate) const SECONDS_IN_HOUR: i64 = 60 * 60;
+
+/// Draw a histogram of the amount billed per hour in the given bill reports, with the windows in
+/// which a node was down, as returned by
+/// [`downtime_windows`](tfgrid_graphql::uptime::downtime_windows), drawn as shaded bands. This
+/// shows if contracts stopped being billed because their node was down. Only the part of the
+/// windows in the time range of the bills is drawn.
+pub fn graph_bills_with_outages(
+    ui: &mut egui::Ui,
+    reports: &[ContractBillReport],
+    downtime_windows: &[(i64, i64)],
+) {
+    let hourly = aggregate_bills(reports, BillBucket::Hour);
+    let (Some(first), Some(last)) = (hourly.keys().next(), hourly.keys().next_back()) else {
+        ui.label("No contracts were billed");
+        return;
+    };
+    let outages = clip_windows(downtime_windows, *first, last + SECONDS_IN_HOUR);
+    let max = hourly.values().copied().max().unwrap_or_default() as f64;
+
+    let bars = hourly
+        .iter()
+        .map(|(hour, billed)| {
+            Bar::new((hour + SECONDS_IN_HOUR / 2) as f64, *billed as f64)
+                .width(SECONDS_IN_HOUR as f64 * 0.9)
+        })
+        .collect();
+    let chart = BarChart::new(bars)
+        .name("billed")
+        .element_formatter(Box::new(|bar, _| {
+            format!(
+                "{}: {}",
+                fmt_utc_hour(bar.argument as i64),
+                fmt_tft(bar.value as u64)
+            )
+        }));
+    let bands = outages
+        .into_iter()
+        .map(|(from, to)| {
+            Polygon::new(PlotPoints::new(vec![
+                [from as f64, 0.],
+                [to as f64, 0.],
+                [to as f64, max],
+                [from as f64, max],
+            ]))
+            .name("node down")
+            .color(Color32::RED)
+            .fill_alpha(0.2)
+            .width(0.)
+        })
+        .collect::<Vec<_>>();
+
+    Plot::new("bills_with_outages_plot")
+        .view_aspect(3.)
+        .include_y(0.)
+        .legend(Legend::default())
+        .label_formatter(|_, value| fmt_utc_hour(value.x as i64))
+        .x_axis_formatter(|value, _range| fmt_utc_hour(value as i64))
+        .y_axis_formatter(|value, _range| fmt_tft(value.max(0.) as u64))
+        .show(ui, |plot_ui| {
+            for band in bands {
+                plot_ui.polygon(band);
+            }
+            plot_ui.bar_chart(chart);
+        });
+}
+
+/// Draw stacked bars of the share of every [`DiscountLevel`] in the amount billed per bucket, as
+/// returned by [`discount_distribution`](tfgrid_graphql::analytics::discount_distribution). A twin
+/// which slips to a lower discount level is running low on funds, so a growing share of lower
+/// levels is an early warning of funding problems.
+pub fn graph_discount_shares(
+    ui: &mut egui::Ui,
+    distribution: &BTreeMap<i64, BTreeMap<DiscountLevel, u64>>,
+    bucket: BillBucket,
+) {
+    if distribution.is_empty() {
+        ui.label("No contracts were billed");
+        return;
+    }
+
+    // Bars of a month are a bit too narrow for long months, which is fine for a share chart.
+    let width = match bucket {
+        BillBucket::Hour => SECONDS_IN_HOUR,
+        BillBucket::Day => 24 * SECONDS_IN_HOUR,
+        BillBucket::Week => 7 * 24 * SECONDS_IN_HOUR,
+        BillBucket::Month => 28 * 24 * SECONDS_IN_HOUR,
+    };
+    let mut charts: Vec<BarChart> = Vec::with_capacity(DiscountLevel::ALL.len());
+    for level in DiscountLevel::ALL {
+        let bars = distribution
+            .iter()
+            .map(|(start, levels)| {
+                let total = levels.values().sum::<u64>().max(1);
+                let share =
+                    levels.get(&level).copied().unwrap_or_default() as f64 * 100. / total as f64;
+                Bar::new((start + width / 2) as f64, share)
+                    .width(width as f64 * 0.9)
+                    .name(fmt_bucket(*start, bucket))
+            })
+            .collect();
+        let chart = BarChart::new(bars)
+            .name(format!("{level:?}"))
+            .color(discount_color(level))
+            .element_formatter(Box::new(move |bar, _| {
+                format!("{}: {level:?} {:.1}%", bar.name, bar.value)
+            }))
+            .stack_on(&charts.iter().collect::<Vec<_>>());
+        charts.push(chart);
+    }
+
+    Plot::new("discount_shares_plot")
+        .view_aspect(3.)
+        .include_y(0.)
+        .include_y(100.)
+        .legend(Legend::default())
+        .label_formatter(move |_, value| fmt_bucket(value.x as i64, bucket))
+        .x_axis_formatter(move |value, _range| fmt_bucket(value as i64, bucket))
+        .y_axis_formatter(|value, _range| format!("{value}%"))
+        .show(ui, |plot_ui| {
+            for chart in charts {
+                plot_ui.bar_chart(chart);
+            }
+        });
+}
+
+/// Color to use for a [`DiscountLevel`] in charts.
+fn discount_color(level: DiscountLevel) -> Color32 {
+    match level {
+        DiscountLevel::None => Color32::GRAY,
+        DiscountLevel::Default => Color32::LIGHT_BLUE,
+        DiscountLevel::Bronze => Color32::from_rgb(205, 127, 50),
+        DiscountLevel::Silver => Color32::from_rgb(192, 192, 192),
+        DiscountLevel::Gold => Color32::GOLD,
+    }
+}
+
+/// Format the hour containing a timestamp in UTC.
+fn fmt_utc_hour(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|hour| hour.format("%d/%m %H:00 UTC").to_string())
+        .unwrap_or_default()
+}
+
+/// Amount of seconds in a day.
+const SECONDS_IN_DAY: i64 = 24 * 60 * 60;
+
+/// Draw a heatmap of the uptime of a node in the period from `start` to `end`, based on the state
+/// changes in that period. Every column is a day, and every row an hour of that day, both in UTC.
+/// The color of a cell shows the fraction of that hour the node was up. Hours outside of the
+/// period are not drawn, so the first and last day might only be partially filled.
+pub fn graph_uptime_heatmap(ui: &mut egui::Ui, changes: &[NodeStateChange], start: i64, end: i64) {
+    let cells = hourly_uptime(changes, start, end)
+        .into_iter()
+        .map(|(hour, uptime)| {
+            let day = hour.div_euclid(SECONDS_IN_DAY) as f64;
+            let hour_of_day = (hour.rem_euclid(SECONDS_IN_DAY) / 3600) as f64;
+            Polygon::new(PlotPoints::new(vec![
+                [day, hour_of_day],
+                [day + 1., hour_of_day],
+                [day + 1., hour_of_day + 1.],
+                [day, hour_of_day + 1.],
+            ]))
+            .color(uptime_color(uptime))
+            .fill_alpha(1.)
+            .width(0.)
+        })
+        .collect::<Vec<_>>();
+
+    Plot::new("uptime_heatmap_plot")
+        .view_aspect(3.)
+        .include_y(0.)
+        .include_y(24.)
+        .label_formatter(|_, value| {
+            let hour = value.x.floor() as i64 * SECONDS_IN_DAY + value.y.floor() as i64 * 3600;
+            Utc.timestamp_opt(hour, 0)
+                .single()
+                .map(|hour| hour.format("%d/%m/%Y %H:00 UTC").to_string())
+                .unwrap_or_default()
+        })
+        .x_axis_formatter(|value, _range| {
+            Utc.timestamp_opt(value as i64 * SECONDS_IN_DAY, 0)
+                .single()
+                .map(|day| day.format("%d/%m").to_string())
+                .unwrap_or_default()
+        })
+        .y_axis_formatter(|value, _range| {
+            if (0. ..=24.).contains(&value) && value.fract() == 0. {
+                format!("{value:02}:00")
+            } else {
+                String::new()
+            }
+        })
+        .show(ui, |plot_ui| {
+            for cell in cells {
+                plot_ui.polygon(cell);
+            }
+        });
+
+    // Color scale legend.
+    ui.horizontal(|ui| {
+        ui.label("Uptime: 0%");
+        for step in 0..=10 {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(16., 16.), egui::Sense::hover());
+            ui.painter()
+                .rect_filled(rect, 0., uptime_color(step as f64 / 10.));
+        }
+        ui.label("100%");
+    });
+}
+
+/// Color of an uptime fraction in the heatmap, going from red for no uptime to green for full
+/// uptime.
+fn uptime_color(uptime: f64) -> Color32 {
+    let uptime = uptime.clamp(0., 1.);
+    Color32::from_rgb((200. * (1. - uptime)) as u8, (180. * uptime) as u8, 40)
+}
@@ -0,0 +1,594 @@
+//! Command line interface, to query a graphql instance without starting the UI. Commands run to
+//! completion on a single threaded runtime, and print their results as tables.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use crate::{
+    app::{
+        fmt_bucket, fmt_local_time, fmt_resources, fmt_tft, node_state_formatted,
+        POST_PERIOD_UPTIME_FETCH,
+    },
+    charts::SECONDS_IN_HOUR,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use prettytable::{format::TableFormat, row, Table};
+use tfgrid_graphql::{
+    analytics::{aggregate_bills, uptime_summary, BillBucket},
+    contract::ContractState,
+    graphql::{self, Client, Contracts, ALL_CONTRACT_STATES},
+    period::Period,
+    report,
+    uptime::calculate_node_state_changes,
+};
+
+/// The states of a contract which are considered to be active.
+const ACTIVE_CONTRACT_STATES: [ContractState; 2] =
+    [ContractState::Created, ContractState::GracePeriod];
+
+/// Amount of characters of the deployment data shown in the node contract table.
+const DEPLOYMENT_DATA_WIDTH: usize = 30;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct Cli {
+    #[arg(short, long, value_enum, default_value_t = Network::Mainnet)]
+    pub network: Network,
+    /// Run a single command and print the result, instead of starting the UI
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Qanet,
+    Devnet,
+}
+
+/// Size of the time buckets in which bills are broken down.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Bucket {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Calculate the state changes of a node
+    NodeState {
+        /// The id of the node for which to check the node state
+        node_id: u32,
+        /// The period for which to check the uptime
+        period: i64,
+    },
+    /// List contracts with given parameters
+    ///
+    /// All provided filters apply to the result at the same time, e.g. if both nodes and twins are
+    /// set, only contracts deployed on the given nodes by the given twins will be returned, but
+    /// contracts on the given nodes by different twins, or contracts deployed by the twins on
+    /// different nodes will be excluded.
+    Contracts {
+        #[command(flatten)]
+        filters: ContractFilters,
+    },
+    /// Calculate the total amount billed for the last hours
+    TotalBilled {
+        /// Amount of hours to get bills for
+        hours: u32,
+        /// Also show the amount billed per bucket of this size
+        #[arg(long, value_enum)]
+        bucket: Option<Bucket>,
+    },
+    /// Generate a markdown report of the contracts and bills of a twin
+    Report {
+        /// The id of the twin to report on
+        #[arg(long = "twin")]
+        twin_id: u32,
+    },
+    /// Report the availability, downtime and failures of a node in a minting period
+    NodeReport {
+        /// The id of the node to report on
+        #[arg(long = "node")]
+        node_id: u32,
+        /// The offset of the minting period to report on, 0 being the current period
+        #[arg(long, default_value_t = 0)]
+        period: i64,
+        /// Write a heatmap of the hourly uptime of the node as SVG to this file
+        #[arg(long)]
+        heatmap: Option<PathBuf>,
+    },
+    /// Serve Prometheus metrics of bills, node uptime and contracts over HTTP
+    #[cfg(feature = "metrics")]
+    ServeMetrics {
+        /// Port to serve the metrics on
+        #[arg(long)]
+        port: u16,
+        /// Nodes for which to report the uptime and the contracts
+        #[arg(short = 'n', long = "nodes")]
+        node_ids: Vec<u32>,
+        /// Seconds between refreshes of the metrics
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+}
+
+#[derive(Args)]
+/// Filter fields for listing contracts.
+pub struct ContractFilters {
+    /// Nodes for which to list contracts
+    #[arg(short = 'n', long = "nodes")]
+    node_ids: Option<Vec<u32>>,
+    /// Twin ID's which must own the contracts
+    #[arg(short = 't', long = "twins")]
+    twin_ids: Option<Vec<u32>>,
+    /// Contract ID's to list
+    #[arg(short = 'c', long = "contracts")]
+    contract_ids: Vec<u64>,
+    /// Solution provider ID's for which to list contracts
+    #[arg(short = 's', long)]
+    solution_provider_ids: Vec<u32>,
+    /// Deployment hashes of the node contracts to list. Only node contracts have a deployment
+    /// hash, so no other contracts are listed if this is set
+    #[arg(long = "hashes")]
+    deployment_hashes: Option<Vec<String>>,
+    /// Include expired contracts as well
+    #[arg(short = 'e', long)]
+    include_expired: bool,
+    /// Caluclate the total cost in TFT of all contracts. This might take a while
+    ///
+    /// This does not account for the variance in TFT price, and just shows the total amount of
+    /// TFT billed over the life of the contract. Specifically, for longer running contracts,
+    /// this might give a wrong idea of the average cost of the contract over time, as drops in
+    /// TFT price will cause this amount to inflate, and similarly spikes in TFT price will
+    /// cause this amount to deflate. As a result, this value is just informational.
+    #[arg(long)]
+    include_cost: bool,
+    /// Calculate the total amount of public network used by the contract. This might take a
+    /// while.
+    #[arg(long)]
+    include_network: bool,
+}
+
+/// Run a single command against the given network, and print the result.
+pub fn run(network: Network, command: Commands) -> Result<(), Box<dyn Error>> {
+    let client = match network {
+        Network::Mainnet => Client::mainnet()?,
+        Network::Testnet => Client::testnet()?,
+        Network::Qanet => Client::qanet()?,
+        Network::Devnet => Client::devnet()?,
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async move {
+        match command {
+            Commands::NodeState { node_id, period } => {
+                calculate_node_states(client, node_id, Period::at_offset(period)).await
+            }
+            Commands::Contracts { filters } => list_contracts(client, filters).await,
+            Commands::TotalBilled { hours, bucket } => {
+                calculate_contract_bills(client, hours, bucket).await
+            }
+            Commands::Report { twin_id } => twin_report(client, twin_id).await,
+            Commands::NodeReport {
+                node_id,
+                period,
+                heatmap,
+            } => node_report(client, node_id, Period::at_offset(period), heatmap).await,
+            #[cfg(feature = "metrics")]
+            Commands::ServeMetrics {
+                port,
+                node_ids,
+                interval,
+            } => Ok(tfgrid_graphql::metrics::serve(
+                client,
+                node_ids,
+                port,
+                std::time::Duration::from_secs(interval),
+            )
+            .await?),
+        }
+    })
+}
+
+async fn calculate_node_states(
+    client: Client,
+    node_id: u32,
+    period: Period,
+) -> Result<(), Box<dyn Error>> {
+    println!(
+        "Checking node state between {} and {}",
+        fmt_local_time(period.start()),
+        fmt_local_time(period.end())
+    );
+    println!("Fetching uptime events");
+    let uptimes = client
+        .uptime_events(
+            node_id,
+            period.start(),
+            period.end() + POST_PERIOD_UPTIME_FETCH,
+        )
+        .await?;
+
+    if uptimes.is_empty() {
+        println!("No uptime events found, node is down for the entire period");
+        return Ok(());
+    }
+
+    println!("Calculating node changes");
+    let node_states = calculate_node_state_changes(&uptimes, period.start(), period.end());
+    println!();
+
+    let mut state_table = Table::new();
+    state_table.set_titles(row![
+        l->"",
+        l->"Event",
+        l->"Event detected",
+    ]);
+    for ns in node_states {
+        let (emoji, msg) = node_state_formatted(ns.state());
+        state_table.add_row(row![
+            l->emoji,
+            l->msg,
+            l->fmt_local_time(ns.timestamp()),
+        ]);
+    }
+    let mut fmt = TableFormat::new();
+    fmt.padding(1, 1);
+    *state_table.get_format() = fmt;
+    state_table.printstd();
+    Ok(())
+}
+
+async fn list_contracts(client: Client, filters: ContractFilters) -> Result<(), Box<dyn Error>> {
+    println!("Fetching contracts");
+    let ContractFilters {
+        node_ids,
+        twin_ids,
+        contract_ids,
+        solution_provider_ids,
+        deployment_hashes,
+        include_expired,
+        include_cost,
+        include_network,
+    } = filters;
+    let mut query = graphql::ContractFilters::default()
+        .states(if include_expired {
+            &ALL_CONTRACT_STATES[..]
+        } else {
+            &ACTIVE_CONTRACT_STATES[..]
+        })
+        .contract_ids(contract_ids)
+        .spids(solution_provider_ids);
+    if let Some(node_ids) = node_ids {
+        query = query.nodes(node_ids);
+    }
+    if let Some(twin_ids) = twin_ids {
+        query = query.twins(twin_ids);
+    }
+    if let Some(deployment_hashes) = deployment_hashes {
+        query = query.deployment_hashes(deployment_hashes);
+    }
+    let contracts = client.contracts(&query).await?;
+    if contracts.is_empty() {
+        println!();
+        println!("No contracts found for this query");
+        return Ok(());
+    }
+    let contract_ids = contracts.all_contract_ids();
+    let mut contract_costs = if include_cost {
+        println!("Fetching contract bills");
+        client
+            .contract_bill_reports(None, None, Some(&contract_ids), None)
+            .await?
+            .into_iter()
+            .fold(HashMap::new(), |mut acc: HashMap<u64, u64>, value| {
+                *acc.entry(value.contract_id).or_default() += value.amount_billed;
+                acc
+            })
+    } else {
+        HashMap::new()
+    };
+    let total_cost = include_cost.then(|| contract_costs.values().sum::<u64>());
+    let mut network_usage = if include_network {
+        println!("Fetching NRU consumption reports");
+        client
+            .nru_consumptions(None, None, Some(&contract_ids))
+            .await?
+            .into_iter()
+            .fold(HashMap::new(), |mut acc: HashMap<u64, u64>, value| {
+                *acc.entry(value.contract_id).or_default() += value.nru;
+                acc
+            })
+    } else {
+        HashMap::new()
+    };
+    let Contracts {
+        node_contracts,
+        name_contracts,
+        rent_contracts,
+    } = &contracts;
+    if !node_contracts.is_empty() {
+        let mut node_table = Table::new();
+        node_table.set_titles(row![
+            r->"Contract ID",
+            r->"Node ID",
+            r->"Owner",
+            r->"Solution Provider ID",
+            r->"Cru",
+            r->"Mru",
+            r->"Sru",
+            r->"Hru",
+            r->"Nru",
+            r->"Public IPs",
+            r->"Total Cost",
+            r->"Deployment Hash",
+            r->"Deployment Data",
+            r->"Created",
+            r->"State"
+        ]);
+        for contract in node_contracts {
+            node_table.add_row(row![
+                r->contract.contract_id,
+                r->contract.node_id,
+                r->contract.twin_id,
+                r->fmt_solution_provider(contract.solution_provider_id),
+                r->if let Some(ref r) = contract.resources_used {
+                    format!("{}", r.cru)
+                } else {
+                    "-".to_string()
+                },
+                r->if let Some(ref r) = contract.resources_used {
+                    fmt_resources(r.mru)
+                } else {
+                    "-".to_string()
+                },
+                r->if let Some(ref r) = contract.resources_used {
+                    fmt_resources(r.sru)
+                } else {
+                    "-".to_string()
+                },
+                r->if let Some(ref r) = contract.resources_used {
+                    fmt_resources(r.hru)
+                } else {
+                    "-".to_string()
+                },
+                r->fmt_resources(network_usage.remove(&contract.contract_id).unwrap_or_default()),
+                r->contract.number_of_public_ips,
+                r->fmt_tft(contract_costs.remove(&contract.contract_id).unwrap_or_default()),
+                r->contract.deployment_hash,
+                r->fmt_deployment_data(&contract.deployment_data),
+                r->fmt_local_time(contract.created_at / 1000),
+                r->contract.state,
+            ]);
+        }
+        node_table.printstd();
+    }
+    if !name_contracts.is_empty() {
+        let mut name_table = Table::new();
+        name_table.set_titles(row![
+            r->"Contract ID",
+            r->"Owner",
+            r->"Solution Provider ID",
+            r->"Name",
+            r->"Nru",
+            r->"Total Cost",
+            r->"Created",
+            r->"State"
+        ]);
+        for contract in name_contracts {
+            name_table.add_row(row![
+                r->contract.contract_id,
+                r->contract.twin_id,
+                r->fmt_solution_provider(contract.solution_provider_id),
+                r->contract.name,
+                r->fmt_resources(network_usage.remove(&contract.contract_id).unwrap_or_default()),
+                r->fmt_tft(contract_costs.remove(&contract.contract_id).unwrap_or_default()),
+                r->fmt_local_time(contract.created_at / 1000),
+                r->contract.state,
+            ]);
+        }
+        name_table.printstd();
+    }
+    if !rent_contracts.is_empty() {
+        let mut rent_table = Table::new();
+        rent_table.set_titles(row![
+            r->"Contract ID",
+            r->"Node ID",
+            r->"Owner",
+            r->"Solution Provider ID",
+            r->"Total Cost",
+            r->"Created",
+            r->"State"
+        ]);
+        for contract in rent_contracts {
+            rent_table.add_row(row![
+                r->contract.contract_id,
+                r->contract.node_id,
+                r->contract.twin_id,
+                r->fmt_solution_provider(contract.solution_provider_id),
+                r->fmt_tft(contract_costs.remove(&contract.contract_id).unwrap_or_default()),
+                r->fmt_local_time(contract.created_at / 1000),
+                r->contract.state,
+            ]);
+        }
+        rent_table.printstd();
+    }
+
+    println!();
+    print!("{}", report::contracts_summary(&contracts, total_cost));
+    Ok(())
+}
+
+async fn calculate_contract_bills(
+    client: Client,
+    hours: u32,
+    bucket: Option<Bucket>,
+) -> Result<(), Box<dyn Error>> {
+    println!("Calculating amount of tokens billed for the last {hours} hours");
+    println!("Fetching bill events");
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let start = now - SECONDS_IN_HOUR * hours as i64;
+    let bills = client
+        .contract_bill_reports(Some(start), Some(now), None, None)
+        .await?;
+    println!("Calculate total bill cost");
+    println!();
+
+    if let Some(bucket) = bucket {
+        let bucket = match bucket {
+            Bucket::Hour => BillBucket::Hour,
+            Bucket::Day => BillBucket::Day,
+            Bucket::Week => BillBucket::Week,
+            Bucket::Month => BillBucket::Month,
+        };
+        let mut bucket_table = Table::new();
+        bucket_table.set_titles(row![
+            l->format!("{bucket} starting at"),
+            r->"Billed",
+        ]);
+        for (start, billed) in aggregate_bills(&bills, bucket) {
+            bucket_table.add_row(row![
+                l->fmt_bucket(start, bucket),
+                r->fmt_tft(billed),
+            ]);
+        }
+        let mut fmt = TableFormat::new();
+        fmt.padding(1, 1);
+        *bucket_table.get_format() = fmt;
+        bucket_table.printstd();
+        println!();
+    }
+
+    println!(
+        "Total billed from {} to {}: ",
+        fmt_local_time(start),
+        fmt_local_time(now)
+    );
+    let total: u64 = bills.into_iter().map(|bill| bill.amount_billed).sum();
+    println!("\t{}", fmt_tft(total));
+    Ok(())
+}
+
+async fn twin_report(client: Client, twin_id: u32) -> Result<(), Box<dyn Error>> {
+    let contracts = client
+        .contracts(&graphql::ContractFilters::default().twins([twin_id]))
+        .await?;
+    let contract_ids = contracts.all_contract_ids();
+    let bills = client
+        .contract_bill_reports(None, None, Some(&contract_ids), None)
+        .await?;
+    print!(
+        "{}",
+        report::twin_report_markdown(twin_id, &contracts, &bills, &BTreeMap::new())
+    );
+    Ok(())
+}
+
+async fn node_report(
+    client: Client,
+    node_id: u32,
+    period: Period,
+    heatmap: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    println!(
+        "Reporting on node {node_id} between {} and {}",
+        fmt_local_time(period.start()),
+        fmt_local_time(period.end())
+    );
+    println!("Fetching uptime events");
+    let uptimes = client
+        .uptime_events(
+            node_id,
+            period.start(),
+            period.end() + POST_PERIOD_UPTIME_FETCH,
+        )
+        .await?;
+
+    if uptimes.is_empty() {
+        println!("No uptime events found, node is down for the entire period");
+        return Ok(());
+    }
+
+    let changes = calculate_node_state_changes(&uptimes, period.start(), period.end());
+    let summary = uptime_summary(&changes, period.start(), period.end());
+    println!();
+
+    let mut summary_table = Table::new();
+    summary_table
+        .add_row(row![l->"Availability", r->format!("{:.2}%", summary.availability * 100.)]);
+    summary_table.add_row(row![l->"Downtime", r->format!("{} seconds", summary.downtime_seconds)]);
+    summary_table.add_row(row![l->"Failures", r->summary.reliability.failure_count]);
+    summary_table.add_row(row![
+        l->"MTBF",
+        r->summary
+            .reliability
+            .mtbf_seconds
+            .map_or_else(|| "-".to_string(), |mtbf| format!("{mtbf} seconds")),
+    ]);
+    summary_table.add_row(row![
+        l->"Longest uptime streak",
+        r->format!("{} seconds", summary.reliability.longest_uptime_streak),
+    ]);
+    summary_table.printstd();
+
+    if !summary.downtime_windows.is_empty() {
+        let mut downtime_table = Table::new();
+        downtime_table.set_titles(row![l->"Down from", l->"Down until", r->"Duration"]);
+        for (start, end) in &summary.downtime_windows {
+            downtime_table.add_row(row![
+                l->fmt_local_time(*start),
+                l->fmt_local_time(*end),
+                r->format!("{} seconds", end - start),
+            ]);
+        }
+        downtime_table.printstd();
+    }
+
+    if let Some(path) = heatmap {
+        std::fs::write(
+            &path,
+            report::graph_uptime_heatmap_svg(
+                &changes,
+                period.start(),
+                period.end(),
+                &report::ChartOptions {
+                    title: format!("Uptime of node {node_id}"),
+                    x_desc: "Day".to_string(),
+                    y_desc: "Hour".to_string(),
+                    ..Default::default()
+                },
+            ),
+        )?;
+        println!("Wrote uptime heatmap to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Format the solution provider of a contract, contracts without one show a dash.
+fn fmt_solution_provider(spid: Option<u32>) -> String {
+    spid.map_or_else(|| "-".to_string(), |spid| spid.to_string())
+}
+
+/// Format deployment data, only retaining the first portion.
+fn fmt_deployment_data(data: &str) -> String {
+    if data.chars().count() > DEPLOYMENT_DATA_WIDTH {
+        format!(
+            "{}...",
+            data.chars().take(DEPLOYMENT_DATA_WIDTH).collect::<String>()
+        )
+    } else {
+        data.to_string()
+    }
+}
@@ -1,24 +1,194 @@
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serializer};
 use serde_json::Value;
 
 /// Helper function to deserialize an i64 which is returned as string (BigNum) in graphql.
 pub fn de_i64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
-    Ok(match Value::deserialize(deserializer)? {
-        Value::String(s) => s.parse().map_err(de::Error::custom)?,
-        Value::Number(num) => num
-            .as_i64()
-            .ok_or_else(|| de::Error::custom("Invalid number"))?,
-        _ => return Err(de::Error::custom("wrong type")),
-    })
+    value_to_i64(Value::deserialize(deserializer)?)
 }
 
 /// Helper function to deserialize an u64 which is returned as string (BigNum) in graphql.
 pub fn de_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
-    Ok(match Value::deserialize(deserializer)? {
-        Value::String(s) => s.parse().map_err(de::Error::custom)?,
-        Value::Number(num) => num
-            .as_u64()
-            .ok_or_else(|| de::Error::custom("Invalid number"))?,
+    value_to_u64(Value::deserialize(deserializer)?)
+}
+
+/// Helper function to deserialize an optional i64 which is returned as string (BigNum) in
+/// graphql. A `null` value is deserialized as [`None`].
+pub fn de_optional_i64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i64>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => value_to_i64(value).map(Some),
+    }
+}
+
+/// Helper function to deserialize an optional u64 which is returned as string (BigNum) in
+/// graphql. A `null` value is deserialized as [`None`].
+pub fn de_optional_u64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => value_to_u64(value).map(Some),
+    }
+}
+
+/// Helper function to serialize an i64 as string (BigNum), as it is returned by graphql.
+pub fn ser_i64<S: Serializer>(value: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Helper function to serialize an u64 as string (BigNum), as it is returned by graphql.
+pub fn ser_u64<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Convert a raw json value, either a string or a number, to an i64. Values in float or
+/// scientific notation are accepted as long as they are integral.
+fn value_to_i64<E: de::Error>(value: Value) -> Result<i64, E> {
+    Ok(match value {
+        Value::String(s) => match s.parse() {
+            Ok(v) => v,
+            Err(e) => integral_float(s.parse().map_err(|_| de::Error::custom(e))?)?,
+        },
+        Value::Number(num) => match num.as_i64() {
+            Some(v) => v,
+            None => integral_float(
+                num.as_f64()
+                    .ok_or_else(|| de::Error::custom("Invalid number"))?,
+            )?,
+        },
         _ => return Err(de::Error::custom("wrong type")),
     })
 }
+
+/// Convert a raw json value, either a string or a number, to an u64. Values in float or
+/// scientific notation are accepted as long as they are integral.
+fn value_to_u64<E: de::Error>(value: Value) -> Result<u64, E> {
+    Ok(match value {
+        Value::String(s) => match s.parse() {
+            Ok(v) => v,
+            Err(e) => integral_float(s.parse().map_err(|_| de::Error::custom(e))?)?,
+        },
+        Value::Number(num) => match num.as_u64() {
+            Some(v) => v,
+            None => integral_float(
+                num.as_f64()
+                    .ok_or_else(|| de::Error::custom("Invalid number"))?,
+            )?,
+        },
+        _ => return Err(de::Error::custom("wrong type")),
+    })
+}
+
+/// Convert a float to an integer type, if the float does not have a fractional part and fits in
+/// the target type.
+fn integral_float<T, E>(value: f64) -> Result<T, E>
+where
+    T: TryFrom<i128>,
+    E: de::Error,
+{
+    if !value.is_finite() || value.fract() != 0. {
+        return Err(de::Error::custom(format!(
+            "Number {value} is not an integer"
+        )));
+    }
+    // Casting a float to i128 saturates, so values out of range for T are caught by the
+    // conversion below.
+    T::try_from(value as i128)
+        .map_err(|_| de::Error::custom(format!("Number {value} is out of range")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{de_i64, de_optional_i64, de_optional_u64, de_u64};
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Values {
+        #[serde(deserialize_with = "de_u64")]
+        unsigned: u64,
+        #[serde(deserialize_with = "de_i64")]
+        signed: i64,
+    }
+
+    #[derive(Deserialize)]
+    struct OptionalValues {
+        #[serde(deserialize_with = "de_optional_u64")]
+        unsigned: Option<u64>,
+        #[serde(deserialize_with = "de_optional_i64")]
+        signed: Option<i64>,
+    }
+
+    #[test]
+    fn de_optional_from_string() {
+        let v: OptionalValues =
+            serde_json::from_str(r#"{"unsigned": "12345", "signed": "-12345"}"#)
+                .expect("Can decode string values");
+
+        assert_eq!(v.unsigned, Some(12345));
+        assert_eq!(v.signed, Some(-12345));
+    }
+
+    #[test]
+    fn de_optional_from_number() {
+        let v: OptionalValues = serde_json::from_str(r#"{"unsigned": 12345, "signed": -12345}"#)
+            .expect("Can decode number values");
+
+        assert_eq!(v.unsigned, Some(12345));
+        assert_eq!(v.signed, Some(-12345));
+    }
+
+    #[test]
+    fn de_optional_from_null() {
+        let v: OptionalValues = serde_json::from_str(r#"{"unsigned": null, "signed": null}"#)
+            .expect("Can decode null values");
+
+        assert_eq!(v.unsigned, None);
+        assert_eq!(v.signed, None);
+    }
+
+    #[test]
+    fn de_from_string() {
+        let v: Values = serde_json::from_str(r#"{"unsigned": "12345", "signed": "-12345"}"#)
+            .expect("Can decode string values");
+
+        assert_eq!(v.unsigned, 12345);
+        assert_eq!(v.signed, -12345);
+    }
+
+    #[test]
+    fn de_from_number() {
+        let v: Values = serde_json::from_str(r#"{"unsigned": 12345, "signed": -12345}"#)
+            .expect("Can decode number values");
+
+        assert_eq!(v.unsigned, 12345);
+        assert_eq!(v.signed, -12345);
+    }
+
+    #[test]
+    fn de_from_scientific_notation() {
+        let v: Values = serde_json::from_str(r#"{"unsigned": 1.2e4, "signed": -1.2e4}"#)
+            .expect("Can decode integral values in scientific notation");
+
+        assert_eq!(v.unsigned, 12000);
+        assert_eq!(v.signed, -12000);
+
+        let v: Values = serde_json::from_str(r#"{"unsigned": "1.23e7", "signed": "12000.0"}"#)
+            .expect("Can decode integral strings in scientific notation");
+
+        assert_eq!(v.unsigned, 12_300_000);
+        assert_eq!(v.signed, 12000);
+    }
+
+    #[test]
+    fn de_fractional_is_rejected() {
+        let err = serde_json::from_str::<Values>(r#"{"unsigned": 12.5, "signed": 1}"#)
+            .err()
+            .expect("Fractional values can't be decoded");
+        assert!(err.to_string().contains("is not an integer"));
+
+        assert!(serde_json::from_str::<Values>(r#"{"unsigned": 1, "signed": "-0.5"}"#).is_err());
+        assert!(serde_json::from_str::<Values>(r#"{"unsigned": -1.0e3, "signed": 1}"#).is_err());
+    }
+}
@@ -1,15 +1,19 @@
-use crate::compat::{de_i64, de_u64};
-use serde::Deserialize;
+use crate::compat::{de_i64, de_u64, ser_i64, ser_u64};
+use serde::{Deserialize, Serialize};
 
 /// A report about nru consumption, also used to prove workload liveliness
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct NRUConsumption {
-    #[serde(rename = "contractID", deserialize_with = "de_u64")]
+    #[serde(
+        rename = "contractID",
+        deserialize_with = "de_u64",
+        serialize_with = "ser_u64"
+    )]
     pub contract_id: u64,
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     pub window: u64,
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     pub nru: u64,
-    #[serde(deserialize_with = "de_i64")]
+    #[serde(deserialize_with = "de_i64", serialize_with = "ser_i64")]
     pub timestamp: i64,
 }
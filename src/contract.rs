@@ -1,18 +1,24 @@
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
-use crate::compat::{de_i64, de_u64};
+use crate::compat::{de_i64, de_u64, ser_i64, ser_u64};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NodeContract {
-    #[serde(rename = "contractID", deserialize_with = "de_u64")]
+    #[serde(
+        rename = "contractID",
+        deserialize_with = "de_u64",
+        serialize_with = "ser_u64"
+    )]
     pub contract_id: u64,
     // Timestamp the object was created, in milliseconds.
-    #[serde(deserialize_with = "de_i64")]
+    #[serde(deserialize_with = "de_i64", serialize_with = "ser_i64")]
     pub created_at: i64,
     pub deployment_data: String,
     pub deployment_hash: String,
+    // Version of the grid the deployment was made for.
+    pub grid_version: u32,
     #[serde(rename = "nodeID")]
     pub node_id: u32,
     #[serde(rename = "numberOfPublicIPs")]
@@ -25,13 +31,17 @@ pub struct NodeContract {
     pub twin_id: u32,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NameContract {
-    #[serde(rename = "contractID", deserialize_with = "de_u64")]
+    #[serde(
+        rename = "contractID",
+        deserialize_with = "de_u64",
+        serialize_with = "ser_u64"
+    )]
     pub contract_id: u64,
     // Timestamp the object was created, in milliseconds.
-    #[serde(deserialize_with = "de_i64")]
+    #[serde(deserialize_with = "de_i64", serialize_with = "ser_i64")]
     pub created_at: i64,
     #[serde(rename = "solutionProviderID")]
     pub solution_provider_id: Option<u32>,
@@ -41,13 +51,17 @@ pub struct NameContract {
     pub name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RentContract {
-    #[serde(rename = "contractID", deserialize_with = "de_u64")]
+    #[serde(
+        rename = "contractID",
+        deserialize_with = "de_u64",
+        serialize_with = "ser_u64"
+    )]
     pub contract_id: u64,
     // Timestamp the object was created, in milliseconds.
-    #[serde(deserialize_with = "de_i64")]
+    #[serde(deserialize_with = "de_i64", serialize_with = "ser_i64")]
     pub created_at: i64,
     #[serde(rename = "nodeID")]
     pub node_id: u32,
@@ -58,19 +72,219 @@ pub struct RentContract {
     pub twin_id: u32,
 }
 
-#[derive(Deserialize)]
+/// A transition of a contract to a new state.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContractEvent {
+    /// The state the contract transitioned to.
+    pub state: ContractState,
+    /// Timestamp of the transition, in seconds.
+    pub timestamp: i64,
+}
+
+/// Workload description stored in the deployment data of a contract by deployment tools, e.g.
+/// `{"version":3,"type":"vm","name":"myvm","projectName":"vm/myvm"}`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentData {
+    /// The type of solution deployed, e.g. `vm` or `kubernetes`.
+    #[serde(rename = "type")]
+    pub solution_type: String,
+    /// The name given to the deployment.
+    pub name: String,
+    /// The project the deployment is part of, if set.
+    #[serde(default)]
+    pub project_name: Option<String>,
+}
+
+impl fmt::Display for DeploymentData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.solution_type)
+    }
+}
+
+/// Decode the deployment data of a contract, if it holds a known workload description. Returns
+/// [`None`] if the data is not JSON, or does not have the expected shape.
+pub fn parse_deployment_data(data: &str) -> Option<DeploymentData> {
+    serde_json::from_str(data).ok()
+}
+
+/// Fields common to all contract types, used to sort contracts.
+pub trait ContractInfo {
+    /// The ID of the contract.
+    fn contract_id(&self) -> u64;
+    /// Timestamp the contract was created, in milliseconds.
+    fn created_at(&self) -> i64;
+    /// The current state of the contract.
+    fn state(&self) -> ContractState;
+}
+
+macro_rules! impl_contract_info {
+    ($($contract:ty),*) => {
+        $(
+            impl ContractInfo for $contract {
+                fn contract_id(&self) -> u64 {
+                    self.contract_id
+                }
+
+                fn created_at(&self) -> i64 {
+                    self.created_at
+                }
+
+                fn state(&self) -> ContractState {
+                    self.state
+                }
+            }
+        )*
+    };
+}
+
+impl_contract_info!(NodeContract, NameContract, RentContract);
+
+/// A contract of any type.
+pub enum Contract {
+    Node(NodeContract),
+    Name(NameContract),
+    Rent(RentContract),
+}
+
+impl ContractInfo for Contract {
+    fn contract_id(&self) -> u64 {
+        match self {
+            Contract::Node(c) => c.contract_id,
+            Contract::Name(c) => c.contract_id,
+            Contract::Rent(c) => c.contract_id,
+        }
+    }
+
+    fn created_at(&self) -> i64 {
+        match self {
+            Contract::Node(c) => c.created_at,
+            Contract::Name(c) => c.created_at,
+            Contract::Rent(c) => c.created_at,
+        }
+    }
+
+    fn state(&self) -> ContractState {
+        match self {
+            Contract::Node(c) => c.state,
+            Contract::Name(c) => c.state,
+            Contract::Rent(c) => c.state,
+        }
+    }
+}
+
+/// Sort contracts by the time they were created, oldest first. Contracts created at the same time
+/// keep their relative order.
+pub fn sort_by_created<C: ContractInfo>(contracts: &mut [C]) {
+    contracts.sort_by_key(|c| c.created_at());
+}
+
+/// Sort contracts by their cost in the given map, which is keyed by contract ID, cheapest first.
+/// Contracts without a known cost are sorted last. Contracts with the same cost keep their
+/// relative order.
+pub fn sort_by_cost<C: ContractInfo>(contracts: &mut [C], costs: &HashMap<u64, u64>) {
+    contracts.sort_by_key(|c| match costs.get(&c.contract_id()) {
+        Some(cost) => (false, *cost),
+        None => (true, 0),
+    });
+}
+
+/// Sort contracts by their ID, lowest first.
+pub fn sort_by_id<C: ContractInfo>(contracts: &mut [C]) {
+    contracts.sort_by_key(|c| c.contract_id());
+}
+
+impl NodeContract {
+    /// Check if the contract ID, deployment hash or deployment data contain `text`, ignoring
+    /// case. An empty text matches every contract.
+    pub fn matches_text(&self, text: &str) -> bool {
+        contains_text(&self.contract_id.to_string(), text)
+            || contains_text(&self.deployment_hash, text)
+            || contains_text(&self.deployment_data, text)
+    }
+}
+
+impl NameContract {
+    /// Check if the contract ID or the name contain `text`, ignoring case. An empty text matches
+    /// every contract.
+    pub fn matches_text(&self, text: &str) -> bool {
+        contains_text(&self.contract_id.to_string(), text) || contains_text(&self.name, text)
+    }
+}
+
+impl RentContract {
+    /// Check if the contract ID contains `text`. An empty text matches every contract.
+    pub fn matches_text(&self, text: &str) -> bool {
+        contains_text(&self.contract_id.to_string(), text)
+    }
+}
+
+/// Check if `haystack` contains `text`, ignoring case.
+fn contains_text(haystack: &str, text: &str) -> bool {
+    haystack.to_lowercase().contains(&text.to_lowercase())
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Resources {
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     pub cru: u64,
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     pub hru: u64,
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     pub mru: u64,
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     pub sru: u64,
 }
 
-#[derive(Serialize, Deserialize)]
+impl Resources {
+    /// Check if no resources are used at all. Some contracts report all zeros instead of no
+    /// resources, which means they are idle rather than unknown.
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The resources used by a set of node contracts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceTotals {
+    /// The sum of the known resources of the contracts.
+    pub used: Resources,
+    /// The amount of contracts which don't use any resources.
+    pub idle: usize,
+    /// The amount of contracts of which the resources are unknown. These are not included in
+    /// `used`.
+    pub unknown: usize,
+}
+
+/// Sum the resources used by the given node contracts. Contracts without known resources are
+/// counted as using no resources, but are reported in [`ResourceTotals::unknown`].
+pub fn total_resources<'a>(
+    contracts: impl IntoIterator<Item = &'a NodeContract>,
+) -> ResourceTotals {
+    let mut totals = ResourceTotals::default();
+    for contract in contracts {
+        match &contract.resources_used {
+            None => totals.unknown += 1,
+            Some(resources) => {
+                if resources.is_zero() {
+                    totals.idle += 1;
+                }
+                totals.used.cru += resources.cru;
+                totals.used.hru += resources.hru;
+                totals.used.mru += resources.mru;
+                totals.used.sru += resources.sru;
+            }
+        }
+    }
+    totals
+}
+
+/// State of a contract. States are ordered by the health of the contract, from [`Created`] to
+/// [`Deleted`], so contracts can be grouped by health.
+///
+/// [`Created`]: ContractState::Created
+/// [`Deleted`]: ContractState::Deleted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ContractState {
     Created,
     GracePeriod,
@@ -78,6 +292,14 @@ pub enum ContractState {
     Deleted,
 }
 
+impl ContractState {
+    /// Check if a contract in this state is still active, i.e. it has not been removed from the
+    /// grid, either by the owner or because it ran out of funds.
+    pub fn is_active(self) -> bool {
+        matches!(self, ContractState::Created | ContractState::GracePeriod)
+    }
+}
+
 impl fmt::Display for ContractState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -88,3 +310,175 @@ impl fmt::Display for ContractState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_deployment_data, sort_by_cost, sort_by_created, sort_by_id, total_resources,
+        ContractState, DeploymentData, NameContract, NodeContract, RentContract, ResourceTotals,
+        Resources,
+    };
+    use std::collections::HashMap;
+
+    fn rent_contract(contract_id: u64, created_at: i64, state: ContractState) -> RentContract {
+        RentContract {
+            contract_id,
+            created_at,
+            node_id: 1,
+            solution_provider_id: None,
+            state,
+            twin_id: 1,
+        }
+    }
+
+    fn ids(contracts: &[RentContract]) -> Vec<u64> {
+        contracts.iter().map(|c| c.contract_id).collect()
+    }
+
+    #[test]
+    fn parse_known_deployment_data() {
+        let data = parse_deployment_data(
+            r#"{"version":3,"type":"vm","name":"myvm","projectName":"vm/myvm"}"#,
+        )
+        .expect("Can parse deployment data");
+
+        assert_eq!(
+            data,
+            DeploymentData {
+                solution_type: "vm".to_string(),
+                name: "myvm".to_string(),
+                project_name: Some("vm/myvm".to_string()),
+            }
+        );
+        assert_eq!(data.to_string(), "myvm (vm)");
+    }
+
+    #[test]
+    fn reject_unknown_deployment_data() {
+        assert_eq!(parse_deployment_data(""), None);
+        assert_eq!(parse_deployment_data("some raw data"), None);
+        assert_eq!(parse_deployment_data(r#"{"type":"vm"}"#), None);
+    }
+
+    #[test]
+    fn sort_contracts_stably() {
+        let mut contracts = vec![
+            rent_contract(3, 2_000, ContractState::Created),
+            rent_contract(1, 3_000, ContractState::Deleted),
+            rent_contract(4, 1_000, ContractState::GracePeriod),
+            rent_contract(2, 2_000, ContractState::Created),
+        ];
+
+        sort_by_created(&mut contracts);
+        assert_eq!(ids(&contracts), [4, 3, 2, 1]);
+
+        sort_by_id(&mut contracts);
+        assert_eq!(ids(&contracts), [1, 2, 3, 4]);
+
+        let costs = HashMap::from([(1, 50), (3, 10), (4, 10)]);
+        sort_by_cost(&mut contracts, &costs);
+        assert_eq!(ids(&contracts), [3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn total_resources_of_known_idle_and_unknown_contracts() {
+        let node_contract = |contract_id, resources_used| NodeContract {
+            contract_id,
+            created_at: 0,
+            deployment_data: String::new(),
+            deployment_hash: String::new(),
+            grid_version: 3,
+            node_id: 1,
+            number_of_public_ips: 0,
+            resources_used,
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 1,
+        };
+        let resources = |cru, mru| Resources {
+            cru,
+            hru: 0,
+            mru,
+            sru: 100,
+        };
+        let contracts = [
+            node_contract(1, Some(resources(2, 1_024))),
+            node_contract(2, None),
+            node_contract(3, Some(Resources::default())),
+            node_contract(4, Some(resources(4, 2_048))),
+        ];
+
+        assert!(Resources::default().is_zero());
+        assert!(!resources(0, 1).is_zero());
+        assert_eq!(
+            total_resources(&contracts),
+            ResourceTotals {
+                used: Resources {
+                    cru: 6,
+                    hru: 0,
+                    mru: 3_072,
+                    sru: 200,
+                },
+                idle: 1,
+                unknown: 1,
+            }
+        );
+        assert_eq!(total_resources(&[]), ResourceTotals::default());
+    }
+
+    #[test]
+    fn match_contracts_on_text() {
+        let node_contract = NodeContract {
+            contract_id: 1234,
+            created_at: 0,
+            deployment_data: r#"{"version":3,"type":"vm","name":"MyVM"}"#.to_string(),
+            deployment_hash: "a1b2c3".to_string(),
+            grid_version: 3,
+            node_id: 1,
+            number_of_public_ips: 0,
+            resources_used: None,
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 1,
+        };
+        let name_contract = NameContract {
+            contract_id: 56,
+            created_at: 0,
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 1,
+            name: "gateway".to_string(),
+        };
+
+        for text in ["", "23", "A1B2", "myvm"] {
+            assert!(node_contract.matches_text(text), "matches {text}");
+        }
+        assert!(!node_contract.matches_text("gateway"));
+        assert!(name_contract.matches_text("Gate"));
+        assert!(name_contract.matches_text("56"));
+        assert!(!name_contract.matches_text("1234"));
+        assert!(rent_contract(78, 0, ContractState::Created).matches_text("7"));
+        assert!(!rent_contract(78, 0, ContractState::Created).matches_text("vm"));
+    }
+
+    #[test]
+    fn order_contract_states_by_health() {
+        let mut states = [
+            ContractState::Deleted,
+            ContractState::GracePeriod,
+            ContractState::OutOfFunds,
+            ContractState::Created,
+        ];
+        states.sort();
+
+        assert_eq!(
+            states,
+            [
+                ContractState::Created,
+                ContractState::GracePeriod,
+                ContractState::OutOfFunds,
+                ContractState::Deleted,
+            ]
+        );
+    }
+}
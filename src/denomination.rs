@@ -0,0 +1,151 @@
+//! Denomination of token amounts, to convert and format amounts in the smallest on chain unit.
+
+/// A token as used on chain, with the amount of the smallest on chain unit which equate 1 token,
+/// and the symbol of the token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Denomination {
+    /// Amount of the smallest on chain unit which equate 1 token. This is expected to be a power
+    /// of 10.
+    pub units_per_token: u64,
+    /// Symbol shown after formatted amounts.
+    pub symbol: &'static str,
+}
+
+impl Denomination {
+    /// The denomination of TFT, which has 7 decimals.
+    pub const TFT: Self = Self {
+        units_per_token: 10_000_000,
+        symbol: "TFT",
+    };
+
+    /// The amount of decimals of a token.
+    pub fn decimals(self) -> usize {
+        self.units_per_token.max(1).ilog10() as usize
+    }
+
+    /// Convert an amount in the smallest on chain unit to tokens.
+    pub fn to_tokens(self, amount: u64) -> f64 {
+        amount as f64 / self.units_per_token as f64
+    }
+
+    /// Convert an amount of tokens to the smallest on chain unit, rounded to the nearest unit.
+    pub fn from_tokens(self, tokens: f64) -> u64 {
+        (tokens * self.units_per_token as f64).round() as u64
+    }
+
+    /// Format an amount in the smallest on chain unit as tokens, with all decimals so amounts line
+    /// up in a table, e.g. `1.5000000 TFT`.
+    pub fn format(self, amount: u64) -> String {
+        self.format_with(amount, FormatOptions::default())
+    }
+
+    /// Format an amount in the smallest on chain unit as tokens with the given options, e.g.
+    /// `1.5 TFT` with [`FormatOptions::COMPACT`]. Amounts are rounded to the nearest value with
+    /// the requested precision.
+    pub fn format_with(self, amount: u64, options: FormatOptions) -> String {
+        let decimals = self.decimals();
+        let precision = options.precision.unwrap_or(decimals).min(decimals);
+        // Work in units of the requested precision, which can't overflow in an u128.
+        let scale = 10u128.pow((decimals - precision) as u32);
+        let rounded = (amount as u128 + scale / 2) / scale;
+        let per_token = 10u128.pow(precision as u32);
+        let whole = rounded / per_token;
+        let mut fraction = format!("{:0precision$}", rounded % per_token);
+        if precision == 0 {
+            fraction.clear();
+        }
+        if options.trim_trailing_zeros {
+            fraction.truncate(fraction.trim_end_matches('0').len());
+        }
+        if fraction.is_empty() {
+            format!("{whole} {}", self.symbol)
+        } else {
+            format!("{whole}.{fraction} {}", self.symbol)
+        }
+    }
+}
+
+/// Options to format an amount of tokens with [`Denomination::format_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatOptions {
+    /// Amount of decimals to show, or all decimals of the token if [`None`]. This is capped at
+    /// the amount of decimals of the token.
+    pub precision: Option<usize>,
+    /// Whether to remove zeros at the end of the decimals, and the decimal point if no decimals
+    /// are left.
+    pub trim_trailing_zeros: bool,
+}
+
+impl FormatOptions {
+    /// Short amounts for display, e.g. `12.34 TFT`, with at most 2 decimals.
+    pub const COMPACT: Self = Self {
+        precision: Some(2),
+        trim_trailing_zeros: true,
+    };
+}
+
+impl Default for Denomination {
+    fn default() -> Self {
+        Self::TFT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Denomination, FormatOptions};
+
+    #[test]
+    fn format_and_convert_amounts() {
+        let tft = Denomination::default();
+        assert_eq!(tft.decimals(), 7);
+        assert_eq!(tft.format(15_000_005), "1.5000005 TFT");
+        assert_eq!(tft.format(0), "0.0000000 TFT");
+        assert_eq!(tft.to_tokens(15_000_000), 1.5);
+        assert_eq!(tft.from_tokens(1.5), 15_000_000);
+
+        let other = Denomination {
+            units_per_token: 100,
+            symbol: "XYZ",
+        };
+        assert_eq!(other.format(1_205), "12.05 XYZ");
+        let whole = Denomination {
+            units_per_token: 1,
+            symbol: "XYZ",
+        };
+        assert_eq!(whole.format(12), "12 XYZ");
+    }
+
+    #[test]
+    fn format_with_precision_and_trimming() {
+        let tft = Denomination::TFT;
+        let format = |precision, trim_trailing_zeros| {
+            let options = FormatOptions {
+                precision,
+                trim_trailing_zeros,
+            };
+            [0, 120_000_000, 12_345_678_901].map(|amount| tft.format_with(amount, options))
+        };
+
+        assert_eq!(
+            format(None, false),
+            ["0.0000000 TFT", "12.0000000 TFT", "1234.5678901 TFT"]
+        );
+        assert_eq!(format(None, true), ["0 TFT", "12 TFT", "1234.5678901 TFT"]);
+        assert_eq!(
+            format(Some(2), false),
+            ["0.00 TFT", "12.00 TFT", "1234.57 TFT"]
+        );
+        assert_eq!(format(Some(2), true), ["0 TFT", "12 TFT", "1234.57 TFT"]);
+        assert_eq!(format(Some(0), false), ["0 TFT", "12 TFT", "1235 TFT"]);
+        // Precision is capped at the decimals of the token.
+        assert_eq!(format(Some(10), false), format(None, false));
+        assert_eq!(
+            tft.format_with(u64::MAX, FormatOptions::COMPACT),
+            "1844674407370.96 TFT"
+        );
+        assert_eq!(
+            tft.format_with(1_234_000, FormatOptions::COMPACT),
+            "0.12 TFT"
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Export of fetched and computed data to common file formats, for record-keeping.
+
+use crate::{
+    contract::{NameContract, NodeContract, RentContract},
+    uptime::{NodeState, NodeStateChange, UptimeEvent},
+};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Header of the CSV export of [`UptimeEvent`]s.
+const UPTIME_EVENTS_CSV_HEADER: &str = "timestamp,uptime";
+
+/// Header of the CSV export of [`NodeStateChange`]s.
+const STATE_CHANGES_CSV_HEADER: &str = "detected_at,state,state_at";
+
+/// Header of the CSV export of [`ContractRow`]s.
+const CONTRACTS_CSV_HEADER: &str =
+    "type,contract_id,twin_id,node_id,solution_provider_id,state,created_at,public_ips,deployment_hash,cost,nru";
+
+/// A contract of any type, borrowed from fetched contracts.
+#[derive(Clone, Copy)]
+pub enum ContractRef<'a> {
+    Node(&'a NodeContract),
+    Name(&'a NameContract),
+    Rent(&'a RentContract),
+}
+
+/// A contract as exported by [`contracts_csv`], with the values which are loaded separately for
+/// every contract, if they are loaded.
+#[derive(Clone, Copy)]
+pub struct ContractRow<'a> {
+    pub contract: ContractRef<'a>,
+    /// Total amount billed for the contract, in the smallest on chain unit.
+    pub cost: Option<u64>,
+    /// Total network used by the contract, in bytes.
+    pub nru: Option<u64>,
+}
+
+/// Format [`UptimeEvent`]s as CSV, with a `timestamp,uptime` header. If there are no events, only
+/// the header is returned.
+pub fn uptime_events_csv(events: &[UptimeEvent]) -> String {
+    let mut csv = format!("{UPTIME_EVENTS_CSV_HEADER}\n");
+    for event in events {
+        // Writing to a String can't fail.
+        let _ = writeln!(csv, "{},{}", event.timestamp(), event.uptime());
+    }
+    csv
+}
+
+/// Format [`NodeStateChange`]s as CSV, with a `detected_at,state,state_at` header. The state
+/// column holds the name of the [`NodeState`] variant, the `state_at` column holds the value
+/// enclosed in the state. For [`NodeState::Drift`] this is the drift in seconds rather than a
+/// timestamp. If there are no changes, only the header is returned.
+pub fn state_changes_csv(changes: &[NodeStateChange]) -> String {
+    let mut csv = format!("{STATE_CHANGES_CSV_HEADER}\n");
+    for change in changes {
+        let (state, state_at) = match change.state() {
+            NodeState::Offline(ts) => ("Offline", ts),
+            NodeState::PoweredDown(ts) => ("PoweredDown", ts),
+            NodeState::Booted(ts) => ("Booted", ts),
+            NodeState::ImpossibleReboot(ts) => ("ImpossibleReboot", ts),
+            NodeState::Drift(drift) => ("Drift", drift),
+            NodeState::Unknown(ts) => ("Unknown", ts),
+        };
+        // Writing to a String can't fail.
+        let _ = writeln!(csv, "{},{state},{state_at}", change.timestamp());
+    }
+    csv
+}
+
+/// Format [`ContractRow`]s as CSV, in the given order, with a header naming the columns. Values
+/// which don't apply to a type of contract, e.g. the node of a name contract, and values which are
+/// not loaded, are left empty. Creation times are timestamps in seconds. If there are no rows,
+/// only the header is returned.
+pub fn contracts_csv(rows: &[ContractRow]) -> String {
+    let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+    let mut csv = format!("{CONTRACTS_CSV_HEADER}\n");
+    for row in rows {
+        let (kind, contract_id, twin_id, node_id, spid, state, created_at, public_ips, hash) =
+            match row.contract {
+                ContractRef::Node(c) => (
+                    "node",
+                    c.contract_id,
+                    c.twin_id,
+                    Some(c.node_id),
+                    c.solution_provider_id,
+                    c.state,
+                    c.created_at,
+                    Some(c.number_of_public_ips),
+                    c.deployment_hash.as_str(),
+                ),
+                ContractRef::Name(c) => (
+                    "name",
+                    c.contract_id,
+                    c.twin_id,
+                    None,
+                    c.solution_provider_id,
+                    c.state,
+                    c.created_at,
+                    None,
+                    "",
+                ),
+                ContractRef::Rent(c) => (
+                    "rent",
+                    c.contract_id,
+                    c.twin_id,
+                    Some(c.node_id),
+                    c.solution_provider_id,
+                    c.state,
+                    c.created_at,
+                    None,
+                    "",
+                ),
+            };
+        // Writing to a String can't fail.
+        let _ = writeln!(
+            csv,
+            "{kind},{contract_id},{twin_id},{},{},{state:?},{},{},{hash},{},{}",
+            optional(node_id.map(u64::from)),
+            optional(spid.map(u64::from)),
+            created_at / 1000,
+            optional(public_ips.map(u64::from)),
+            optional(row.cost),
+            optional(row.nru),
+        );
+    }
+    csv
+}
+
+/// Format any serializable value, e.g. a list of [`UptimeEvent`]s or [`NodeStateChange`]s, as
+/// pretty printed JSON.
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        contracts_csv, state_changes_csv, to_json, uptime_events_csv, ContractRef, ContractRow,
+    };
+    use crate::{
+        contract::{ContractState, NameContract, NodeContract, RentContract},
+        uptime::{calculate_node_state_changes, UptimeEvent},
+    };
+
+    #[test]
+    fn export_uptime_events_and_state_changes() {
+        let ues = [UptimeEvent::new(1_000, 100), UptimeEvent::new(2_000, 1_100)];
+        let changes = calculate_node_state_changes(&ues, 0, 1_500);
+
+        assert_eq!(
+            uptime_events_csv(&ues),
+            "timestamp,uptime\n1000,100\n2000,1100\n"
+        );
+        assert_eq!(
+            state_changes_csv(&changes),
+            "detected_at,state,state_at\n1000,Offline,0\n1000,Booted,900\n"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&to_json(&changes).unwrap()).unwrap(),
+            serde_json::json!([
+                { "timestamp": 1000, "state": { "Offline": 0 } },
+                { "timestamp": 1000, "state": { "Booted": 900 } },
+            ])
+        );
+    }
+
+    #[test]
+    fn export_contracts_with_loaded_values() {
+        let node_contract = NodeContract {
+            contract_id: 1,
+            created_at: 1_000_000,
+            deployment_data: String::new(),
+            deployment_hash: "a1b2c3".to_string(),
+            grid_version: 3,
+            node_id: 10,
+            number_of_public_ips: 1,
+            resources_used: None,
+            solution_provider_id: Some(2),
+            state: ContractState::GracePeriod,
+            twin_id: 7,
+        };
+        let name_contract = NameContract {
+            contract_id: 2,
+            created_at: 2_000_000,
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 7,
+            name: "example".to_string(),
+        };
+        let rent_contract = RentContract {
+            contract_id: 3,
+            created_at: 3_000_000,
+            node_id: 10,
+            solution_provider_id: None,
+            state: ContractState::Deleted,
+            twin_id: 8,
+        };
+        let rows = [
+            ContractRow {
+                contract: ContractRef::Rent(&rent_contract),
+                cost: None,
+                nru: None,
+            },
+            ContractRow {
+                contract: ContractRef::Node(&node_contract),
+                cost: Some(500),
+                nru: Some(1_024),
+            },
+            ContractRow {
+                contract: ContractRef::Name(&name_contract),
+                cost: Some(0),
+                nru: None,
+            },
+        ];
+
+        assert_eq!(
+            contracts_csv(&rows)
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "type,contract_id,twin_id,node_id,solution_provider_id,state,created_at,public_ips,deployment_hash,cost,nru",
+                "rent,3,8,10,,Deleted,3000,,,,",
+                "node,1,7,10,2,GracePeriod,1000,1,a1b2c3,500,1024",
+                "name,2,7,,,Created,2000,,,0,",
+            ]
+        );
+    }
+
+    #[test]
+    fn export_empty_results_as_headers() {
+        assert_eq!(uptime_events_csv(&[]), "timestamp,uptime\n");
+        assert_eq!(state_changes_csv(&[]), "detected_at,state,state_at\n");
+        assert_eq!(contracts_csv(&[]).lines().count(), 1);
+        assert_eq!(to_json::<[UptimeEvent]>(&[]).unwrap(), "[]");
+    }
+}
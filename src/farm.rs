@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// A farm on the grid, grouping the nodes of a farmer.
+#[derive(Serialize, Deserialize)]
+pub struct Farm {
+    #[serde(rename = "farmID")]
+    pub farm_id: u32,
+    pub name: String,
+    /// The twin of the farmer.
+    #[serde(rename = "twinID")]
+    pub twin_id: u32,
+}
@@ -1,31 +1,77 @@
 use crate::{
-    bill_report::ContractBillReport,
+    analytics::contract_events,
+    bill_report::{ContractBillReport, DiscountLevel},
+    cache::{CacheKey, ResponseCache, ResponseStore},
     consumption::NRUConsumption,
-    contract::{ContractState, NameContract, NodeContract, RentContract},
+    contract::{
+        Contract, ContractEvent, ContractState, NameContract, NodeContract, RentContract, Resources,
+    },
+    farm::Farm,
+    node::Node,
+    solution_provider::SolutionProvider,
+    twin::Twin,
     uptime::UptimeEvent,
 };
-use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures::Future;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    StatusCode,
+};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize, Serialize,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    not(any(feature = "rustls-tls", feature = "native-tls"))
+))]
+compile_error!("Either the `rustls-tls` or the `native-tls` feature must be enabled");
 
-/// Amount of items to fetch when iterating on graphql.
+/// All contract states, this includes expired contract states.
+pub const ALL_CONTRACT_STATES: [ContractState; 4] = [
+    ContractState::Created,
+    ContractState::GracePeriod,
+    ContractState::OutOfFunds,
+    ContractState::Deleted,
+];
+
+/// Default amount of items to fetch when iterating on graphql.
 const PAGE_SIZE: usize = 1000;
 
+/// Maximum amount of characters of a response body which are included in a decode error.
+const DECODE_ERROR_SNIPPET_LEN: usize = 200;
+
 const USER_AGENT: &str = "tfgrid_graphql_client";
+/// Default time to wait for a connection to the server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 const MAINNET_URL: &str = "https://graphql.grid.tf/graphql";
 const TESTNET_URL: &str = "https://graphql.test.grid.tf/graphql";
 const QANET_URL: &str = "https://graphql.qa.grid.tf/graphql";
 const DEVNET_URL: &str = "https://graphql.dev.grid.tf/graphql";
 const UPTIME_EVENT_QUERY: &str = r#"
-query get_uptime_events($node_id: Int, $start: BigInt, $end: BigInt) {
-    uptimeEvents(where: {nodeID_eq: $node_id, timestamp_gte: $start, timestamp_lte: $end}, orderBy: timestamp_ASC) {
+query get_uptime_events($node_ids: [Int!], $start: BigInt, $end: BigInt, $offset: Int, $limit: Int) {
+    uptimeEvents(where: {nodeID_in: $node_ids, timestamp_gte: $start, timestamp_lte: $end}, orderBy: [timestamp_ASC, nodeID_ASC, id_ASC], limit: $limit, offset: $offset) {
+        nodeID
         timestamp
         uptime
     }
 }
 "#;
 const CONTRACT_BILL_REPORT_QUERY: &str = r#"
-query get_contract_bill_reports($start: BigInt, $end: BigInt, $contract_ids: [BigInt!], $offset: Int) {
-  contractBillReports(where: {timestamp_gte: $start, timestamp_lte: $end, contractID_in: $contract_ids}, orderBy: timestamp_ASC, limit: 1000, offset: $offset) {
+query get_contract_bill_reports($start: BigInt, $end: BigInt, $contract_ids: [BigInt!], $discount: DiscountLevel, $offset: Int, $limit: Int) {
+  contractBillReports(where: {timestamp_gte: $start, timestamp_lte: $end, contractID_in: $contract_ids, discountReceived_eq: $discount}, orderBy: [timestamp_ASC, contractID_ASC, id_ASC], limit: $limit, offset: $offset) {
     amountBilled
     contractID
     timestamp
@@ -34,9 +80,9 @@ query get_contract_bill_reports($start: BigInt, $end: BigInt, $contract_ids: [Bi
 }
 
 "#;
-const CONTRACTS_QUERY: &str = r#"
-query contracts($nodes: [Int!], $states: [ContractState!], $twins: [Int!], $contract_ids: [BigInt!], $offset: Int, $spids: [Int!]) {
-  nodeContracts(where: {nodeID_in: $nodes, state_in: $states, twinID_in: $twins, contractID_in: $contract_ids, solutionProviderID_in: $spids}, orderBy: contractID_ASC, limit: 1000, offset: $offset) {
+const NODE_CONTRACTS_QUERY: &str = r#"
+query node_contracts($nodes: [Int!], $states: [ContractState!], $twins: [Int!], $contract_ids: [BigInt!], $offset: Int, $limit: Int, $spids: [Int!], $hashes: [String!], $created_after: BigInt, $created_before: BigInt) {
+  nodeContracts(where: {nodeID_in: $nodes, state_in: $states, twinID_in: $twins, contractID_in: $contract_ids, solutionProviderID_in: $spids, deploymentHash_in: $hashes, createdAt_gte: $created_after, createdAt_lte: $created_before}, orderBy: contractID_ASC, limit: $limit, offset: $offset) {
     contractID
     createdAt
     deploymentData
@@ -54,7 +100,18 @@ query contracts($nodes: [Int!], $states: [ContractState!], $twins: [Int!], $cont
     state
     twinID
   }
-  nameContracts(where: {state_in: $states, twinID_in: $twins, contractID_in: $contract_ids, solutionProviderID_in: $spids}, orderBy: contractID_ASC, limit: 1000, offset: $offset) {
+}
+"#;
+const NODE_CONTRACT_NODES_QUERY: &str = r#"
+query node_contract_nodes($nodes: [Int!], $states: [ContractState!], $offset: Int, $limit: Int) {
+  nodeContracts(where: {nodeID_in: $nodes, state_in: $states}, orderBy: contractID_ASC, limit: $limit, offset: $offset) {
+    nodeID
+  }
+}
+"#;
+const NAME_CONTRACTS_QUERY: &str = r#"
+query name_contracts($states: [ContractState!], $twins: [Int!], $contract_ids: [BigInt!], $offset: Int, $limit: Int, $spids: [Int!], $created_after: BigInt, $created_before: BigInt) {
+  nameContracts(where: {state_in: $states, twinID_in: $twins, contractID_in: $contract_ids, solutionProviderID_in: $spids, createdAt_gte: $created_after, createdAt_lte: $created_before}, orderBy: contractID_ASC, limit: $limit, offset: $offset) {
     twinID
     state
     solutionProviderID
@@ -62,7 +119,11 @@ query contracts($nodes: [Int!], $states: [ContractState!], $twins: [Int!], $cont
     createdAt
     contractID
   }
-  rentContracts(where: {state_in: $states, twinID_in: $twins, contractID_in: $contract_ids, nodeID_in: $nodes, solutionProviderID_in: $spids}, orderBy: contractID_ASC, limit: 1000, offset: $offset) {
+}
+"#;
+const RENT_CONTRACTS_QUERY: &str = r#"
+query rent_contracts($nodes: [Int!], $states: [ContractState!], $twins: [Int!], $contract_ids: [BigInt!], $offset: Int, $limit: Int, $spids: [Int!], $created_after: BigInt, $created_before: BigInt) {
+  rentContracts(where: {state_in: $states, twinID_in: $twins, contractID_in: $contract_ids, nodeID_in: $nodes, solutionProviderID_in: $spids, createdAt_gte: $created_after, createdAt_lte: $created_before}, orderBy: contractID_ASC, limit: $limit, offset: $offset) {
     contractID
     createdAt
     nodeID
@@ -73,8 +134,8 @@ query contracts($nodes: [Int!], $states: [ContractState!], $twins: [Int!], $cont
 }
 "#;
 const NRU_CONSUMPTION_QUERY: &str = r#"
-query nru_consumptions($offset: Int, $contract_ids:[BigInt!]) {
-  nruConsumptions(where: {contractID_in: $contract_ids}, orderBy: timestamp_ASC, limit: 1000, offset: $offset) {
+query nru_consumptions($offset: Int, $limit: Int, $contract_ids:[BigInt!], $start: BigInt, $end: BigInt) {
+  nruConsumptions(where: {contractID_in: $contract_ids, timestamp_gte: $start, timestamp_lte: $end}, orderBy: [timestamp_ASC, contractID_ASC, id_ASC], limit: $limit, offset: $offset) {
     window
     timestamp
     nru
@@ -82,8 +143,74 @@ query nru_consumptions($offset: Int, $contract_ids:[BigInt!]) {
   }
 }
 "#;
+const FARMS_QUERY: &str = r#"
+query farms($farm_ids: [Int!], $offset: Int, $limit: Int) {
+  farms(where: {farmID_in: $farm_ids}, orderBy: farmID_ASC, limit: $limit, offset: $offset) {
+    farmID
+    name
+    twinID
+  }
+}
+"#;
+const NODES_QUERY: &str = r#"
+query nodes($farm_ids: [Int!], $offset: Int, $limit: Int) {
+  nodes(where: {farmID_in: $farm_ids}, orderBy: nodeID_ASC, limit: $limit, offset: $offset) {
+    nodeID
+    farmID
+    twinID
+    country
+    city
+  }
+}
+"#;
+const SOLUTION_PROVIDERS_QUERY: &str = r#"
+query solution_providers($spids: [Int!], $offset: Int, $limit: Int) {
+  solutionProviders(where: {solutionProviderID_in: $spids}, orderBy: solutionProviderID_ASC, limit: $limit, offset: $offset) {
+    solutionProviderID
+    description
+    link
+  }
+}
+"#;
+const NODES_BY_ID_QUERY: &str = r#"
+query nodes_by_id($node_ids: [Int!], $offset: Int, $limit: Int) {
+  nodes(where: {nodeID_in: $node_ids}, orderBy: nodeID_ASC, limit: $limit, offset: $offset) {
+    nodeID
+    farmID
+    twinID
+    country
+    city
+  }
+}
+"#;
+const TWINS_QUERY: &str = r#"
+query twins($twin_ids: [Int!], $offset: Int, $limit: Int) {
+  twins(where: {twinID_in: $twin_ids}, orderBy: twinID_ASC, limit: $limit, offset: $offset) {
+    twinID
+    accountID
+    relay
+  }
+}
+"#;
+const CONTRACT_CONTEXT_QUERY: &str = r#"
+query contract_context($node_ids: [Int!], $twin_ids: [Int!], $offset: Int, $limit: Int) {
+  nodes(where: {nodeID_in: $node_ids}, orderBy: nodeID_ASC, limit: $limit, offset: $offset) {
+    nodeID
+    farmID
+    twinID
+    country
+    city
+  }
+  twins(where: {twinID_in: $twin_ids}, orderBy: twinID_ASC, limit: $limit, offset: $offset) {
+    twinID
+    accountID
+    relay
+  }
+}
+"#;
 
 /// A collection of different types of contracts.
+#[derive(Default)]
 pub struct Contracts {
     /// List of node contracts.
     pub node_contracts: Vec<NodeContract>,
@@ -93,11 +220,552 @@ pub struct Contracts {
     pub rent_contracts: Vec<RentContract>,
 }
 
+impl Contracts {
+    /// Create an empty collection of contracts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add all contracts of `other` to this collection, e.g. to combine the results of multiple
+    /// queries. The contracts of every type are added after the existing contracts of that type.
+    pub fn extend(&mut self, other: Contracts) {
+        self.node_contracts.extend(other.node_contracts);
+        self.name_contracts.extend(other.name_contracts);
+        self.rent_contracts.extend(other.rent_contracts);
+    }
+
+    /// The total amount of contracts of all types.
+    ///
+    /// ```
+    /// use tfgrid_graphql::{
+    ///     contract::{ContractState, NameContract},
+    ///     graphql::Contracts,
+    /// };
+    ///
+    /// let mut contracts = Contracts::new();
+    /// assert!(contracts.is_empty());
+    ///
+    /// contracts.name_contracts.push(NameContract {
+    ///     contract_id: 42,
+    ///     created_at: 0,
+    ///     solution_provider_id: None,
+    ///     state: ContractState::Created,
+    ///     twin_id: 1,
+    ///     name: "example".to_string(),
+    /// });
+    /// assert_eq!(contracts.len(), 1);
+    /// assert_eq!(contracts.all_contract_ids(), vec![42]);
+    /// assert_eq!(contracts.total_public_ips(), 0);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.node_contracts.len() + self.name_contracts.len() + self.rent_contracts.len()
+    }
+
+    /// Check if there are no contracts of any type.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total amount of public IPs reserved by the node contracts.
+    pub fn total_public_ips(&self) -> u64 {
+        self.node_contracts
+            .iter()
+            .map(|c| c.number_of_public_ips as u64)
+            .sum()
+    }
+
+    /// The IDs of all contracts, node contracts first, then name contracts, then rent contracts.
+    pub fn all_contract_ids(&self) -> Vec<u64> {
+        self.node_contracts
+            .iter()
+            .map(|c| c.contract_id)
+            .chain(self.name_contracts.iter().map(|c| c.contract_id))
+            .chain(self.rent_contracts.iter().map(|c| c.contract_id))
+            .collect()
+    }
+
+    /// Remove all contracts which are no longer active, as defined by
+    /// [`ContractState::is_active`], from every list of contracts.
+    pub fn retain_active(&mut self) {
+        self.node_contracts.retain(|c| c.state.is_active());
+        self.name_contracts.retain(|c| c.state.is_active());
+        self.rent_contracts.retain(|c| c.state.is_active());
+    }
+
+    /// Get the node contracts whose used resources match the predicate, e.g. all contracts using
+    /// more than 32 GiB of memory. Node contracts without known resources are never included.
+    pub fn filter_node_resources(&self, pred: impl Fn(&Resources) -> bool) -> Vec<&NodeContract> {
+        self.node_contracts
+            .iter()
+            .filter(|c| c.resources_used.as_ref().is_some_and(&pred))
+            .collect()
+    }
+}
+
+impl IntoIterator for Contracts {
+    type Item = Contract;
+    type IntoIter = std::vec::IntoIter<Contract>;
+
+    /// Iterate over all contracts, node contracts first, then name contracts, then rent contracts.
+    fn into_iter(self) -> Self::IntoIter {
+        self.node_contracts
+            .into_iter()
+            .map(Contract::Node)
+            .chain(self.name_contracts.into_iter().map(Contract::Name))
+            .chain(self.rent_contracts.into_iter().map(Contract::Rent))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// Combine multiple collections of contracts, e.g. the results of different queries, in a single
+/// collection.
+impl std::iter::Sum for Contracts {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Contracts::new(), |mut acc, contracts| {
+            acc.extend(contracts);
+            acc
+        })
+    }
+}
+
+/// The entities referenced by a collection of contracts, by ID.
+pub struct ContractContext {
+    /// The nodes the contracts are deployed on.
+    pub nodes: BTreeMap<u32, Node>,
+    /// The farms of the nodes the contracts are deployed on.
+    pub farms: BTreeMap<u32, Farm>,
+    /// The twins owning the contracts.
+    pub twins: BTreeMap<u32, Twin>,
+}
+
 /// A client to connect to a Threefold Grid GraphQL instance.
 #[derive(Clone)]
 pub struct Client {
     endpoint: String,
     client: reqwest::Client,
+    /// Cache of responses, shared between all clones of the client.
+    cache: Option<Arc<Mutex<Box<dyn ResponseStore>>>>,
+    max_retries: usize,
+    retry_backoff: Duration,
+    /// Token checked before every request, see [`Client::with_cancellation`].
+    cancellation: Option<CancellationToken>,
+    /// Limits the requests in flight, shared between all clones of the client.
+    request_slots: Option<Arc<Semaphore>>,
+    /// Amount of objects requested per page of a paginated request.
+    page_size: usize,
+}
+
+/// Error returned by the requests of a [`Client`].
+#[derive(Debug)]
+pub enum GraphQlError {
+    /// The request could not be sent, or the response could not be read.
+    Http(reqwest::Error),
+    /// The request timed out, also after retrying if the client retries requests.
+    Timeout,
+    /// The variables of the request could not be encoded.
+    Encode(serde_json::Error),
+    /// The response could not be decoded, e.g. because the schema changed.
+    Decode {
+        /// Name of the operation of the request.
+        operation_name: String,
+        /// The reason the response could not be decoded.
+        source: serde_json::Error,
+        /// The start of the response body.
+        body: String,
+    },
+    /// The server responded with errors instead of data, e.g. for an invalid query.
+    GraphQl(Vec<String>),
+    /// The request was not sent, since the [`CancellationToken`] of the client is cancelled.
+    Cancelled,
+    /// There is no contract with the given ID.
+    ContractNotFound(u64),
+    /// The client could not be built, e.g. because a configured header or the proxy url is not
+    /// valid.
+    Config(String),
+}
+
+impl GraphQlError {
+    /// Check if the request might succeed when it is sent again, because the server could not be
+    /// reached or did not respond in time.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Timeout => true,
+            Self::Http(e) => e.is_connect() || e.is_timeout(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for GraphQlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "Request failed: {e}"),
+            Self::Timeout => f.write_str("Request timed out"),
+            Self::Encode(e) => write!(f, "Failed to encode request variables: {e}"),
+            Self::Decode {
+                operation_name,
+                source,
+                body,
+            } => write!(
+                f,
+                "Failed to decode {operation_name} response: {source}, response body: {body}"
+            ),
+            Self::GraphQl(messages) => write!(f, "GraphQL error: {}", messages.join("; ")),
+            Self::Cancelled => f.write_str("Request cancelled"),
+            Self::ContractNotFound(contract_id) => write!(f, "Contract {contract_id} not found"),
+            Self::Config(e) => write!(f, "Invalid client configuration: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphQlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            Self::Encode(e) | Self::Decode { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for GraphQlError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::Http(e)
+        }
+    }
+}
+
+impl From<GraphQlError> for String {
+    fn from(e: GraphQlError) -> Self {
+        e.to_string()
+    }
+}
+
+/// A token to cooperatively cancel the requests of a [`Client`]. All clones of a token share the
+/// same state, so cancelling one cancels all of them.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new token which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token. Requests of clients using this token, which are not sent yet, fail with
+    /// [`GraphQlError::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Check if the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Filters of [`Client::contracts`]. By default, contracts in all states are returned, and no
+/// other filter is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractFilters {
+    nodes: Option<Vec<u32>>,
+    states: Vec<ContractState>,
+    twins: Option<Vec<u32>>,
+    contract_ids: Vec<u64>,
+    spids: Vec<u32>,
+    deployment_hashes: Option<Vec<String>>,
+    created_after: Option<i64>,
+    created_before: Option<i64>,
+}
+
+impl Default for ContractFilters {
+    fn default() -> Self {
+        Self {
+            nodes: None,
+            states: ALL_CONTRACT_STATES.to_vec(),
+            twins: None,
+            contract_ids: Vec::new(),
+            spids: Vec::new(),
+            deployment_hashes: None,
+            created_after: None,
+            created_before: None,
+        }
+    }
+}
+
+impl ContractFilters {
+    /// Only return contracts on one of the given nodes. Name contracts are not bound to a node,
+    /// and are returned regardless.
+    pub fn nodes(mut self, nodes: impl Into<Vec<u32>>) -> Self {
+        self.nodes = Some(nodes.into());
+        self
+    }
+
+    /// Only return contracts in one of the given states.
+    pub fn states(mut self, states: impl Into<Vec<ContractState>>) -> Self {
+        self.states = states.into();
+        self
+    }
+
+    /// Only return contracts owned by one of the given twins.
+    pub fn twins(mut self, twins: impl Into<Vec<u32>>) -> Self {
+        self.twins = Some(twins.into());
+        self
+    }
+
+    /// Only return the contracts with the given IDs. An empty list does not filter.
+    pub fn contract_ids(mut self, contract_ids: impl Into<Vec<u64>>) -> Self {
+        self.contract_ids = contract_ids.into();
+        self
+    }
+
+    /// Only return contracts created through one of the given solution providers. An empty list
+    /// does not filter.
+    pub fn spids(mut self, spids: impl Into<Vec<u32>>) -> Self {
+        self.spids = spids.into();
+        self
+    }
+
+    /// Only return node contracts with one of the given deployment hashes. Other types of
+    /// contracts don't have a deployment hash, so they are not returned at all.
+    pub fn deployment_hashes(mut self, hashes: impl Into<Vec<String>>) -> Self {
+        self.deployment_hashes = Some(hashes.into());
+        self
+    }
+
+    /// Only return contracts created at or after the given timestamp, in seconds.
+    pub fn created_after(mut self, ts: i64) -> Self {
+        self.created_after = Some(ts);
+        self
+    }
+
+    /// Only return contracts created at or before the given timestamp, in seconds.
+    pub fn created_before(mut self, ts: i64) -> Self {
+        self.created_before = Some(ts);
+        self
+    }
+}
+
+/// Connection settings of a [`Client`], see [`Client::with_config`]. Except for the page size,
+/// these only apply on native targets, on the web the browser decides how long to wait and which
+/// user agent to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConfig {
+    /// Time to wait for a connection to the server.
+    pub connect_timeout: Duration,
+    /// Time after which a request fails with [`GraphQlError::Timeout`], from connecting until the
+    /// whole response is read. Every page of a paginated request is a separate request, and a
+    /// retried request starts over. Requests don't time out once connected if [`None`].
+    pub request_timeout: Option<Duration>,
+    /// Value of the `User-Agent` header of every request.
+    pub user_agent: String,
+    /// Amount of objects requested per page of a paginated request. Smaller pages are less likely
+    /// to fail on a flaky connection, larger pages need fewer requests. The server might not allow
+    /// pages beyond a certain size. A page size of 0 is treated as 1.
+    pub page_size: usize,
+}
+
+impl Default for ClientConfig {
+    /// A connect timeout of 5 seconds, no request timeout, the `tfgrid_graphql_client` user agent
+    /// and pages of 1000 objects.
+    fn default() -> Self {
+        Self {
+            connect_timeout: CONNECT_TIMEOUT,
+            request_timeout: None,
+            user_agent: USER_AGENT.to_string(),
+            page_size: PAGE_SIZE,
+        }
+    }
+}
+
+/// A builder to configure a [`Client`].
+pub struct ClientBuilder {
+    endpoint: String,
+    cache: Option<Box<dyn ResponseStore>>,
+    max_retries: usize,
+    retry_backoff: Duration,
+    max_concurrent_requests: Option<usize>,
+    headers: Vec<(String, String)>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<String>,
+    config: ClientConfig,
+}
+
+impl ClientBuilder {
+    /// Creates a new builder for a client which will connect to the given endpoint.
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            cache: None,
+            max_retries: 0,
+            retry_backoff: Duration::from_secs(1),
+            max_concurrent_requests: None,
+            headers: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
+            config: ClientConfig::default(),
+        }
+    }
+
+    /// Cache up to `capacity` responses in memory for `ttl`. Requests with the same operation
+    /// and variables as a cached response are answered from the cache, without sending a request.
+    /// If the cache is full, the least recently used response is evicted. By default, nothing is
+    /// cached.
+    pub fn cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = Some(Box::new(ResponseCache::new(capacity, ttl)));
+        self
+    }
+
+    /// Cache responses in the given store instead of in memory, e.g. to keep them between runs.
+    /// Requests with the same operation and variables as a stored response are answered from the
+    /// store, without sending a request. This replaces a cache set with [`ClientBuilder::cache`].
+    pub fn cache_store(mut self, store: impl ResponseStore + 'static) -> Self {
+        self.cache = Some(Box::new(store));
+        self
+    }
+
+    /// Retry failed requests up to `max_retries` times. Requests are retried if the server can't
+    /// be reached, or responds with a server error or `429 Too Many Requests`. Before retrying,
+    /// the client waits for `backoff`, doubling on every subsequent attempt. A rate limited
+    /// request instead waits as long as the server asks in the `Retry-After` header, if present.
+    /// By default, requests are not retried.
+    pub fn retry(mut self, max_retries: usize, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Send at most `limit` requests at the same time. The limit is shared between all clones of
+    /// the client, so it bounds the requests of all callers together, e.g. every contract of
+    /// which the cost is loaded. Requests over the limit wait until another request finishes. A
+    /// request waiting to be retried does not count towards the limit. By default, the amount of
+    /// requests is not limited.
+    pub fn max_concurrent_requests(mut self, limit: usize) -> Self {
+        self.max_concurrent_requests = Some(limit.max(1));
+        self
+    }
+
+    /// Request `size` objects per page of a paginated request, 1000 by default, see
+    /// [`ClientConfig::page_size`].
+    pub fn page_size(mut self, size: usize) -> Self {
+        self.config.page_size = size.max(1);
+        self
+    }
+
+    /// Add a header which is sent with every request, e.g. an API key for a gateway in front of
+    /// the endpoint. Headers are accumulated, setting a header with the same name multiple times
+    /// sends all values. The header name and value are validated when the client is built.
+    pub fn header(mut self, name: String, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Send all requests through the HTTP proxy at `url`. The proxy url is validated when the
+    /// client is built. This is only available on native targets, on the web requests go through
+    /// the proxy configured in the browser.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, url: String) -> Self {
+        self.proxy = Some(url);
+        self
+    }
+
+    /// Use the given connection settings, instead of [`ClientConfig::default`].
+    pub fn config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Wait at most `timeout` for a connection to the server, see
+    /// [`ClientConfig::connect_timeout`].
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    /// Fail requests which take longer than `timeout`, see [`ClientConfig::request_timeout`].
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Send `user_agent` as the `User-Agent` header of every request, see
+    /// [`ClientConfig::user_agent`].
+    pub fn user_agent(mut self, user_agent: String) -> Self {
+        self.config.user_agent = user_agent;
+        self
+    }
+
+    /// Build the configured [`Client`]. No validation is done on the url at this stage. This
+    /// fails with [`GraphQlError::Config`] if a configured header or the proxy url is not valid.
+    ///
+    /// On native targets, the TLS backend of the client is selected with the `rustls-tls`
+    /// (default) or `native-tls` feature. Exactly one of them should be enabled. If both are
+    /// enabled, e.g. because a dependent crate enables `native-tls` without disabling the default
+    /// features, `native-tls` is used.
+    pub fn build(self) -> Result<Client, GraphQlError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| GraphQlError::Config(format!("header name {name:?}: {e}")))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| GraphQlError::Config(format!("value of header {name}: {e}")))?;
+            headers.append(name, value);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let client = {
+            let mut builder = reqwest::ClientBuilder::new()
+                .default_headers(headers)
+                .gzip(true)
+                .connect_timeout(self.config.connect_timeout)
+                .user_agent(self.config.user_agent);
+            if let Some(proxy) = self.proxy {
+                let proxy = reqwest::Proxy::all(&proxy)
+                    .map_err(|e| GraphQlError::Config(format!("proxy url {proxy:?}: {e}")))?;
+                builder = builder.proxy(proxy);
+            }
+            if let Some(timeout) = self.config.request_timeout {
+                builder = builder.timeout(timeout);
+            }
+            #[cfg(all(feature = "rustls-tls", not(feature = "native-tls")))]
+            {
+                builder = builder.use_rustls_tls();
+            }
+            #[cfg(feature = "native-tls")]
+            {
+                builder = builder.use_native_tls();
+            }
+            builder
+                .build()
+                .map_err(|e| GraphQlError::Config(e.to_string()))?
+        };
+        // The browser manages the connection, so the connection settings don't apply.
+        #[cfg(target_arch = "wasm32")]
+        let client = {
+            let _ = self.config;
+            reqwest::ClientBuilder::new()
+                .default_headers(headers)
+                .build()
+                .map_err(|e| GraphQlError::Config(e.to_string()))?
+        };
+        Ok(Client {
+            endpoint: self.endpoint,
+            client,
+            cache: self.cache.map(|store| Arc::new(Mutex::new(store))),
+            max_retries: self.max_retries,
+            retry_backoff: self.retry_backoff,
+            cancellation: None,
+            request_slots: self
+                .max_concurrent_requests
+                .map(|limit| Arc::new(Semaphore::new(limit))),
+            page_size: self.config.page_size.max(1),
+        })
+    }
 }
 
 #[derive(Serialize)]
@@ -108,16 +776,37 @@ struct GraphQLRequest<'a, T: Serialize> {
     variables: Option<T>,
 }
 
+/// A graphql response. A request which failed has errors, and no data if it failed entirely.
 #[derive(Deserialize)]
 struct GraphQLResponse<T> {
-    data: T,
+    data: Option<T>,
+    errors: Option<Vec<GraphQlErrorEntry>>,
+}
+
+impl<T> GraphQLResponse<T> {
+    /// Take the errors in the response as a [`GraphQlError`], if there are any.
+    fn error(&mut self) -> Option<GraphQlError> {
+        match self.errors.take() {
+            Some(errors) if !errors.is_empty() => Some(GraphQlError::GraphQl(
+                errors.into_iter().map(|error| error.message).collect(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GraphQlErrorEntry {
+    message: String,
 }
 
 #[derive(Serialize)]
-struct UptimeVariables {
-    node_id: u32,
+struct UptimeVariables<'a> {
+    node_ids: &'a [u32],
     start: i64,
     end: i64,
+    offset: usize,
+    limit: usize,
 }
 
 #[derive(Serialize)]
@@ -126,15 +815,17 @@ struct ContractBillReportVariables<'a> {
     start: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     end: Option<i64>,
-    #[serde(skip_serializing_if = "<[_]>::is_empty")]
-    contract_ids: &'a [u64],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contract_ids: Option<&'a [u64]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discount: Option<DiscountLevel>,
     offset: usize,
+    limit: usize,
 }
 
-#[derive(Serialize)]
+/// Filters shared by the queries of all contract types.
+#[derive(Serialize, Clone, Copy)]
 struct ContractsVariables<'a> {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    nodes: Option<&'a [u32]>,
     states: &'a [ContractState],
     #[serde(skip_serializing_if = "Option::is_none")]
     twins: Option<&'a [u32]>,
@@ -142,20 +833,102 @@ struct ContractsVariables<'a> {
     contract_ids: &'a [u64],
     #[serde(skip_serializing_if = "<[_]>::is_empty")]
     spids: &'a [u32],
+    /// Lower bound of the creation time, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_after: Option<i64>,
+    /// Upper bound of the creation time, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_before: Option<i64>,
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct NodeContractsVariables<'a> {
+    #[serde(flatten)]
+    filter: ContractsVariables<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nodes: Option<&'a [u32]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hashes: Option<&'a [String]>,
+}
+
+#[derive(Serialize)]
+struct NodeContractNodesVariables<'a> {
+    nodes: &'a [u32],
+    states: &'a [ContractState],
     offset: usize,
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct RentContractsVariables<'a> {
+    #[serde(flatten)]
+    filter: ContractsVariables<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nodes: Option<&'a [u32]>,
 }
 
 #[derive(Serialize)]
 struct NRUConsumptionVariables<'a> {
-    #[serde(skip_serializing_if = "<[_]>::is_empty")]
-    contract_ids: &'a [u64],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contract_ids: Option<&'a [u64]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<i64>,
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct FarmVariables<'a> {
+    farm_ids: &'a [u32],
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct NodeVariables<'a> {
+    node_ids: &'a [u32],
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct SolutionProviderVariables<'a> {
+    spids: &'a [u32],
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct TwinVariables<'a> {
+    twin_ids: &'a [u32],
+    offset: usize,
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct ContractContextVariables<'a> {
+    node_ids: &'a [u32],
+    twin_ids: &'a [u32],
     offset: usize,
+    limit: usize,
 }
 
 #[derive(Deserialize)]
 struct UptimeEventResponse {
     #[serde(rename = "uptimeEvents")]
-    uptime_events: Vec<UptimeEvent>,
+    uptime_events: Vec<NodeUptimeEvent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeUptimeEvent {
+    #[serde(rename = "nodeID")]
+    node_id: u32,
+    #[serde(flatten)]
+    event: UptimeEvent,
 }
 
 #[derive(Deserialize)]
@@ -165,11 +938,32 @@ struct ContractBillEventResponse {
 }
 
 #[derive(Deserialize)]
-struct ContractsResponse {
+struct NodeContractsResponse {
     #[serde(rename = "nodeContracts")]
     node_contracts: Vec<NodeContract>,
+}
+
+#[derive(Deserialize)]
+struct NodeContractNodesResponse {
+    #[serde(rename = "nodeContracts")]
+    node_contracts: Vec<NodeContractNode>,
+}
+
+/// The node of a node contract, without any of the other contract fields.
+#[derive(Deserialize)]
+struct NodeContractNode {
+    #[serde(rename = "nodeID")]
+    node_id: u32,
+}
+
+#[derive(Deserialize)]
+struct NameContractsResponse {
     #[serde(rename = "nameContracts")]
     name_contracts: Vec<NameContract>,
+}
+
+#[derive(Deserialize)]
+struct RentContractsResponse {
     #[serde(rename = "rentContracts")]
     rent_contracts: Vec<RentContract>,
 }
@@ -180,45 +974,282 @@ struct NRUConsumptionResponse {
     consumption_reports: Vec<NRUConsumption>,
 }
 
-impl Client {
-    /// Creates a new Client which will connect to the given endpoint. No validation is done on the
-    /// url at this stage.
-    pub fn new(endpoint: String) -> Result<Client, Box<dyn std::error::Error>> {
-        Ok(Client {
-            endpoint,
-            #[cfg(not(target_arch = "wasm32"))]
-            client: reqwest::ClientBuilder::new()
-                .gzip(true)
-                .connect_timeout(Duration::from_secs(5))
-                .user_agent(USER_AGENT)
-                .build()?,
-            #[cfg(target_arch = "wasm32")]
-            client: reqwest::ClientBuilder::new().build()?,
-        })
+#[derive(Deserialize)]
+struct FarmsResponse {
+    farms: Vec<Farm>,
+}
+
+#[derive(Deserialize)]
+struct SolutionProvidersResponse {
+    #[serde(rename = "solutionProviders")]
+    solution_providers: Vec<SolutionProvider>,
+}
+
+#[derive(Deserialize)]
+struct NodesResponse {
+    nodes: Vec<Node>,
+}
+
+#[derive(Deserialize)]
+struct TwinsResponse {
+    twins: Vec<Twin>,
+}
+
+#[derive(Deserialize)]
+struct ContractContextResponse {
+    nodes: Vec<Node>,
+    twins: Vec<Twin>,
+}
+
+impl Client {
+    /// Creates a new Client which will connect to the given endpoint. No validation is done on the
+    /// url at this stage.
+    pub fn new(endpoint: String) -> Result<Client, GraphQlError> {
+        Self::with_config(endpoint, ClientConfig::default())
+    }
+
+    /// Creates a new Client which will connect to the given endpoint with the given connection
+    /// settings. No validation is done on the url at this stage. Use [`Client::builder`] to
+    /// configure more than the connection.
+    pub fn with_config(endpoint: String, config: ClientConfig) -> Result<Client, GraphQlError> {
+        Self::builder(endpoint).config(config).build()
+    }
+
+    /// Creates a [`ClientBuilder`] to configure a client which will connect to the given endpoint.
+    pub fn builder(endpoint: String) -> ClientBuilder {
+        ClientBuilder::new(endpoint)
     }
 
     /// Creates a new client connected to the mainnet graphql instance.
-    pub fn mainnet() -> Result<Client, Box<dyn std::error::Error>> {
+    pub fn mainnet() -> Result<Client, GraphQlError> {
         Self::new(MAINNET_URL.to_string())
     }
 
     /// Creates a new client connected to the testnet graphql instance.
-    pub fn testnet() -> Result<Client, Box<dyn std::error::Error>> {
+    pub fn testnet() -> Result<Client, GraphQlError> {
         Self::new(TESTNET_URL.to_string())
     }
 
     /// Creates a new client connected ot the qanet graphql instance.
-    pub fn qanet() -> Result<Client, Box<dyn std::error::Error>> {
+    pub fn qanet() -> Result<Client, GraphQlError> {
         Self::new(QANET_URL.to_string())
     }
 
     /// Creates a new client connected ot the devnet graphql instance.
-    pub fn devnet() -> Result<Client, Box<dyn std::error::Error>> {
+    pub fn devnet() -> Result<Client, GraphQlError> {
         Self::new(DEVNET_URL.to_string())
     }
 
-    // TODO: make these methods a single generic with a trait + associated type on
-    // request/response
+    /// Creates a clone of this client which checks the given token before every request, including
+    /// every page of paginated requests. Once the token is cancelled, requests fail with
+    /// [`GraphQlError::Cancelled`] instead of being sent, so a long fetch stops promptly. Other
+    /// clones of the client are not affected.
+    pub fn with_cancellation(&self, token: CancellationToken) -> Client {
+        Client {
+            cancellation: Some(token),
+            ..self.clone()
+        }
+    }
+
+    /// Remove all cached responses. This does nothing if the client has no cache.
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.lock().expect("cache lock is not poisoned").clear();
+        }
+    }
+
+    /// Send an arbitrary graphql query, and return the data in the response as untyped JSON. This
+    /// is meant for debugging and exploring the schema, without adding a typed method first. The
+    /// response is never taken from the cache, so it is exactly what the server sent. Like all
+    /// other requests, the query is not sent if the client is cancelled. Use [`Client::query`] to
+    /// decode the response into a type.
+    ///
+    /// __Unstable__: this method might change or be removed without notice.
+    pub async fn raw_query<V: Serialize>(
+        &self,
+        operation_name: &str,
+        query: &str,
+        variables: &V,
+    ) -> Result<serde_json::Value, GraphQlError> {
+        self.check_cancelled(operation_name)?;
+        self.send(operation_name, query, variables).await
+    }
+
+    /// Fail with [`GraphQlError::Cancelled`] if the cancellation token of the client is cancelled,
+    /// so the request with the given operation name is not sent.
+    fn check_cancelled(&self, operation_name: &str) -> Result<(), GraphQlError> {
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            log::debug!("Not sending {operation_name} request, the client is cancelled");
+            return Err(GraphQlError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Send a graphql query, and decode the data in the response as `R`. All typed methods of the
+    /// client are built on this, so the query gets the same retries, request limit, cancellation
+    /// and error handling. If the client has a cache, the response is taken from the cache if
+    /// possible. The query is sent as is, it is not paginated.
+    pub async fn query<V, R>(
+        &self,
+        operation_name: &str,
+        query: &str,
+        variables: V,
+    ) -> Result<R, GraphQlError>
+    where
+        V: Serialize,
+        R: DeserializeOwned,
+    {
+        self.check_cancelled(operation_name)?;
+
+        let Some(cache) = &self.cache else {
+            return self.send(operation_name, query, &variables).await;
+        };
+
+        let key = CacheKey::new(
+            operation_name,
+            &serde_json::to_string(&variables).map_err(GraphQlError::Encode)?,
+        );
+        let cached = cache
+            .lock()
+            .expect("cache lock is not poisoned")
+            .get(&key, Utc::now());
+        let data = match cached {
+            Some(data) => {
+                log::trace!("Using cached response for {operation_name} request");
+                data
+            }
+            None => {
+                let data: serde_json::Value = self.send(operation_name, query, &variables).await?;
+                cache.lock().expect("cache lock is not poisoned").insert(
+                    key,
+                    data.clone(),
+                    Utc::now(),
+                );
+                data
+            }
+        };
+
+        R::deserialize(&data).map_err(|e| decode_error(operation_name, e, &data.to_string()))
+    }
+
+    /// Send a single graphql request, and decode the data in the response.
+    async fn send<V, R>(
+        &self,
+        operation_name: &str,
+        query: &str,
+        variables: &V,
+    ) -> Result<R, GraphQlError>
+    where
+        V: Serialize,
+        R: DeserializeOwned,
+    {
+        let mut attempt = 0;
+        loop {
+            // The permit is held until the response is read, or the request is retried.
+            let permit = match &self.request_slots {
+                Some(slots) => Some(
+                    slots
+                        .acquire()
+                        .await
+                        .expect("Request slots are never closed"),
+                ),
+                None => None,
+            };
+            log::trace!("Sending {operation_name} request to {}", self.endpoint);
+            let result = self
+                .client
+                .post(&self.endpoint)
+                .json(&GraphQLRequest {
+                    operation_name,
+                    query,
+                    variables: Some(variables),
+                })
+                .send()
+                .await;
+            let response = match result {
+                Err(e) if (e.is_timeout() || e.is_connect()) && attempt < self.max_retries => {
+                    log::warn!("Retryable failure for {operation_name} request: {e}");
+                    drop(permit);
+                    sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => {
+                    log::debug!("Failed to send {operation_name} request: {e}");
+                    return Err(e.into());
+                }
+                Ok(response) => response,
+            };
+            log::trace!(
+                "Received response for {operation_name} request with status {}",
+                response.status()
+            );
+
+            let status = response.status();
+            if (status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error())
+                && attempt < self.max_retries
+            {
+                // A rate limited request should be retried as instructed by the server.
+                let delay = if status == StatusCode::TOO_MANY_REQUESTS {
+                    retry_after(response.headers(), Utc::now())
+                } else {
+                    None
+                }
+                .unwrap_or_else(|| self.backoff(attempt));
+                log::warn!(
+                    "Retrying {operation_name} request after status {status} in {}s",
+                    delay.as_secs_f64()
+                );
+                drop(permit);
+                sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            // The body is read first, so it can be included in the error if it can't be decoded.
+            let body = response.text().await.map_err(|e| {
+                log::debug!("Failed to read {operation_name} response: {e}");
+                GraphQlError::from(e)
+            })?;
+            let mut response = match serde_json::from_str::<GraphQLResponse<R>>(&body) {
+                Ok(response) => response,
+                Err(e) => {
+                    // Data which is partially missing because of errors might not decode, the
+                    // errors are more useful than the decode error.
+                    return Err(
+                        match serde_json::from_str::<GraphQLResponse<IgnoredAny>>(&body)
+                            .ok()
+                            .and_then(|mut response| response.error())
+                        {
+                            Some(err) => err,
+                            None => decode_error(operation_name, e, &body),
+                        },
+                    );
+                }
+            };
+            if let Some(err) = response.error() {
+                log::debug!("Server returned errors for {operation_name} request: {err}");
+                return Err(err);
+            }
+            return response.data.ok_or_else(|| {
+                decode_error(
+                    operation_name,
+                    serde::de::Error::missing_field("data"),
+                    &body,
+                )
+            });
+        }
+    }
+
+    /// The time to wait before retrying a failed request, which doubles on every attempt.
+    fn backoff(&self, attempt: usize) -> Duration {
+        self.retry_backoff
+            .saturating_mul(2u32.saturating_pow(attempt as u32))
+    }
 
     /// Fetch the uptime events for the given node in the given time range. The returned values are
     /// requested to be sorted in ascending timestamp order from the server.
@@ -227,195 +1258,2259 @@ impl Client {
         node_id: u32,
         start: i64,
         end: i64,
-    ) -> Result<Vec<UptimeEvent>, String> {
+    ) -> Result<Vec<UptimeEvent>, GraphQlError> {
         Ok(self
-            .client
-            .post(&self.endpoint)
-            .json(&GraphQLRequest {
-                operation_name: "get_uptime_events",
-                query: UPTIME_EVENT_QUERY,
-                variables: Some(&UptimeVariables {
-                    node_id,
-                    start,
-                    end,
-                }),
-            })
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .json::<GraphQLResponse<UptimeEventResponse>>()
-            .await
-            .map_err(|e| e.to_string())?
-            .data
-            .uptime_events)
+            .uptime_events_multi(&[node_id], start, end)
+            .await?
+            .remove(&node_id)
+            .unwrap_or_default())
     }
 
-    /// Fetch all contract bill reports in the given time range.
+    /// Fetch the uptime events for all the given nodes in the given time range, grouped per node.
+    /// Every requested node is present in the result, even if it has no uptime events. The values
+    /// of every node are requested to be sorted in ascending timestamp order from the server.
+    pub async fn uptime_events_multi(
+        &self,
+        node_ids: &[u32],
+        start: i64,
+        end: i64,
+    ) -> Result<BTreeMap<u32, Vec<UptimeEvent>>, GraphQlError> {
+        let mut uptime_events: BTreeMap<u32, Vec<UptimeEvent>> = node_ids
+            .iter()
+            .map(|&node_id| (node_id, Vec::new()))
+            .collect();
+        let new_events = fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching uptime events at offset {offset}");
+            Ok(self
+                .query::<_, UptimeEventResponse>(
+                    "get_uptime_events",
+                    UPTIME_EVENT_QUERY,
+                    &UptimeVariables {
+                        node_ids,
+                        start,
+                        end,
+                        offset,
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .uptime_events)
+        })
+        .await?;
+        // Events are returned in ascending timestamp order, so appending keeps the order per node.
+        for NodeUptimeEvent { node_id, event } in new_events {
+            uptime_events.entry(node_id).or_default().push(event);
+        }
+
+        Ok(uptime_events)
+    }
+
+    /// Fetch all contract bill reports in the given time range. If contract IDs are given, only
+    /// bills of those contracts are returned, so no bills are returned for an empty list. If a
+    /// discount level is given, only bills which received that discount are returned.
     pub async fn contract_bill_reports(
         &self,
         start: Option<i64>,
         end: Option<i64>,
-        contract_ids: &[u64],
-    ) -> Result<Vec<ContractBillReport>, String> {
-        let mut offset = 0;
-        let mut bill_reports = Vec::new();
-        loop {
-            let mut new_bills = self
-                .client
-                .post(&self.endpoint)
-                .json(&GraphQLRequest {
-                    operation_name: "get_contract_bill_reports",
-                    query: CONTRACT_BILL_REPORT_QUERY,
-                    variables: Some(&ContractBillReportVariables {
+        contract_ids: Option<&[u64]>,
+        discount: Option<DiscountLevel>,
+    ) -> Result<Vec<ContractBillReport>, GraphQlError> {
+        if contract_ids.is_some_and(<[_]>::is_empty) {
+            return Ok(Vec::new());
+        }
+        fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching contract bill reports at offset {offset}");
+            Ok(self
+                .query::<_, ContractBillEventResponse>(
+                    "get_contract_bill_reports",
+                    CONTRACT_BILL_REPORT_QUERY,
+                    &ContractBillReportVariables {
                         start,
                         end,
                         contract_ids,
+                        discount,
                         offset,
-                    }),
-                })
-                .send()
-                .await
-                .map_err(|e| e.to_string())?
-                .json::<GraphQLResponse<ContractBillEventResponse>>()
-                .await
-                .map_err(|e| e.to_string())?
-                .data
-                .contract_bill_reports;
-            let new_objects = new_bills.len();
-            offset += new_objects;
-            bill_reports.append(&mut new_bills);
-
-            if new_objects != PAGE_SIZE {
-                break;
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .contract_bill_reports)
+        })
+        .await
+    }
+
+    /// Fetch all contracts matching the given filters, see [`ContractFilters`].
+    pub async fn contracts(&self, filters: &ContractFilters) -> Result<Contracts, GraphQlError> {
+        let nodes = filters.nodes.as_deref();
+        let deployment_hashes = filters.deployment_hashes.as_deref();
+        let filter = ContractsVariables {
+            states: &filters.states,
+            twins: filters.twins.as_deref(),
+            contract_ids: &filters.contract_ids,
+            spids: &filters.spids,
+            // The creation time of contracts is in milliseconds.
+            created_after: filters.created_after.map(|ts| ts * 1000),
+            created_before: filters.created_before.map(|ts| ts * 1000),
+            offset: 0,
+            limit: self.page_size,
+        };
+        let node_contracts = fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching node contracts at offset {offset}");
+            Ok(self
+                .query::<_, NodeContractsResponse>(
+                    "node_contracts",
+                    NODE_CONTRACTS_QUERY,
+                    &NodeContractsVariables {
+                        filter: ContractsVariables { offset, ..filter },
+                        nodes,
+                        hashes: deployment_hashes,
+                    },
+                )
+                .await?
+                .node_contracts)
+        });
+        // Only node contracts have a deployment hash, so other types of contracts are not
+        // queried if the hashes are filtered.
+        let other_types = deployment_hashes.is_none();
+        let name_contracts = async {
+            if !other_types {
+                return Ok(Vec::new());
+            }
+            fetch_all_pages(self.page_size, |offset| async move {
+                log::debug!("Fetching name contracts at offset {offset}");
+                Ok(self
+                    .query::<_, NameContractsResponse>(
+                        "name_contracts",
+                        NAME_CONTRACTS_QUERY,
+                        &ContractsVariables { offset, ..filter },
+                    )
+                    .await?
+                    .name_contracts)
+            })
+            .await
+        };
+        let rent_contracts = async {
+            if !other_types {
+                return Ok(Vec::new());
             }
+            fetch_all_pages(self.page_size, |offset| async move {
+                log::debug!("Fetching rent contracts at offset {offset}");
+                Ok(self
+                    .query::<_, RentContractsResponse>(
+                        "rent_contracts",
+                        RENT_CONTRACTS_QUERY,
+                        &RentContractsVariables {
+                            filter: ContractsVariables { offset, ..filter },
+                            nodes,
+                        },
+                    )
+                    .await?
+                    .rent_contracts)
+            })
+            .await
+        };
+        // Every type of contract is paginated on its own, the requests are sent concurrently.
+        let (node_contracts, name_contracts, rent_contracts) =
+            futures::future::try_join3(node_contracts, name_contracts, rent_contracts).await?;
+        Ok(Contracts {
+            node_contracts,
+            name_contracts,
+            rent_contracts,
+        })
+    }
+
+    /// Count the active node contracts on the given nodes, i.e. those which are not deleted. Every
+    /// requested node is present in the result, even if it has no contracts. Only the node of
+    /// every contract is fetched, which is far lighter than fetching the contracts themselves
+    /// with [`Client::contracts`].
+    pub async fn node_contract_counts(
+        &self,
+        node_ids: &[u32],
+    ) -> Result<BTreeMap<u32, u64>, GraphQlError> {
+        let mut counts: BTreeMap<u32, u64> = node_ids.iter().map(|&node_id| (node_id, 0)).collect();
+        // An empty node filter would count the contracts on all nodes.
+        if node_ids.is_empty() {
+            return Ok(counts);
         }
+        let states = [
+            ContractState::Created,
+            ContractState::GracePeriod,
+            ContractState::OutOfFunds,
+        ];
+        let contracts = fetch_all_pages(self.page_size, |offset| {
+            let states = &states;
+            async move {
+                log::debug!("Fetching node contract nodes at offset {offset}");
+                Ok(self
+                    .query::<_, NodeContractNodesResponse>(
+                        "node_contract_nodes",
+                        NODE_CONTRACT_NODES_QUERY,
+                        &NodeContractNodesVariables {
+                            nodes: node_ids,
+                            states,
+                            offset,
+                            limit: self.page_size,
+                        },
+                    )
+                    .await?
+                    .node_contracts)
+            }
+        })
+        .await?;
+        for contract in contracts {
+            *counts.entry(contract.node_id).or_default() += 1;
+        }
+        Ok(counts)
+    }
 
-        Ok(bill_reports)
+    /// Fetch the NRU consumption reports of the given contracts, optionally only those reported
+    /// between `start` and `end` (inclusive). Without bounds, the whole history of the contracts
+    /// is fetched, which can be large for long lived contracts. Without contract IDs, the reports
+    /// of all contracts are fetched, and no reports are returned for an empty list.
+    pub async fn nru_consumptions(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+        contract_ids: Option<&[u64]>,
+    ) -> Result<Vec<NRUConsumption>, GraphQlError> {
+        if contract_ids.is_some_and(<[_]>::is_empty) {
+            return Ok(Vec::new());
+        }
+        fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching nru consumptions at offset {offset}");
+            Ok(self
+                .query::<_, NRUConsumptionResponse>(
+                    "nru_consumptions",
+                    NRU_CONSUMPTION_QUERY,
+                    &NRUConsumptionVariables {
+                        contract_ids,
+                        start,
+                        end,
+                        offset,
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .consumption_reports)
+        })
+        .await
     }
 
-    /// Fetch all contracts in the given states from the given nodes.
-    pub async fn contracts(
+    /// Fetch the farms with the given IDs.
+    pub async fn farms(&self, farm_ids: &[u32]) -> Result<Vec<Farm>, GraphQlError> {
+        fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching farms at offset {offset}");
+            Ok(self
+                .query::<_, FarmsResponse>(
+                    "farms",
+                    FARMS_QUERY,
+                    &FarmVariables {
+                        farm_ids,
+                        offset,
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .farms)
+        })
+        .await
+    }
+
+    /// Fetch the solution providers with the given IDs, in ascending ID order.
+    pub async fn solution_providers(
         &self,
-        nodes: Option<&[u32]>,
-        states: &[ContractState],
-        twins: Option<&[u32]>,
-        contract_ids: &[u64],
         spids: &[u32],
-    ) -> Result<Contracts, String> {
-        let mut node_contracts = Vec::new();
-        let mut name_contracts = Vec::new();
-        let mut rent_contracts = Vec::new();
+    ) -> Result<Vec<SolutionProvider>, GraphQlError> {
+        fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching solution providers at offset {offset}");
+            Ok(self
+                .query::<_, SolutionProvidersResponse>(
+                    "solution_providers",
+                    SOLUTION_PROVIDERS_QUERY,
+                    &SolutionProviderVariables {
+                        spids,
+                        offset,
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .solution_providers)
+        })
+        .await
+    }
+
+    /// Fetch all nodes in the given farms, in ascending node ID order.
+    pub async fn nodes(&self, farm_ids: &[u32]) -> Result<Vec<Node>, GraphQlError> {
+        fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching nodes at offset {offset}");
+            Ok(self
+                .query::<_, NodesResponse>(
+                    "nodes",
+                    NODES_QUERY,
+                    &FarmVariables {
+                        farm_ids,
+                        offset,
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .nodes)
+        })
+        .await
+    }
+
+    /// Fetch the nodes with the given IDs, in ascending node ID order. Node IDs which are not
+    /// known are not present in the result.
+    pub async fn nodes_by_id(&self, node_ids: &[u32]) -> Result<Vec<Node>, GraphQlError> {
+        fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching nodes by ID at offset {offset}");
+            Ok(self
+                .query::<_, NodesResponse>(
+                    "nodes_by_id",
+                    NODES_BY_ID_QUERY,
+                    &NodeVariables {
+                        node_ids,
+                        offset,
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .nodes)
+        })
+        .await
+    }
+
+    /// Fetch the twins with the given IDs.
+    pub async fn twins(&self, twin_ids: &[u32]) -> Result<Vec<Twin>, GraphQlError> {
+        fetch_all_pages(self.page_size, |offset| async move {
+            log::debug!("Fetching twins at offset {offset}");
+            Ok(self
+                .query::<_, TwinsResponse>(
+                    "twins",
+                    TWINS_QUERY,
+                    &TwinVariables {
+                        twin_ids,
+                        offset,
+                        limit: self.page_size,
+                    },
+                )
+                .await?
+                .twins)
+        })
+        .await
+    }
+
+    /// Resolve the nodes, farms and twins referenced by the given contracts. The nodes and twins
+    /// are fetched together in a single request (per page). Farms are only known once the nodes
+    /// are fetched, so they are fetched in a second request. Entities which don't exist are not
+    /// present in the result.
+    pub async fn resolve_contract_context(
+        &self,
+        contracts: &Contracts,
+    ) -> Result<ContractContext, GraphQlError> {
+        let node_ids = contracts
+            .node_contracts
+            .iter()
+            .map(|c| c.node_id)
+            .chain(contracts.rent_contracts.iter().map(|c| c.node_id))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let twin_ids = contracts
+            .node_contracts
+            .iter()
+            .map(|c| c.twin_id)
+            .chain(contracts.name_contracts.iter().map(|c| c.twin_id))
+            .chain(contracts.rent_contracts.iter().map(|c| c.twin_id))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut nodes = BTreeMap::new();
+        let mut twins = BTreeMap::new();
         let mut offset = 0;
-        loop {
-            let ContractsResponse {
-                name_contracts: mut new_name_contracts,
-                node_contracts: mut new_node_contracts,
-                rent_contracts: mut new_rent_contracts,
+        while !node_ids.is_empty() || !twin_ids.is_empty() {
+            log::debug!("Fetching contract context at offset {offset}");
+            let ContractContextResponse {
+                nodes: new_nodes,
+                twins: new_twins,
             } = self
-                .client
-                .post(&self.endpoint)
-                .json(&GraphQLRequest {
-                    operation_name: "contracts",
-                    query: CONTRACTS_QUERY,
-                    variables: Some(&ContractsVariables {
-                        nodes,
-                        states,
-                        twins,
-                        contract_ids,
-                        spids,
+                .query(
+                    "contract_context",
+                    CONTRACT_CONTEXT_QUERY,
+                    &ContractContextVariables {
+                        node_ids: &node_ids,
+                        twin_ids: &twin_ids,
                         offset,
-                    }),
-                })
-                .send()
-                .await
-                .map_err(|e| format!("{}", e))?
-                .json::<GraphQLResponse<ContractsResponse>>()
-                .await
-                .map_err(|e| format!("{}", e))?
-                .data;
-            let found_objects = usize::max(
-                new_node_contracts.len(),
-                usize::max(new_name_contracts.len(), new_rent_contracts.len()),
+                        limit: self.page_size,
+                    },
+                )
+                .await?;
+            log::debug!(
+                "Fetched {} nodes and {} twins at offset {offset}",
+                new_nodes.len(),
+                new_twins.len()
             );
+            let found_objects = usize::max(new_nodes.len(), new_twins.len());
             offset += found_objects;
-            node_contracts.append(&mut new_node_contracts);
-            name_contracts.append(&mut new_name_contracts);
-            rent_contracts.append(&mut new_rent_contracts);
-            if found_objects != PAGE_SIZE {
+            nodes.extend(new_nodes.into_iter().map(|node| (node.node_id, node)));
+            twins.extend(new_twins.into_iter().map(|twin| (twin.twin_id, twin)));
+            if found_objects != self.page_size {
                 break;
             }
         }
-        Ok(Contracts {
-            node_contracts,
-            name_contracts,
-            rent_contracts,
+
+        let farm_ids = nodes
+            .values()
+            .map(|node| node.farm_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let farms = if farm_ids.is_empty() {
+            BTreeMap::new()
+        } else {
+            self.farms(&farm_ids)
+                .await?
+                .into_iter()
+                .map(|farm| (farm.farm_id, farm))
+                .collect()
+        };
+
+        Ok(ContractContext {
+            nodes,
+            farms,
+            twins,
         })
     }
 
-    pub async fn nru_consumptions(
+    /// Fetch the state transitions of a contract, oldest first. The state history is not stored
+    /// by the graphql server, so it is reconstructed from the bill reports of the contract, see
+    /// [`contract_events`](crate::analytics::contract_events).
+    pub async fn contract_events(
         &self,
-        contract_ids: &[u64],
-    ) -> Result<Vec<NRUConsumption>, String> {
-        let mut consumptions = Vec::new();
-        let mut offset = 0;
-        loop {
-            let mut new_consumptions = self
-                .client
-                .post(&self.endpoint)
-                .json(&GraphQLRequest {
-                    operation_name: "nru_consumptions",
-                    query: NRU_CONSUMPTION_QUERY,
-                    variables: Some(&NRUConsumptionVariables {
-                        contract_ids,
-                        offset,
-                    }),
-                })
-                .send()
-                .await
-                .map_err(|e| format!("{}", e))?
-                .json::<GraphQLResponse<NRUConsumptionResponse>>()
-                .await
-                .map_err(|e| format!("{}", e))?
-                .data
-                .consumption_reports;
-            let found_objects = new_consumptions.len();
-            offset += found_objects;
-            consumptions.append(&mut new_consumptions);
-            if found_objects != PAGE_SIZE {
-                break;
+        contract_id: u64,
+    ) -> Result<Vec<ContractEvent>, GraphQlError> {
+        let Some(contract) = self.contract(contract_id).await? else {
+            return Err(GraphQlError::ContractNotFound(contract_id));
+        };
+        let bills = self
+            .contract_bill_reports(None, None, Some(&[contract_id]), None)
+            .await?;
+
+        Ok(contract_events(&contract, &bills))
+    }
+
+    /// Fetch the contract with the given ID, of any type and in any state. Returns [`None`] if
+    /// there is no contract with this ID.
+    pub async fn contract(&self, contract_id: u64) -> Result<Option<Contract>, GraphQlError> {
+        Ok(self
+            .contracts(&ContractFilters::default().contract_ids([contract_id]))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Calculate the total amount billed per twin in the given time range. All contracts of the
+    /// twins are considered, including expired ones, since they might still have been billed in
+    /// the time range. Every requested twin is present in the result, even if nothing was billed.
+    pub async fn billing_by_twin(
+        &self,
+        twin_ids: &[u32],
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> Result<BTreeMap<u32, u64>, GraphQlError> {
+        let mut billed: BTreeMap<u32, u64> = twin_ids.iter().map(|&twin| (twin, 0)).collect();
+        if twin_ids.is_empty() {
+            return Ok(billed);
+        }
+
+        let contracts = self
+            .contracts(&ContractFilters::default().twins(twin_ids))
+            .await?;
+        let contract_owners: BTreeMap<u64, u32> = contracts
+            .node_contracts
+            .iter()
+            .map(|c| (c.contract_id, c.twin_id))
+            .chain(
+                contracts
+                    .name_contracts
+                    .iter()
+                    .map(|c| (c.contract_id, c.twin_id)),
+            )
+            .chain(
+                contracts
+                    .rent_contracts
+                    .iter()
+                    .map(|c| (c.contract_id, c.twin_id)),
+            )
+            .collect();
+        let contract_ids = contract_owners.keys().copied().collect::<Vec<_>>();
+        for report in self
+            .contract_bill_reports(start, end, Some(&contract_ids), None)
+            .await?
+        {
+            if let Some(twin) = contract_owners.get(&report.contract_id) {
+                *billed.entry(*twin).or_default() += report.amount_billed;
             }
         }
-        Ok(consumptions)
+
+        Ok(billed)
+    }
+}
+
+/// Describe why the response to an operation could not be decoded, including the start of the
+/// response body, so changes in the shape of the response can be found.
+fn decode_error(operation_name: &str, err: serde_json::Error, body: &str) -> GraphQlError {
+    log::debug!("Failed to decode {operation_name} response: {err}");
+    let mut snippet = body
+        .chars()
+        .take(DECODE_ERROR_SNIPPET_LEN)
+        .collect::<String>();
+    if snippet.len() < body.len() {
+        snippet.push_str("...");
+    }
+    GraphQlError::Decode {
+        operation_name: operation_name.to_string(),
+        source: err,
+        body: snippet,
+    }
+}
+
+/// Get the time to wait before retrying a request from the `Retry-After` header of a response, if
+/// present. The header holds either an amount of seconds, or an HTTP date after which the request
+/// can be retried. A date in the past results in no wait.
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
     }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Fetch all pages of a paginated query, where `page_fn` fetches the page of `page_size` objects
+/// at the given offset. Pages are fetched one after the other, until a page holds less than
+/// `page_size` objects.
+async fn fetch_all_pages<T, F, Fut>(
+    page_size: usize,
+    mut page_fn: F,
+) -> Result<Vec<T>, GraphQlError>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, GraphQlError>>,
+{
+    let mut objects = Vec::new();
+    loop {
+        let offset = objects.len();
+        let mut page = page_fn(offset).await?;
+        let found_objects = page.len();
+        log::debug!("Fetched {found_objects} objects at offset {offset}");
+        objects.append(&mut page);
+        if found_objects != page_size {
+            return Ok(objects);
+        }
+    }
+}
+
+/// Wait for the given duration.
+async fn sleep(duration: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::sleep(duration).await;
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Client;
+    use super::{
+        decode_error, fetch_all_pages, retry_after, CancellationToken, Client, ClientConfig,
+        ContractBillReportVariables, ContractContextVariables, ContractFilters, Contracts,
+        ContractsVariables, FarmVariables, GraphQLResponse, GraphQlError, NRUConsumptionVariables,
+        NodeContractNodesVariables, NodeContractsVariables, NodeUptimeEvent, NodeVariables,
+        RentContractsVariables, SolutionProviderVariables, TwinVariables, UptimeEventResponse,
+        UptimeVariables, ALL_CONTRACT_STATES, CONTRACT_BILL_REPORT_QUERY, CONTRACT_CONTEXT_QUERY,
+        DECODE_ERROR_SNIPPET_LEN, FARMS_QUERY, NAME_CONTRACTS_QUERY, NODES_BY_ID_QUERY,
+        NODES_QUERY, NODE_CONTRACTS_QUERY, NODE_CONTRACT_NODES_QUERY, NRU_CONSUMPTION_QUERY,
+        PAGE_SIZE, RENT_CONTRACTS_QUERY, SOLUTION_PROVIDERS_QUERY, TWINS_QUERY, UPTIME_EVENT_QUERY,
+    };
+    use crate::{
+        analytics::{aggregate_bills, state_distribution, top_contracts, BillBucket},
+        bill_report::{ContractBillReport, DiscountLevel},
+        cache::{CacheKey, ResponseStore},
+        consumption::NRUConsumption,
+        contract::{Contract, ContractState, NameContract, NodeContract, RentContract, Resources},
+        uptime::{calculate_node_state_changes, downtime_windows},
+    };
+    use chrono::{TimeZone, Utc};
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    /// The variables declared by a query, with their GraphQL type.
+    fn declared_variables(query: &str) -> BTreeMap<&str, &str> {
+        let start = query.find('(').expect("Query declares variables");
+        let end = start + query[start..].find(')').expect("Variable list is closed");
+        query[start + 1..end]
+            .split(',')
+            .map(|declaration| {
+                let (name, ty) = declaration
+                    .split_once(':')
+                    .expect("Variable declaration has a type");
+                (name.trim().trim_start_matches('$'), ty.trim())
+            })
+            .collect()
+    }
+
+    /// Check if a serialized variable can be used as a value of the given GraphQL type.
+    fn matches_type(value: &serde_json::Value, ty: &str) -> bool {
+        if let Some(ty) = ty.strip_suffix('!') {
+            return !value.is_null() && matches_type(value, ty);
+        }
+        if value.is_null() {
+            return true;
+        }
+        if let Some(item_ty) = ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+            return value
+                .as_array()
+                .is_some_and(|items| items.iter().all(|item| matches_type(item, item_ty)));
+        }
+        match ty {
+            "Int" => value.is_i64() || value.is_u64(),
+            // Large numbers might be passed as strings.
+            "BigInt" => value.is_i64() || value.is_u64() || value.is_string(),
+            "Boolean" => value.is_boolean(),
+            "String" | "ContractState" | "DiscountLevel" => value.is_string(),
+            _ => false,
+        }
+    }
+
+    /// Assert that variables with all optional fields set serialize to exactly the variables
+    /// declared by the query, with matching types, and that every declared variable is used.
+    fn assert_variables_match<V: Serialize>(query: &str, variables: &V) {
+        let declared = declared_variables(query);
+        let serialized = serde_json::to_value(variables).expect("Can serialize variables");
+        let serialized = serialized.as_object().expect("Variables are an object");
+
+        assert_eq!(
+            serialized
+                .keys()
+                .map(String::as_str)
+                .collect::<BTreeSet<_>>(),
+            declared.keys().copied().collect::<BTreeSet<_>>(),
+            "Serialized variables don't match the declared variables of {query}"
+        );
+        let body = &query[query.find('{').expect("Query has a body")..];
+        for (name, ty) in declared {
+            assert!(
+                matches_type(&serialized[name], ty),
+                "Variable {name} is serialized as {}, which is not a {ty}",
+                serialized[name]
+            );
+            let used = body.match_indices(&format!("${name}")).any(|(idx, var)| {
+                !body[idx + var.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+            });
+            assert!(used, "Variable {name} is declared but not used in {query}");
+        }
+    }
+
+    #[test]
+    fn variables_match_queries() {
+        let hashes = ["hash".to_string()];
+        assert_variables_match(
+            UPTIME_EVENT_QUERY,
+            &UptimeVariables {
+                node_ids: &[1],
+                start: 0,
+                end: 1,
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+        assert_variables_match(
+            CONTRACT_BILL_REPORT_QUERY,
+            &ContractBillReportVariables {
+                start: Some(0),
+                end: Some(1),
+                contract_ids: Some(&[1]),
+                discount: Some(DiscountLevel::Gold),
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+        let filter = ContractsVariables {
+            states: &ALL_CONTRACT_STATES,
+            twins: Some(&[1]),
+            contract_ids: &[1],
+            spids: &[1],
+            created_after: Some(0),
+            created_before: Some(1),
+            offset: 0,
+            limit: PAGE_SIZE,
+        };
+        assert_variables_match(
+            NODE_CONTRACTS_QUERY,
+            &NodeContractsVariables {
+                filter,
+                nodes: Some(&[1]),
+                hashes: Some(&hashes),
+            },
+        );
+        assert_variables_match(NAME_CONTRACTS_QUERY, &filter);
+        assert_variables_match(
+            NODE_CONTRACT_NODES_QUERY,
+            &NodeContractNodesVariables {
+                nodes: &[1],
+                states: &ALL_CONTRACT_STATES,
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+        assert_variables_match(
+            RENT_CONTRACTS_QUERY,
+            &RentContractsVariables {
+                filter,
+                nodes: Some(&[1]),
+            },
+        );
+        assert_variables_match(
+            NRU_CONSUMPTION_QUERY,
+            &NRUConsumptionVariables {
+                contract_ids: Some(&[1]),
+                start: Some(0),
+                end: Some(1),
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+        let farm_variables = FarmVariables {
+            farm_ids: &[1],
+            offset: 0,
+            limit: PAGE_SIZE,
+        };
+        assert_variables_match(FARMS_QUERY, &farm_variables);
+        assert_variables_match(NODES_QUERY, &farm_variables);
+        assert_variables_match(
+            NODES_BY_ID_QUERY,
+            &NodeVariables {
+                node_ids: &[1],
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+        assert_variables_match(
+            SOLUTION_PROVIDERS_QUERY,
+            &SolutionProviderVariables {
+                spids: &[1],
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+        assert_variables_match(
+            TWINS_QUERY,
+            &TwinVariables {
+                twin_ids: &[1],
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+        assert_variables_match(
+            CONTRACT_CONTEXT_QUERY,
+            &ContractContextVariables {
+                node_ids: &[1],
+                twin_ids: &[1],
+                offset: 0,
+                limit: PAGE_SIZE,
+            },
+        );
+    }
+
+    /// A response of a graphql server recorded in a fixture file, together with the request it
+    /// answers.
+    #[derive(Deserialize)]
+    struct Fixture {
+        operation_name: String,
+        variables: serde_json::Value,
+        response: serde_json::Value,
+    }
+
+    /// Serves the responses recorded in the fixture files in a directory. A request is answered
+    /// with the response of the fixture with the same operation name and variables, requests
+    /// without a matching fixture are answered with an error.
+    struct ReplayServer {
+        server: mockito::ServerGuard,
+        _mocks: Vec<mockito::Mock>,
+    }
+
+    impl Fixture {
+        /// Load a fixture file.
+        fn load(path: &std::path::Path) -> Self {
+            serde_json::from_slice(&std::fs::read(path).expect("Can read fixture file"))
+                .unwrap_or_else(|e| panic!("Can decode fixture {}: {e}", path.display()))
+        }
+    }
+
+    /// Directory holding the fixture files.
+    fn fixture_dir() -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+    }
+
+    impl ReplayServer {
+        /// Load all fixture files in the `fixtures` directory of the crate.
+        async fn new() -> Self {
+            let mut server = mockito::Server::new_async().await;
+            let mut mocks = Vec::new();
+            for entry in std::fs::read_dir(fixture_dir()).expect("Can read fixture directory") {
+                let fixture =
+                    Fixture::load(&entry.expect("Can read fixture directory entry").path());
+                mocks.push(
+                    server
+                        .mock("POST", "/")
+                        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                            "operation_name": fixture.operation_name,
+                            "variables": fixture.variables,
+                        })))
+                        .with_body(fixture.response.to_string())
+                        .create_async()
+                        .await,
+                );
+            }
+            Self {
+                server,
+                _mocks: mocks,
+            }
+        }
+
+        /// A client which sends its requests to the replay server.
+        fn client(&self) -> Client {
+            Client::new(self.server.url()).expect("Can create a client")
+        }
+    }
 
     #[tokio::test]
-    async fn fetch_uptime_events() {
-        let cl = Client::new("https://graphql.grid.tf/graphql".to_string())
+    async fn replay_uptime_events() {
+        let replay = ReplayServer::new().await;
+        let (start, end) = (1663850262, 1663857474);
+
+        let ues = replay
+            .client()
+            .uptime_events(4200, start, end)
+            .await
+            .expect("Can fetch recorded uptime events");
+
+        assert_eq!(ues.len(), 2);
+        // Went down after the first event, booted 1200 seconds before the second event, and did
+        // not report its uptime in the remainder of the period.
+        let changes = calculate_node_state_changes(&ues, start, end);
+        assert_eq!(
+            downtime_windows(&changes, end),
+            [(1663852062, 1663854462), (1663855662, end)]
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_contract_bill_reports() {
+        let replay = ReplayServer::new().await;
+
+        let bills = replay
+            .client()
+            .contract_bill_reports(Some(1663850262), Some(1663857474), None, None)
+            .await
+            .expect("Can fetch recorded contract bill reports");
+
+        assert_eq!(bills.len(), 4);
+        assert_eq!(
+            aggregate_bills(&bills, BillBucket::Hour)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [
+                (1663848000, 7_500_000),
+                (1663851600, 5_000_000),
+                (1663855200, 1_000)
+            ]
+        );
+        assert_eq!(top_contracts(&bills, 1), [(100, 10_000_000)]);
+        // The recorded responses don't hold bills for other periods.
+        assert!(replay
+            .client()
+            .contract_bill_reports(Some(0), Some(1663850262), None, None)
+            .await
+            .is_err());
+    }
+
+    /// Fetch all pages of `total` numbers, recording the offsets of the requested pages.
+    async fn fetch_numbers(total: usize) -> (Result<Vec<usize>, GraphQlError>, Vec<usize>) {
+        let mut offsets = Vec::new();
+        let numbers = fetch_all_pages(PAGE_SIZE, |offset| {
+            offsets.push(offset);
+            async move { Ok((offset..total.min(offset + PAGE_SIZE)).collect()) }
+        })
+        .await;
+        (numbers, offsets)
+    }
+
+    #[tokio::test]
+    async fn fetch_all_pages_until_short_page() {
+        // A full last page needs another request to know it is the last one.
+        let (numbers, offsets) = fetch_numbers(2 * PAGE_SIZE).await;
+        assert_eq!(numbers.ok(), Some((0..2 * PAGE_SIZE).collect()));
+        assert_eq!(offsets, [0, PAGE_SIZE, 2 * PAGE_SIZE]);
+
+        let (numbers, offsets) = fetch_numbers(PAGE_SIZE + 3).await;
+        assert_eq!(numbers.ok(), Some((0..PAGE_SIZE + 3).collect()));
+        assert_eq!(offsets, [0, PAGE_SIZE]);
+
+        let (numbers, offsets) = fetch_numbers(0).await;
+        assert_eq!(numbers.ok(), Some(Vec::new()));
+        assert_eq!(offsets, [0]);
+    }
+
+    #[tokio::test]
+    async fn stop_fetching_pages_on_error() {
+        let mut pages = 0;
+        let result = fetch_all_pages(PAGE_SIZE, |offset| {
+            pages += 1;
+            async move {
+                if offset == 0 {
+                    Ok(vec![0; PAGE_SIZE])
+                } else {
+                    Err(GraphQlError::GraphQl(
+                        vec!["Page not available".to_string()],
+                    ))
+                }
+            }
+        })
+        .await;
+
+        assert!(
+            matches!(result, Err(GraphQlError::GraphQl(messages)) if messages == ["Page not available"])
+        );
+        assert_eq!(pages, 2);
+    }
+
+    /// Decode every row of `list` in the recorded response of a fixture file, serialize it again
+    /// and check nothing was lost or changed on the way, so fields dropped by the types are
+    /// noticed.
+    fn assert_fixture_round_trips<T: Serialize + DeserializeOwned>(file: &str, list: &str) {
+        let fixture = Fixture::load(&fixture_dir().join(file));
+        let rows = fixture.response["data"][list]
+            .as_array()
+            .unwrap_or_else(|| panic!("{file} records a {list} list"));
+        assert!(!rows.is_empty(), "{file} records some {list}");
+        for row in rows {
+            let decoded: T = serde_json::from_value(row.clone())
+                .unwrap_or_else(|e| panic!("Can decode {list} row {row} of {file}: {e}"));
+            let encoded = serde_json::to_value(&decoded).expect("Can serialize decoded row");
+            assert_eq!(
+                &encoded, row,
+                "{list} row of {file} changed in a round trip"
+            );
+            serde_json::from_value::<T>(encoded).expect("Can decode serialized row again");
+        }
+    }
+
+    #[test]
+    fn round_trip_recorded_responses() {
+        assert_fixture_round_trips::<NodeContract>("node_contracts.json", "nodeContracts");
+        assert_fixture_round_trips::<NameContract>("name_contracts.json", "nameContracts");
+        assert_fixture_round_trips::<RentContract>("rent_contracts.json", "rentContracts");
+        assert_fixture_round_trips::<ContractBillReport>(
+            "contract_bill_reports.json",
+            "contractBillReports",
+        );
+        assert_fixture_round_trips::<NodeUptimeEvent>("uptime_events.json", "uptimeEvents");
+        assert_fixture_round_trips::<NRUConsumption>("nru_consumptions.json", "nruConsumptions");
+    }
+
+    #[tokio::test]
+    async fn replay_contracts() {
+        let replay = ReplayServer::new().await;
+
+        let contracts = replay
+            .client()
+            .contracts(
+                &ContractFilters::default()
+                    .nodes([42])
+                    .states([ContractState::Created, ContractState::GracePeriod]),
+            )
+            .await
+            .expect("Can fetch recorded contracts");
+
+        assert_eq!(contracts.node_contracts.len(), 3);
+        assert_eq!(contracts.name_contracts.len(), 1);
+        assert_eq!(contracts.rent_contracts.len(), 1);
+        let distribution = state_distribution(&contracts);
+        assert_eq!(distribution[&ContractState::Created], 4);
+        assert_eq!(distribution[&ContractState::GracePeriod], 1);
+        let large = contracts.filter_node_resources(|r| r.mru > 32 * 1024 * 1024 * 1024);
+        assert_eq!(
+            large.iter().map(|c| c.contract_id).collect::<Vec<_>>(),
+            [101]
+        );
+    }
+
+    #[test]
+    fn decode_multi_node_uptime_events() {
+        let resp: GraphQLResponse<UptimeEventResponse> = serde_json::from_str(
+            r#"{"data": {"uptimeEvents": [
+                {"nodeID": 1, "timestamp": "100", "uptime": "10"},
+                {"nodeID": 2, "timestamp": "150", "uptime": "5"}
+            ]}}"#,
+        )
+        .expect("Can decode uptime events of multiple nodes");
+
+        let events = resp.data.expect("Response has data").uptime_events;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].node_id, 1);
+        assert_eq!(events[0].event.timestamp(), 100);
+        assert_eq!(events[1].node_id, 2);
+        assert_eq!(events[1].event.uptime(), 5);
+    }
+
+    #[test]
+    fn parse_retry_after() {
+        let now = Utc.timestamp_opt(784_111_777, 0).unwrap();
+        let headers =
+            |value| HeaderMap::from_iter([(RETRY_AFTER, HeaderValue::from_static(value))]);
+
+        assert_eq!(
+            retry_after(&headers("2"), now),
+            Some(Duration::from_secs(2))
+        );
+        // 10 seconds after now.
+        assert_eq!(
+            retry_after(&headers("Sun, 06 Nov 1994 08:49:47 GMT"), now),
+            Some(Duration::from_secs(10))
+        );
+        // Dates in the past don't wait.
+        assert_eq!(
+            retry_after(&headers("Sun, 06 Nov 1994 08:49:27 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers("soon"), now), None);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[tokio::test]
+    async fn retry_failed_page_without_duplicate_rows() {
+        let mut server = mockito::Server::new_async().await;
+        let page = |timestamps: std::ops::Range<usize>| {
+            let events = timestamps
+                .map(|ts| {
+                    serde_json::json!({
+                        "nodeID": 1,
+                        "timestamp": ts.to_string(),
+                        "uptime": (ts + 1).to_string(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            serde_json::json!({"data": {"uptimeEvents": events}}).to_string()
+        };
+        let at_offset = |offset: usize| {
+            mockito::Matcher::PartialJson(serde_json::json!({"variables": {"offset": offset}}))
+        };
+        let first_page = server
+            .mock("POST", "/")
+            .match_body(at_offset(0))
+            .with_body(page(0..PAGE_SIZE))
+            .expect(1)
+            .create_async()
+            .await;
+        let failed_second_page = server
+            .mock("POST", "/")
+            .match_body(at_offset(PAGE_SIZE))
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("POST", "/")
+            .match_body(at_offset(PAGE_SIZE))
+            .with_body(page(PAGE_SIZE..PAGE_SIZE + 3))
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::builder(server.url())
+            .retry(1, Duration::from_millis(10))
+            .build()
             .expect("Can create a client");
 
         let ues = cl
-            .uptime_events(4200, 1663850262, 1663857474)
+            .uptime_events(1, 0, 10_000)
             .await
-            .expect("Can fetch uptime events from mainnet");
+            .expect("Failed page is retried");
 
-        assert_eq!(ues.len(), 2);
+        // Only the failed page is requested again, and its rows are only added once.
+        first_page.assert_async().await;
+        failed_second_page.assert_async().await;
+        second_page.assert_async().await;
+        assert_eq!(
+            ues.iter().map(|ue| ue.timestamp()).collect::<Vec<_>>(),
+            (0..PAGE_SIZE as i64 + 3).collect::<Vec<_>>()
+        );
     }
 
     #[tokio::test]
-    async fn fetch_contract_bill_reports() {
-        let cl = Client::new("https://graphql.grid.tf/graphql".to_string())
+    async fn page_uptime_events_with_equal_timestamps() {
+        let mut server = mockito::Server::new_async().await;
+        // Events with the same timestamp need a tiebreak, otherwise their order can change
+        // between the requests of two pages.
+        let at_offset = |offset: usize| {
+            mockito::Matcher::AllOf(vec![
+                mockito::Matcher::Regex("timestamp_ASC, nodeID_ASC, id_ASC".to_string()),
+                mockito::Matcher::PartialJson(serde_json::json!({
+                    "variables": {"node_ids": [1, 2], "offset": offset, "limit": 2},
+                })),
+            ])
+        };
+        let first_page = server
+            .mock("POST", "/")
+            .match_body(at_offset(0))
+            .with_body(
+                r#"{"data": {"uptimeEvents": [
+                    {"nodeID": 1, "timestamp": "100", "uptime": "10"},
+                    {"nodeID": 1, "timestamp": "200", "uptime": "110"}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("POST", "/")
+            .match_body(at_offset(2))
+            .with_body(
+                r#"{"data": {"uptimeEvents": [
+                    {"nodeID": 2, "timestamp": "200", "uptime": "50"}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::builder(server.url())
+            .page_size(2)
+            .build()
             .expect("Can create a client");
 
+        let events = cl
+            .uptime_events_multi(&[1, 2], 0, 1_000)
+            .await
+            .expect("Can fetch uptime events in pages");
+
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+        let timestamps = |node_id| {
+            events[&node_id]
+                .iter()
+                .map(|ue| ue.timestamp())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(timestamps(1), [100, 200]);
+        assert_eq!(timestamps(2), [200]);
+    }
+
+    #[tokio::test]
+    async fn fetch_pages_of_configured_size() {
+        let mut server = mockito::Server::new_async().await;
+        let at_offset = |offset: usize| {
+            mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "farms",
+                "variables": {"offset": offset, "limit": 2},
+            }))
+        };
+        let first_page = server
+            .mock("POST", "/")
+            .match_body(at_offset(0))
+            .with_body(
+                r#"{"data": {"farms": [
+                    {"farmID": 1, "name": "one", "twinID": 1},
+                    {"farmID": 2, "name": "two", "twinID": 2}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("POST", "/")
+            .match_body(at_offset(2))
+            .with_body(r#"{"data": {"farms": [{"farmID": 3, "name": "three", "twinID": 3}]}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::builder(server.url())
+            .page_size(2)
+            .build()
+            .expect("Can create a client");
+
+        let farms = cl.farms(&[]).await.expect("Can fetch farms in pages");
+
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+        assert_eq!(
+            farms.iter().map(|farm| farm.farm_id).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_rate_limited_request_after_delay() {
+        let mut server = mockito::Server::new_async().await;
+        let rate_limited = server
+            .mock("POST", "/")
+            .with_status(429)
+            .with_header("Retry-After", "2")
+            .expect(1)
+            .create_async()
+            .await;
+        let success = server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_body(
+                r#"{"data": {"uptimeEvents": [{"nodeID": 1, "timestamp": "10", "uptime": "5"}]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        // The generic backoff is far shorter than the delay requested by the server.
+        let cl = Client::builder(server.url())
+            .retry(1, Duration::from_millis(10))
+            .build()
+            .expect("Can create a client");
+
+        let start = Instant::now();
         let ues = cl
-            .contract_bill_reports(Some(1663850262), Some(1663857474), &[])
+            .uptime_events(1, 0, 100)
+            .await
+            .expect("Rate limited request is retried");
+
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert_eq!(ues.len(), 1);
+        rate_limited.assert_async().await;
+        success.assert_async().await;
+    }
+
+    /// A store which keeps responses forever, and can be inspected by the test.
+    #[derive(Clone, Default)]
+    struct SharedStore(Arc<Mutex<HashMap<CacheKey, serde_json::Value>>>);
+
+    impl ResponseStore for SharedStore {
+        fn get(&mut self, key: &CacheKey, _: chrono::DateTime<Utc>) -> Option<serde_json::Value> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn insert(&mut self, key: CacheKey, data: serde_json::Value, _: chrono::DateTime<Utc>) {
+            self.0.lock().unwrap().insert(key, data);
+        }
+
+        fn clear(&mut self) {
+            self.0.lock().unwrap().clear();
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_responses_in_custom_store() {
+        let mut server = mockito::Server::new_async().await;
+        let uptime = server
+            .mock("POST", "/")
+            .with_body(
+                r#"{"data": {"uptimeEvents": [{"nodeID": 1, "timestamp": "10", "uptime": "5"}]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let store = SharedStore::default();
+        let cl = Client::builder(server.url())
+            .cache_store(store.clone())
+            .build()
+            .expect("Can create a client");
+
+        for _ in 0..2 {
+            let ues = cl
+                .uptime_events(1, 0, 100)
+                .await
+                .expect("Can fetch uptime events");
+            assert_eq!(ues.len(), 1);
+        }
+
+        uptime.assert_async().await;
+        {
+            let stored = store.0.lock().unwrap();
+            assert_eq!(stored.len(), 1);
+            let key = stored.keys().next().unwrap();
+            assert_eq!(key.operation_name(), "get_uptime_events");
+            assert!(key.variables().contains(r#""node_ids":[1]"#));
+        }
+        cl.clear_cache();
+        assert!(store.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolve_contract_context_in_two_requests() {
+        let mut server = mockito::Server::new_async().await;
+        let context = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "contract_context",
+                "variables": {"node_ids": [10], "twin_ids": [1, 2]},
+            })))
+            .with_body(
+                r#"{"data": {
+                    "nodes": [{"nodeID": 10, "farmID": 5, "twinID": 30, "country": "Belgium", "city": null}],
+                    "twins": [
+                        {"twinID": 1, "accountID": "5Alice", "relay": "relay.grid.tf"},
+                        {"twinID": 2, "accountID": "5Bob", "relay": null}
+                    ]
+                }}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let farms = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "farms",
+                "variables": {"farm_ids": [5]},
+            })))
+            .with_body(r#"{"data": {"farms": [{"farmID": 5, "name": "farm", "twinID": 20}]}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let contracts = Contracts {
+            node_contracts: Vec::new(),
+            name_contracts: vec![NameContract {
+                contract_id: 1,
+                created_at: 0,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 1,
+                name: String::new(),
+            }],
+            rent_contracts: vec![RentContract {
+                contract_id: 2,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 2,
+            }],
+        };
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let ctx = cl
+            .resolve_contract_context(&contracts)
+            .await
+            .expect("Can resolve contract context");
+
+        assert_eq!(ctx.nodes[&10].farm_id, 5);
+        assert_eq!(ctx.farms[&5].name, "farm");
+        assert_eq!(ctx.twins[&1].account_id, "5Alice");
+        assert_eq!(ctx.twins[&2].relay, None);
+        context.assert_async().await;
+        farms.assert_async().await;
+    }
+
+    #[test]
+    fn retain_only_active_contracts() {
+        let name_contract = |contract_id, state| NameContract {
+            contract_id,
+            created_at: 0,
+            solution_provider_id: None,
+            state,
+            twin_id: 1,
+            name: String::new(),
+        };
+        let mut contracts = Contracts {
+            node_contracts: vec![NodeContract {
+                contract_id: 1,
+                created_at: 0,
+                deployment_data: String::new(),
+                deployment_hash: String::new(),
+                grid_version: 3,
+                node_id: 10,
+                number_of_public_ips: 0,
+                resources_used: None,
+                solution_provider_id: None,
+                state: ContractState::OutOfFunds,
+                twin_id: 1,
+            }],
+            name_contracts: vec![
+                name_contract(2, ContractState::Created),
+                name_contract(3, ContractState::Deleted),
+                name_contract(4, ContractState::GracePeriod),
+            ],
+            rent_contracts: vec![RentContract {
+                contract_id: 5,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::Deleted,
+                twin_id: 1,
+            }],
+        };
+
+        contracts.retain_active();
+
+        assert!(contracts.node_contracts.is_empty());
+        assert_eq!(
+            contracts
+                .name_contracts
+                .iter()
+                .map(|c| c.contract_id)
+                .collect::<Vec<_>>(),
+            vec![2, 4]
+        );
+        assert!(contracts.rent_contracts.is_empty());
+    }
+
+    #[test]
+    fn sum_contracts_and_totals() {
+        let node_contract = |contract_id, number_of_public_ips| NodeContract {
+            contract_id,
+            created_at: 0,
+            deployment_data: String::new(),
+            deployment_hash: String::new(),
+            grid_version: 3,
+            node_id: 10,
+            number_of_public_ips,
+            resources_used: None,
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 1,
+        };
+        let first = Contracts {
+            node_contracts: vec![node_contract(1, 2)],
+            name_contracts: Vec::new(),
+            rent_contracts: vec![RentContract {
+                contract_id: 3,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 1,
+            }],
+        };
+        let second = Contracts {
+            node_contracts: vec![node_contract(2, 1)],
+            name_contracts: Vec::new(),
+            rent_contracts: Vec::new(),
+        };
+
+        let contracts: Contracts = [first, second].into_iter().sum();
+
+        assert_eq!(contracts.len(), 3);
+        assert!(!contracts.is_empty());
+        assert_eq!(contracts.total_public_ips(), 3);
+        assert_eq!(contracts.all_contract_ids(), vec![1, 2, 3]);
+        assert!(std::iter::empty::<Contracts>()
+            .sum::<Contracts>()
+            .is_empty());
+    }
+
+    #[test]
+    fn extend_partial_contracts() {
+        let partial = |contract_id| Contracts {
+            name_contracts: vec![NameContract {
+                contract_id,
+                created_at: 0,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 1,
+                name: format!("name{contract_id}"),
+            }],
+            ..Contracts::new()
+        };
+
+        let mut contracts = Contracts::new();
+        assert!(contracts.is_empty());
+        contracts.extend(partial(1));
+        contracts.extend(partial(2));
+
+        assert_eq!(contracts.len(), 2);
+        assert_eq!(contracts.name_contracts.len(), 2);
+        assert!(contracts.node_contracts.is_empty());
+        assert!(contracts.rent_contracts.is_empty());
+        assert_eq!(contracts.all_contract_ids(), vec![1, 2]);
+    }
+
+    #[test]
+    fn filter_node_contracts_on_resources() {
+        const GIB: u64 = 1 << 30;
+        let node_contract = |contract_id, mru: Option<u64>| NodeContract {
+            contract_id,
+            created_at: 0,
+            deployment_data: String::new(),
+            deployment_hash: String::new(),
+            grid_version: 3,
+            node_id: 10,
+            number_of_public_ips: 0,
+            resources_used: mru.map(|mru| Resources {
+                cru: 1,
+                hru: 0,
+                mru,
+                sru: 0,
+            }),
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 1,
+        };
+        let contracts = Contracts {
+            node_contracts: vec![
+                node_contract(1, Some(64 * GIB)),
+                node_contract(2, None),
+                node_contract(3, Some(2 * GIB)),
+                node_contract(4, Some(33 * GIB)),
+            ],
+            name_contracts: Vec::new(),
+            rent_contracts: Vec::new(),
+        };
+
+        let large = contracts.filter_node_resources(|r| r.mru > 32 * GIB);
+        assert_eq!(
+            large.iter().map(|c| c.contract_id).collect::<Vec<_>>(),
+            vec![1, 4]
+        );
+        // Contracts without resources are excluded, even if every set of resources matches.
+        assert_eq!(contracts.filter_node_resources(|_| true).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn send_requests_through_proxy() {
+        let mut proxy = mockito::Server::new_async().await;
+        // The proxy receives requests for the host of the endpoint.
+        let farms = proxy
+            .mock("POST", mockito::Matcher::Any)
+            .match_header("host", "graphql.example.invalid")
+            .with_body(r#"{"data": {"farms": [{"farmID": 5, "name": "farm", "twinID": 20}]}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::builder("http://graphql.example.invalid/graphql".to_string())
+            .proxy(proxy.url())
+            .build()
+            .expect("Can create a client with a proxy");
+
+        let farms_resp = cl.farms(&[5]).await.expect("Can fetch farms through proxy");
+
+        assert_eq!(farms_resp.len(), 1);
+        farms.assert_async().await;
+        assert!(matches!(
+            Client::builder("http://graphql.example.invalid".to_string())
+                .proxy("not a url".to_string())
+                .build(),
+            Err(GraphQlError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_custom_headers() {
+        let mut server = mockito::Server::new_async().await;
+        let farms = server
+            .mock("POST", "/")
+            .match_header("x-api-key", "secret")
+            .with_body(r#"{"data": {"farms": []}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::builder(server.url())
+            .header("X-Api-Key".to_string(), "secret".to_string())
+            .build()
+            .expect("Can create a client with custom headers");
+
+        cl.farms(&[5]).await.expect("Can fetch farms with headers");
+
+        farms.assert_async().await;
+        assert!(matches!(
+            Client::builder(server.url())
+                .header("X Api Key".to_string(), "secret".to_string())
+                .build(),
+            Err(GraphQlError::Config(_))
+        ));
+        assert!(matches!(
+            Client::builder(server.url())
+                .header("X-Api-Key".to_string(), "secret\n".to_string())
+                .build(),
+            Err(GraphQlError::Config(_))
+        ));
+    }
+
+    /// Mock the query of one `kind` of contracts, i.e. `node`, `name` or `rent`. Requests with
+    /// the given variables are answered once with the `found` list of contracts.
+    async fn mock_contracts(
+        server: &mut mockito::ServerGuard,
+        kind: &str,
+        variables: serde_json::Value,
+        found: &str,
+    ) -> mockito::Mock {
+        server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": format!("{kind}_contracts"),
+                "variables": variables,
+            })))
+            .with_body(format!(r#"{{"data": {{"{kind}Contracts": {found}}}}}"#))
+            .expect(1)
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn search_contracts_by_deployment_hash() {
+        let mut server = mockito::Server::new_async().await;
+        let hash = "8b9a3d5b0a1e6a7f1b0e2c3d4e5f6a7b".to_string();
+        // Name and rent contracts don't have a deployment hash, so they are not requested when
+        // filtering on hashes.
+        let contracts = mock_contracts(
+            &mut server,
+            "node",
+            serde_json::json!({"hashes": [hash]}),
+            r#"[{
+                "contractID": "12", "createdAt": "100", "deploymentData": "",
+                "deploymentHash": "8b9a3d5b0a1e6a7f1b0e2c3d4e5f6a7b", "gridVersion": 4,
+                "nodeID": 10, "numberOfPublicIPs": 0, "resourcesUsed": null,
+                "solutionProviderID": null, "state": "Created", "twinID": 1
+            }]"#,
+        )
+        .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let found = cl
+            .contracts(&ContractFilters::default().deployment_hashes([hash]))
+            .await
+            .expect("Can search contracts by deployment hash");
+
+        contracts.assert_async().await;
+        assert_eq!(found.all_contract_ids(), vec![12]);
+        assert!(found.name_contracts.is_empty());
+        assert!(found.rent_contracts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn filter_contracts_on_creation_window() {
+        let mut server = mockito::Server::new_async().await;
+        // The bounds are passed in seconds, but contracts are filtered on their creation time in
+        // milliseconds.
+        let variables = serde_json::json!({
+            "twins": [1],
+            "created_after": 1_700_000_000_000_i64,
+            "created_before": 1_700_604_799_000_i64,
+        });
+        let mut contracts = Vec::new();
+        for (kind, found) in [
+            ("node", "[]"),
+            (
+                "name",
+                r#"[{
+                    "contractID": "7", "createdAt": "1700100000000", "name": "example",
+                    "solutionProviderID": null, "state": "Created", "twinID": 1
+                }]"#,
+            ),
+            ("rent", "[]"),
+        ] {
+            contracts.push(mock_contracts(&mut server, kind, variables.clone(), found).await);
+        }
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let found = cl
+            .contracts(
+                &ContractFilters::default()
+                    .twins([1])
+                    .created_after(1_700_000_000)
+                    .created_before(1_700_604_799),
+            )
+            .await
+            .expect("Can filter contracts on their creation time");
+
+        for mock in contracts {
+            mock.assert_async().await;
+        }
+        assert_eq!(found.all_contract_ids(), vec![7]);
+    }
+
+    #[tokio::test]
+    async fn filter_bill_reports_on_discount_level() {
+        let mut server = mockito::Server::new_async().await;
+        let bills = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "get_contract_bill_reports",
+                "variables": {"discount": "None"},
+            })))
+            .with_body(
+                r#"{"data": {"contractBillReports": [
+                    {"amountBilled": "100", "contractID": "1", "timestamp": "1000", "discountReceived": "None"},
+                    {"amountBilled": "200", "contractID": "2", "timestamp": "2000", "discountReceived": "None"}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let found = cl
+            .contract_bill_reports(None, None, None, Some(DiscountLevel::None))
+            .await
+            .expect("Can fetch bill reports at a discount level");
+
+        bills.assert_async().await;
+        assert_eq!(found.len(), 2);
+        assert!(found
+            .iter()
+            .all(|bill| matches!(bill.discount_received, DiscountLevel::None)));
+    }
+
+    #[tokio::test]
+    async fn no_bills_without_contracts() {
+        let mut server = mockito::Server::new_async().await;
+        let requests = server.mock("POST", "/").expect(0).create_async().await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let bills = cl
+            .contract_bill_reports(None, None, Some(&[]), None)
+            .await
+            .expect("Can fetch the bills of no contracts");
+        let consumptions = cl
+            .nru_consumptions(None, None, Some(&[]))
+            .await
+            .expect("Can fetch the NRU consumptions of no contracts");
+
+        assert!(bills.is_empty());
+        assert!(consumptions.is_empty());
+        requests.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn decode_error_includes_response_body() {
+        let mut server = mockito::Server::new_async().await;
+        let _changed = server
+            .mock("POST", "/")
+            .with_body(
+                r#"{"data": {"uptimeEvents": [{"node": 1, "timestamp": "10", "uptime": "5"}]}}"#,
+            )
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let Err(err) = cl.uptime_events(1, 0, 100).await else {
+            panic!("Response with unknown shape can't be decoded");
+        };
+
+        assert!(matches!(err, GraphQlError::Decode { .. }));
+        let err = err.to_string();
+        assert!(err.starts_with("Failed to decode get_uptime_events response"));
+        assert!(err.contains(r#"{"node": 1, "timestamp": "10""#));
+
+        let long_body = "x".repeat(1000);
+        let err = decode_error(
+            "farms",
+            serde_json::from_str::<serde_json::Value>(&long_body).unwrap_err(),
+            &long_body,
+        )
+        .to_string();
+        assert!(err.ends_with(&format!("{}...", "x".repeat(DECODE_ERROR_SNIPPET_LEN))));
+    }
+
+    #[tokio::test]
+    async fn report_errors_in_response() {
+        let mut server = mockito::Server::new_async().await;
+        let farms = |farm_id: u32| {
+            mockito::Matcher::PartialJson(serde_json::json!({
+                "variables": {"farm_ids": [farm_id]},
+            }))
+        };
+        let _failed = server
+            .mock("POST", "/")
+            .match_body(farms(1))
+            .with_body(
+                r#"{"data": null, "errors": [
+                    {"message": "Cannot query field \"nodeID\""},
+                    {"message": "Unknown argument \"offset\""}
+                ]}"#,
+            )
+            .create_async()
+            .await;
+        let _partial = server
+            .mock("POST", "/")
+            .match_body(farms(2))
+            .with_body(
+                r#"{"data": {"farms": [{"farmID": 2}]}, "errors": [
+                    {"message": "Cannot return null for non-nullable field Farm.name."}
+                ]}"#,
+            )
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let Err(err) = cl.farms(&[1]).await else {
+            panic!("Response without data is an error");
+        };
+        assert!(!err.is_transient());
+        assert_eq!(
+            err.to_string(),
+            r#"GraphQL error: Cannot query field "nodeID"; Unknown argument "offset""#
+        );
+        assert!(matches!(err, GraphQlError::GraphQl(messages) if messages.len() == 2));
+
+        // The errors are reported, even though the partial data can't be decoded.
+        let Err(err) = cl.farms(&[2]).await else {
+            panic!("Response with errors is an error");
+        };
+        assert!(matches!(
+            err,
+            GraphQlError::GraphQl(messages)
+                if messages == ["Cannot return null for non-nullable field Farm.name."]
+        ));
+    }
+
+    #[tokio::test]
+    async fn query_with_custom_response_type() {
+        #[derive(Deserialize)]
+        struct Countries {
+            nodes: Vec<Country>,
+        }
+        #[derive(Deserialize)]
+        struct Country {
+            country: String,
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        let nodes = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "countries",
+                "variables": {"ids": [1, 2]},
+            })))
+            .with_body(r#"{"data": {"nodes": [{"country": "Belgium"}, {"country": "Egypt"}]}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::builder(server.url())
+            .cache(10, Duration::from_secs(60))
+            .build()
+            .expect("Can create a client");
+        let query = "query countries($ids: [Int!]) { nodes(where: {nodeID_in: $ids}) { country } }";
+
+        // The second query is answered from the cache.
+        for _ in 0..2 {
+            let countries: Countries = cl
+                .query("countries", query, serde_json::json!({"ids": [1, 2]}))
+                .await
+                .expect("Can send a typed query");
+            assert_eq!(
+                countries
+                    .nodes
+                    .iter()
+                    .map(|node| node.country.as_str())
+                    .collect::<Vec<_>>(),
+                ["Belgium", "Egypt"]
+            );
+        }
+        nodes.assert_async().await;
+
+        let Err(err) = cl
+            .with_cancellation({
+                let token = CancellationToken::new();
+                token.cancel();
+                token
+            })
+            .query::<_, Countries>("countries", query, serde_json::json!({"ids": [3]}))
+            .await
+        else {
+            panic!("Query of a cancelled client is not sent");
+        };
+        assert!(matches!(err, GraphQlError::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn raw_query_returns_untyped_data() {
+        let mut server = mockito::Server::new_async().await;
+        let raw = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "explore",
+                "query": "query explore($id: Int) { nodes(where: {nodeID_eq: $id}) { country } }",
+                "variables": {"id": 1},
+            })))
+            .with_body(r#"{"data": {"nodes": [{"country": "Belgium"}]}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let data = cl
+            .raw_query(
+                "explore",
+                "query explore($id: Int) { nodes(where: {nodeID_eq: $id}) { country } }",
+                &serde_json::json!({"id": 1}),
+            )
             .await
-            .expect("Can fetch contract bill events from mainnet");
+            .expect("Can send a raw query");
+
+        raw.assert_async().await;
+        assert_eq!(data, serde_json::json!({"nodes": [{"country": "Belgium"}]}));
+
+        let Err(err) = cl
+            .with_cancellation({
+                let token = CancellationToken::new();
+                token.cancel();
+                token
+            })
+            .raw_query(
+                "explore",
+                "query explore($id: Int) { nodes(where: {nodeID_eq: $id}) { country } }",
+                &serde_json::json!({"id": 2}),
+            )
+            .await
+        else {
+            panic!("Raw query of a cancelled client is not sent");
+        };
+        assert!(matches!(err, GraphQlError::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn fetch_nru_consumptions_in_window() {
+        let mut server = mockito::Server::new_async().await;
+        let consumptions = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "nru_consumptions",
+                "variables": {"contract_ids": [1], "start": 3600, "end": 7200},
+            })))
+            .with_body(
+                r#"{"data": {"nruConsumptions": [
+                    {"contractID": "1", "window": "3600", "nru": "1000", "timestamp": "3600"},
+                    {"contractID": "1", "window": "3600", "nru": "500", "timestamp": "7200"}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let found = cl
+            .nru_consumptions(Some(3600), Some(7200), Some(&[1]))
+            .await
+            .expect("Can fetch nru consumptions in a window");
+
+        consumptions.assert_async().await;
+        assert_eq!(
+            found
+                .iter()
+                .map(|c| (c.timestamp, c.nru))
+                .collect::<Vec<_>>(),
+            [(3600, 1000), (7200, 500)]
+        );
+    }
+
+    #[tokio::test]
+    async fn count_active_node_contracts_per_node() {
+        let mut server = mockito::Server::new_async().await;
+        let nodes = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "node_contract_nodes",
+                "variables": {
+                    "nodes": [1, 2, 3],
+                    "states": ["Created", "GracePeriod", "OutOfFunds"],
+                    "offset": 0,
+                },
+            })))
+            .with_body(
+                r#"{"data": {"nodeContracts": [
+                    {"nodeID": 1}, {"nodeID": 2}, {"nodeID": 1}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let counts = cl
+            .node_contract_counts(&[1, 2, 3])
+            .await
+            .expect("Can count node contracts");
+
+        nodes.assert_async().await;
+        assert_eq!(counts, BTreeMap::from([(1, 2), (2, 1), (3, 0)]));
+        // Without nodes nothing is fetched.
+        assert!(cl
+            .node_contract_counts(&[])
+            .await
+            .expect("Can count contracts on no nodes")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_single_contract() {
+        let mut server = mockito::Server::new_async().await;
+        let mut mocks = Vec::new();
+        for (kind, contract_id, found) in [
+            ("node", 7, "[]"),
+            (
+                "name",
+                7,
+                r#"[
+                    {"contractID": "7", "createdAt": "1000000", "solutionProviderID": null, "state": "Deleted", "twinID": 1, "name": "example"}
+                ]"#,
+            ),
+            ("rent", 7, "[]"),
+            ("node", 8, "[]"),
+            ("name", 8, "[]"),
+            ("rent", 8, "[]"),
+        ] {
+            let variables = serde_json::json!({"contract_ids": [contract_id]});
+            mocks.push(mock_contracts(&mut server, kind, variables, found).await);
+        }
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let contract = cl.contract(7).await.expect("Can fetch a contract");
+        let Some(Contract::Name(contract)) = contract else {
+            panic!("Contract 7 is a name contract");
+        };
+        assert_eq!(contract.name, "example");
+        assert_eq!(contract.state, ContractState::Deleted);
+        assert!(cl
+            .contract(8)
+            .await
+            .expect("Can fetch a missing contract")
+            .is_none());
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_contract_events() {
+        let mut server = mockito::Server::new_async().await;
+        let mut contracts = Vec::new();
+        for (kind, found) in [
+            ("node", "[]"),
+            ("name", "[]"),
+            (
+                "rent",
+                r#"[
+                    {"contractID": "5", "createdAt": "1000000", "nodeID": 10, "solutionProviderID": null, "state": "GracePeriod", "twinID": 1}
+                ]"#,
+            ),
+        ] {
+            let variables = serde_json::json!({"contract_ids": [5]});
+            contracts.push(mock_contracts(&mut server, kind, variables, found).await);
+        }
+        let bills = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "get_contract_bill_reports",
+                "variables": {"contract_ids": [5]},
+            })))
+            .with_body(
+                r#"{"data": {"contractBillReports": [
+                    {"amountBilled": "100", "contractID": "5", "timestamp": "4600", "discountReceived": "None"},
+                    {"amountBilled": "100", "contractID": "5", "timestamp": "8200", "discountReceived": "None"}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let events = cl
+            .contract_events(5)
+            .await
+            .expect("Can fetch the events of a contract");
+
+        for mock in contracts {
+            mock.assert_async().await;
+        }
+        bills.assert_async().await;
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.state, event.timestamp))
+                .collect::<Vec<_>>(),
+            [
+                (ContractState::Created, 1_000),
+                (ContractState::GracePeriod, 8_200)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_solution_providers() {
+        let mut server = mockito::Server::new_async().await;
+        let providers = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "operation_name": "solution_providers",
+                "variables": {"spids": [1]},
+            })))
+            .with_body(
+                r#"{"data": {"solutionProviders": [
+                    {"solutionProviderID": 1, "description": "Example provider", "link": "https://example.com"}
+                ]}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let found = cl
+            .solution_providers(&[1])
+            .await
+            .expect("Can fetch solution providers");
+
+        providers.assert_async().await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].solution_provider_id, 1);
+        assert_eq!(found[0].description, "Example provider");
+        assert_eq!(found[0].link, "https://example.com");
+    }
+
+    #[tokio::test]
+    async fn limit_concurrent_requests() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A plain server which holds every request for a while before answering, and counts the
+        // requests it holds at the same time.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Can bind a local port");
+        let address = listener.local_addr().expect("Listener has an address");
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let (counter, max) = (in_flight.clone(), max_in_flight.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.expect("Can accept a connection");
+                let (counter, max) = (counter.clone(), max.clone());
+                tokio::spawn(async move {
+                    // The requests are small, so a single read holds the whole request.
+                    let mut buf = vec![0; 4096];
+                    let _ = stream.read(&mut buf).await;
+                    let current = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    counter.fetch_sub(1, Ordering::SeqCst);
+                    let body = r#"{"data": {"nodes": []}}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        let cl = Client::builder(format!("http://{address}"))
+            .max_concurrent_requests(2)
+            .build()
+            .expect("Can create a client");
+
+        // Every request goes through its own clone, as the UI does.
+        let results = futures::future::join_all((0..8).map(|node_id| {
+            let cl = cl.clone();
+            async move { cl.nodes_by_id(&[node_id]).await }
+        }))
+        .await;
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn builder_sets_connection_settings() {
+        let config = ClientConfig {
+            connect_timeout: Duration::from_secs(1),
+            request_timeout: Some(Duration::from_secs(30)),
+            user_agent: "farm-dashboard".to_string(),
+            page_size: 100,
+        };
+        let builder = Client::builder("http://localhost".to_string())
+            .connect_timeout(Duration::from_secs(1))
+            .request_timeout(Duration::from_secs(30))
+            .user_agent("farm-dashboard".to_string())
+            .page_size(100);
+
+        assert_eq!(builder.config, config);
+        assert_eq!(
+            ClientConfig::default().connect_timeout,
+            Duration::from_secs(5)
+        );
+        assert_eq!(ClientConfig::default().request_timeout, None);
+    }
+
+    #[tokio::test]
+    async fn time_out_on_stalled_response() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A plain server which sends the headers of the response, but never the whole body.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Can bind a local port");
+        let address = listener.local_addr().expect("Listener has an address");
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.expect("Can accept a connection");
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let _ = stream.read(&mut buf).await;
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 100\r\n\r\n{\"data\"")
+                        .await;
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                });
+            }
+        });
+        let cl = Client::with_config(
+            format!("http://{address}"),
+            ClientConfig {
+                request_timeout: Some(Duration::from_millis(100)),
+                user_agent: "farm-dashboard".to_string(),
+                ..ClientConfig::default()
+            },
+        )
+        .expect("Can create a client with a timeout");
+
+        let Err(err) = cl.farms(&[1]).await else {
+            panic!("A stalled response times out");
+        };
+
+        assert!(matches!(err, GraphQlError::Timeout));
+        assert!(err.is_transient());
+    }
+
+    #[tokio::test]
+    async fn stop_paginating_when_cancelled() {
+        let mut server = mockito::Server::new_async().await;
+        let full_page = (0..1000)
+            .map(|ts| format!(r#"{{"nodeID": 1, "timestamp": "{ts}", "uptime": "{ts}"}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        // The page is full, so another page would be requested if the token was not checked.
+        let pages = server
+            .mock("POST", "/")
+            .with_body_from_request(move |_| {
+                cancel.cancel();
+                format!(r#"{{"data": {{"uptimeEvents": [{full_page}]}}}}"#).into()
+            })
+            .expect(1)
+            .create_async()
+            .await;
+        let cl = Client::new(server.url()).expect("Can create a client");
+
+        let result = cl
+            .with_cancellation(token)
+            .uptime_events(1, 0, 10_000)
+            .await;
 
-        assert_eq!(ues.len(), 223);
+        pages.assert_async().await;
+        assert!(matches!(result, Err(GraphQlError::Cancelled)));
     }
 }
@@ -1,9 +1,20 @@
+pub mod analytics;
 pub mod bill_report;
+pub mod cache;
 /// Compatibility tools for (de)serializing data from graphql to regular data types.
-mod compat;
+pub mod compat;
 pub mod consumption;
 pub mod contract;
+pub mod denomination;
+pub mod export;
+pub mod farm;
 pub mod graphql;
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+pub mod metrics;
+pub mod node;
 /// Utilities to work with minting periods.
 pub mod period;
+pub mod report;
+pub mod solution_provider;
+pub mod twin;
 pub mod uptime;
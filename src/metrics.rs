@@ -0,0 +1,275 @@
+//! Prometheus metrics of the grid, and a minimal HTTP server exposing them for scraping.
+//!
+//! This is meant for headless monitoring, and is independent of the UI.
+
+use crate::{
+    analytics::state_distribution,
+    contract::ContractState,
+    denomination::Denomination,
+    graphql::{Client, ContractFilters, GraphQlError, ALL_CONTRACT_STATES},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// Time range in which bills are summed for the total billed metric, in seconds.
+const BILLED_WINDOW: i64 = 60 * 60;
+
+/// A node is considered up if it reported its uptime in this time range, in seconds.
+const NODE_UP_WINDOW: i64 = 2 * 60 * 60;
+
+/// Maximum size of a request read by the metrics server. Only the request line is needed, so
+/// anything after this is ignored.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// The reasons the metrics server can't be started.
+#[derive(Debug)]
+pub enum ServeError {
+    /// The refresh interval is zero, which would refresh the metrics without pause.
+    ZeroInterval,
+    /// The server could not listen on the requested port, e.g. because it is already in use.
+    Bind(std::io::Error),
+}
+
+impl fmt::Display for ServeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroInterval => f.write_str("Metrics refresh interval must not be zero"),
+            Self::Bind(e) => write!(f, "Could not start metrics server: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ServeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Bind(e) => Some(e),
+            Self::ZeroInterval => None,
+        }
+    }
+}
+
+/// A snapshot of the metrics of the grid.
+pub struct Metrics {
+    /// Amount billed for all contracts in the last hour.
+    billed: u64,
+    /// Whether every monitored node is up.
+    node_up: BTreeMap<u32, bool>,
+    /// Amount of contracts on the monitored nodes in every state.
+    contract_count: HashMap<ContractState, usize>,
+}
+
+impl Metrics {
+    /// Collect the metrics at time `now`. Uptime and contracts are only collected for the given
+    /// nodes, bills are collected for all contracts.
+    pub async fn collect(
+        client: &Client,
+        node_ids: &[u32],
+        now: i64,
+    ) -> Result<Self, GraphQlError> {
+        let billed = client
+            .contract_bill_reports(Some(now - BILLED_WINDOW), Some(now), None, None)
+            .await?
+            .into_iter()
+            .map(|bill| bill.amount_billed)
+            .sum();
+        // Without nodes, the filters would select the contracts on all nodes.
+        if node_ids.is_empty() {
+            return Ok(Self {
+                billed,
+                node_up: BTreeMap::new(),
+                contract_count: HashMap::new(),
+            });
+        }
+        let node_up = client
+            .uptime_events_multi(node_ids, now - NODE_UP_WINDOW, now)
+            .await?
+            .into_iter()
+            .map(|(node_id, events)| (node_id, !events.is_empty()))
+            .collect();
+        let contracts = client
+            .contracts(&ContractFilters::default().nodes(node_ids))
+            .await?;
+
+        Ok(Self {
+            billed,
+            node_up,
+            contract_count: state_distribution(&contracts),
+        })
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        // Writing to a String can't fail.
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP tfgrid_total_billed_tft Amount of TFT billed for all contracts in the last hour."
+        );
+        let _ = writeln!(out, "# TYPE tfgrid_total_billed_tft gauge");
+        let _ = writeln!(
+            out,
+            "tfgrid_total_billed_tft {}",
+            Denomination::TFT.to_tokens(self.billed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP tfgrid_node_up Whether the node reported its uptime in the last 2 hours."
+        );
+        let _ = writeln!(out, "# TYPE tfgrid_node_up gauge");
+        for (node_id, up) in &self.node_up {
+            let _ = writeln!(out, "tfgrid_node_up{{node_id=\"{node_id}\"}} {}", *up as u8);
+        }
+        let _ = writeln!(
+            out,
+            "# HELP tfgrid_contract_count Amount of contracts on the monitored nodes per state."
+        );
+        let _ = writeln!(out, "# TYPE tfgrid_contract_count gauge");
+        for state in ALL_CONTRACT_STATES {
+            let _ = writeln!(
+                out,
+                "tfgrid_contract_count{{state=\"{state:?}\"}} {}",
+                self.contract_count.get(&state).copied().unwrap_or_default()
+            );
+        }
+        out
+    }
+}
+
+/// Serve the metrics on `/metrics` on the given port, refreshing them every `interval`. If
+/// refreshing fails, the previous metrics are served until the next refresh. Until the metrics
+/// are collected for the first time, requests are answered with `503 Service Unavailable`, so a
+/// scrape doesn't record an empty set of metrics. This only returns if the server can't be
+/// started, which includes a zero `interval`.
+pub async fn serve(
+    client: Client,
+    node_ids: Vec<u32>,
+    port: u16,
+    interval: Duration,
+) -> Result<(), ServeError> {
+    if interval.is_zero() {
+        return Err(ServeError::ZeroInterval);
+    }
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(ServeError::Bind)?;
+    let rendered = Arc::new(Mutex::new(None));
+
+    let refreshed = rendered.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let now = chrono::Utc::now().timestamp();
+            match Metrics::collect(&client, &node_ids, now).await {
+                Ok(metrics) => *refreshed.lock().unwrap() = Some(metrics.render()),
+                Err(e) => log::error!("Could not refresh metrics: {e}"),
+            }
+        }
+    });
+
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::warn!("Could not accept metrics connection: {e}");
+                continue;
+            }
+        };
+        let body = rendered.lock().unwrap().clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, body.as_deref()).await {
+                log::warn!("Could not serve metrics: {e}");
+            }
+        });
+    }
+}
+
+/// Answer a single HTTP request, see [`response`].
+async fn respond(mut stream: TcpStream, metrics: Option<&str>) -> std::io::Result<()> {
+    let mut buf = vec![0; MAX_REQUEST_SIZE];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    stream
+        .write_all(response(&request, metrics).as_bytes())
+        .await?;
+    stream.shutdown().await
+}
+
+/// The response to an HTTP request: the metrics, a 503 if the metrics are not collected yet, or a
+/// 404 if anything but the metrics is requested.
+fn response(request: &str, metrics: Option<&str>) -> String {
+    if !request.starts_with("GET /metrics ") {
+        return "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            .to_string();
+    }
+    match metrics {
+        Some(metrics) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{metrics}",
+            metrics.len()
+        ),
+        None => "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{response, serve, Metrics, ServeError};
+    use crate::{contract::ContractState, graphql::Client};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        time::Duration,
+    };
+
+    #[test]
+    fn render_prometheus_metrics() {
+        let metrics = Metrics {
+            billed: 15_000_000,
+            node_up: BTreeMap::from([(1, true), (2, false)]),
+            contract_count: HashMap::from([
+                (ContractState::Created, 3),
+                (ContractState::GracePeriod, 1),
+            ]),
+        };
+
+        let rendered = metrics.render();
+
+        assert!(rendered.contains("\ntfgrid_total_billed_tft 1.5\n"));
+        assert!(rendered.contains("\ntfgrid_node_up{node_id=\"1\"} 1\n"));
+        assert!(rendered.contains("\ntfgrid_node_up{node_id=\"2\"} 0\n"));
+        assert!(rendered.contains("\ntfgrid_contract_count{state=\"Created\"} 3\n"));
+        assert!(rendered.contains("\ntfgrid_contract_count{state=\"GracePeriod\"} 1\n"));
+        assert!(rendered.contains("\ntfgrid_contract_count{state=\"Deleted\"} 0\n"));
+        assert!(rendered.contains("# TYPE tfgrid_node_up gauge\n"));
+    }
+
+    #[tokio::test]
+    async fn reject_zero_interval() {
+        let client = Client::new("http://localhost".to_string()).expect("Can create a client");
+
+        let Err(err) = serve(client, Vec::new(), 0, Duration::ZERO).await else {
+            panic!("A zero interval is rejected");
+        };
+
+        assert!(matches!(err, ServeError::ZeroInterval));
+    }
+
+    #[test]
+    fn unavailable_until_collected() {
+        let request = "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        assert!(response(request, None).starts_with("HTTP/1.1 503 "));
+        let collected = response(request, Some("tfgrid_total_billed_tft 1.5\n"));
+        assert!(collected.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(collected.ends_with("\r\n\r\ntfgrid_total_billed_tft 1.5\n"));
+        assert!(response("GET / HTTP/1.1\r\n\r\n", None).starts_with("HTTP/1.1 404 "));
+    }
+}
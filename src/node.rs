@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// A node on the grid.
+#[derive(Serialize, Deserialize)]
+pub struct Node {
+    #[serde(rename = "nodeID")]
+    pub node_id: u32,
+    /// The farm the node is part of.
+    #[serde(rename = "farmID")]
+    pub farm_id: u32,
+    /// The twin of the node itself.
+    #[serde(rename = "twinID")]
+    pub twin_id: u32,
+    pub country: Option<String>,
+    pub city: Option<String>,
+}
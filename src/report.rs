@@ -0,0 +1,839 @@
+//! Human readable reports which combine fetched data and the analytics computed from it.
+//!
+//! Reports are formatted deterministically: entries are sorted and times are formatted in UTC,
+//! so reports generated from the same data are identical, and reports of different periods can
+//! be compared with a regular diff.
+
+use crate::{
+    analytics::{aggregate_bills, hourly_uptime, state_distribution, top_contracts, BillBucket},
+    bill_report::ContractBillReport,
+    contract::{total_resources, ContractState},
+    denomination::Denomination,
+    graphql::{Contracts, ALL_CONTRACT_STATES},
+    uptime::NodeStateChange,
+};
+use chrono::{TimeZone, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+/// Amount of contracts listed in the top contracts section of a report.
+const TOP_CONTRACTS: usize = 5;
+
+/// Generate a markdown report for a twin, with a summary, a table of the contracts of the twin,
+/// the amount billed per contract, and the contracts which were billed the most.
+///
+/// `bills` are expected to be the bill reports of the twin's contracts in the reported period.
+/// `node_uptime` holds the fraction of the period every node was up, by node ID. If it is empty,
+/// the node uptime section is left out of the report.
+pub fn twin_report_markdown(
+    twin_id: u32,
+    contracts: &Contracts,
+    bills: &[ContractBillReport],
+    node_uptime: &BTreeMap<u32, f64>,
+) -> String {
+    let billed = billed_per_contract(bills);
+    let total_billed = billed.values().sum();
+    let rows = contract_rows(contracts);
+    let active = rows.iter().filter(|row| row.state.is_active()).count();
+
+    // Writing to a String can't fail.
+    let mut md = format!("# Report for twin {twin_id}\n\n");
+    let _ = writeln!(md, "## Summary\n");
+    let _ = writeln!(
+        md,
+        "- Contracts: {} ({} node, {} name, {} rent)",
+        contracts.len(),
+        contracts.node_contracts.len(),
+        contracts.name_contracts.len(),
+        contracts.rent_contracts.len(),
+    );
+    let _ = writeln!(md, "- Active contracts: {active}");
+    let _ = writeln!(md, "- Total billed: {}\n", fmt_tft(total_billed));
+
+    let _ = writeln!(md, "## Contracts\n");
+    if rows.is_empty() {
+        let _ = writeln!(md, "No contracts found.\n");
+    } else {
+        let _ = writeln!(md, "| {} |", CONTRACT_COLUMNS.join(" | "));
+        let _ = writeln!(md, "|---:|---|---:|---|---|---:|");
+        for row in rows {
+            let _ = writeln!(md, "| {} |", row.cells(&billed).join(" | "));
+        }
+        let _ = writeln!(md);
+    }
+
+    let _ = writeln!(md, "## Top contracts\n");
+    let top = top_contracts(bills, TOP_CONTRACTS);
+    if top.is_empty() {
+        let _ = writeln!(md, "No contracts were billed.\n");
+    } else {
+        for (rank, (contract_id, amount)) in top.into_iter().enumerate() {
+            let _ = writeln!(
+                md,
+                "{}. Contract {contract_id}: {}",
+                rank + 1,
+                fmt_tft(amount)
+            );
+        }
+        let _ = writeln!(md);
+    }
+
+    if !node_uptime.is_empty() {
+        let _ = writeln!(md, "## Node uptime\n");
+        let _ = writeln!(md, "| Node ID | Uptime |");
+        let _ = writeln!(md, "|---:|---:|");
+        for (node_id, uptime) in node_uptime {
+            let _ = writeln!(md, "| {node_id} | {:.2}% |", uptime * 100.);
+        }
+        let _ = writeln!(md);
+    }
+
+    md
+}
+
+/// Generate a self-contained HTML dashboard for a twin, which can be shared as a single file. The
+/// dashboard holds the same summary as [`twin_report_markdown`], a chart of the amount billed per
+/// `bucket`, a chart of the states of the contracts, and the contract tables. Charts are inlined
+/// as SVG, and no scripts or external assets are used.
+pub fn dashboard_html(
+    twin_id: u32,
+    contracts: &Contracts,
+    bills: &[ContractBillReport],
+    bucket: BillBucket,
+) -> String {
+    let billed = billed_per_contract(bills);
+    let total_billed = billed.values().sum();
+    let rows = contract_rows(contracts);
+    let active = rows.iter().filter(|row| row.state.is_active()).count();
+
+    // Writing to a String can't fail.
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, "<html>");
+    let _ = writeln!(html, "<head>");
+    let _ = writeln!(html, "<meta charset=\"utf-8\">");
+    let _ = writeln!(html, "<title>Dashboard for twin {twin_id}</title>");
+    let _ = writeln!(html, "<style>{DASHBOARD_STYLE}</style>");
+    let _ = writeln!(html, "</head>");
+    let _ = writeln!(html, "<body>");
+    let _ = writeln!(html, "<h1>Dashboard for twin {twin_id}</h1>");
+
+    let _ = writeln!(html, "<h2>Summary</h2>");
+    let _ = writeln!(html, "<ul>");
+    let _ = writeln!(
+        html,
+        "<li>Contracts: {} ({} node, {} name, {} rent)</li>",
+        contracts.len(),
+        contracts.node_contracts.len(),
+        contracts.name_contracts.len(),
+        contracts.rent_contracts.len(),
+    );
+    let _ = writeln!(html, "<li>Active contracts: {active}</li>");
+    let _ = writeln!(html, "<li>Total billed: {}</li>", fmt_tft(total_billed));
+    let _ = writeln!(html, "</ul>");
+
+    let _ = writeln!(
+        html,
+        "<h2>Billed per {}</h2>",
+        bucket.to_string().to_lowercase()
+    );
+    let _ = writeln!(
+        html,
+        "{}",
+        graph_bills_svg(&aggregate_bills(bills, bucket), &ChartOptions::default())
+    );
+    let _ = writeln!(html, "<h2>Contract states</h2>");
+    let _ = writeln!(
+        html,
+        "{}",
+        graph_state_distribution_svg(&state_distribution(contracts), &ChartOptions::default())
+    );
+
+    let _ = writeln!(html, "<h2>Contracts</h2>");
+    if rows.is_empty() {
+        let _ = writeln!(html, "<p>No contracts found.</p>");
+    } else {
+        let _ = writeln!(html, "<table>");
+        let _ = write!(html, "<tr>");
+        for title in CONTRACT_COLUMNS {
+            let _ = write!(html, "<th>{title}</th>");
+        }
+        let _ = writeln!(html, "</tr>");
+        for row in rows {
+            let _ = write!(html, "<tr>");
+            for cell in row.cells(&billed) {
+                let _ = write!(html, "<td>{}</td>", escape_html(&cell));
+            }
+            let _ = writeln!(html, "</tr>");
+        }
+        let _ = writeln!(html, "</table>");
+    }
+
+    let _ = writeln!(html, "<h2>Top contracts</h2>");
+    let top = top_contracts(bills, TOP_CONTRACTS);
+    if top.is_empty() {
+        let _ = writeln!(html, "<p>No contracts were billed.</p>");
+    } else {
+        let _ = writeln!(html, "<ol>");
+        for (contract_id, amount) in top {
+            let _ = writeln!(html, "<li>Contract {contract_id}: {}</li>", fmt_tft(amount));
+        }
+        let _ = writeln!(html, "</ol>");
+    }
+
+    let _ = writeln!(html, "</body>");
+    let _ = writeln!(html, "</html>");
+    html
+}
+
+/// Summarize a list of contracts in a few lines: the amount of contracts of every type, the
+/// public IPs they reserve, and the resources used by the node contracts. If `total_billed` is
+/// set, the total amount billed for the contracts is added as well.
+pub fn contracts_summary(contracts: &Contracts, total_billed: Option<u64>) -> String {
+    let totals = total_resources(&contracts.node_contracts);
+    let mut summary = String::new();
+    let _ = writeln!(
+        summary,
+        "{} node contracts, {} name contracts, {} rent contracts",
+        contracts.node_contracts.len(),
+        contracts.name_contracts.len(),
+        contracts.rent_contracts.len()
+    );
+    let _ = writeln!(summary, "Public IPs: {}", contracts.total_public_ips());
+    let _ = writeln!(
+        summary,
+        "Resources used: {} CRU, {} MRU, {} SRU, {} HRU",
+        totals.used.cru,
+        fmt_bytes(totals.used.mru),
+        fmt_bytes(totals.used.sru),
+        fmt_bytes(totals.used.hru),
+    );
+    if totals.unknown > 0 {
+        let _ = writeln!(
+            summary,
+            "The resources of {} contracts are unknown and not counted",
+            totals.unknown
+        );
+    }
+    if let Some(total_billed) = total_billed {
+        let _ = writeln!(summary, "Total billed: {}", fmt_tft(total_billed));
+    }
+    summary
+}
+
+/// Options of the SVG charts. The default options draw a chart of 600 by 300 pixels without a
+/// caption or axis descriptions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChartOptions {
+    /// Caption drawn above the chart, e.g. "Farm 42 billing, March 2024". Left out if empty.
+    pub title: String,
+    /// Description of the horizontal axis. Left out if empty.
+    pub x_desc: String,
+    /// Description of the vertical axis. Left out if empty.
+    pub y_desc: String,
+    /// Width and height of the chart, in pixels.
+    pub dimensions: (u32, u32),
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            x_desc: String::new(),
+            y_desc: String::new(),
+            dimensions: (SVG_WIDTH, SVG_HEIGHT),
+        }
+    }
+}
+
+/// Draw a bar chart of amounts billed over time as SVG, with one bar per bucket as returned by
+/// [`aggregate_bills`]. If there are no bills, a placeholder text is drawn instead.
+pub fn graph_bills_svg(aggregated: &BTreeMap<i64, u64>, options: &ChartOptions) -> String {
+    let (width, height) = options.dimensions;
+    let mut svg = svg_header(options);
+    let max = aggregated.values().copied().max().unwrap_or_default();
+    if max == 0 {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle">No bills</text>"#,
+            width / 2,
+            height / 2
+        );
+    } else {
+        let plot_width = width.saturating_sub(2 * SVG_MARGIN) as f64;
+        let plot_height = height.saturating_sub(2 * SVG_MARGIN) as f64;
+        let bar_width = plot_width / aggregated.len() as f64;
+        for (idx, amount) in aggregated.values().enumerate() {
+            let bar_height = plot_height * *amount as f64 / max as f64;
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{bar_height:.1}" fill="#4682b4"><title>{}</title></rect>"##,
+                SVG_MARGIN as f64 + idx as f64 * bar_width,
+                height.saturating_sub(SVG_MARGIN) as f64 - bar_height,
+                bar_width * 0.9,
+                fmt_tft(*amount),
+            );
+        }
+        // Label the axes with the extremes, the exact amounts are in the tooltips of the bars.
+        let (first, last) = (aggregated.keys().next(), aggregated.keys().next_back());
+        if let (Some(first), Some(last)) = (first, last) {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{SVG_MARGIN}" y="{}">{}</text>"#,
+                height.saturating_sub(SVG_MARGIN / 2),
+                fmt_utc_time(*first)
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+                width.saturating_sub(SVG_MARGIN),
+                height.saturating_sub(SVG_MARGIN / 2),
+                fmt_utc_time(*last)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r#"<text x="{SVG_MARGIN}" y="{}">{}</text>"#,
+            SVG_MARGIN * 3 / 4,
+            fmt_tft(max)
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Draw a horizontal bar chart of the amount of contracts in every state as SVG, as returned by
+/// [`state_distribution`]. States are always drawn in the same order, and states without
+/// contracts are drawn as well, so charts of different twins can be compared.
+pub fn graph_state_distribution_svg(
+    distribution: &HashMap<ContractState, usize>,
+    options: &ChartOptions,
+) -> String {
+    let (width, height) = options.dimensions;
+    let mut svg = svg_header(options);
+    let max = distribution
+        .values()
+        .copied()
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let plot_width = width.saturating_sub(2 * SVG_MARGIN + STATE_LABEL_WIDTH) as f64;
+    let bar_height =
+        height.saturating_sub(2 * SVG_MARGIN) as f64 / ALL_CONTRACT_STATES.len() as f64;
+    for (idx, state) in ALL_CONTRACT_STATES.into_iter().enumerate() {
+        let count = distribution.get(&state).copied().unwrap_or_default();
+        let y = SVG_MARGIN as f64 + idx as f64 * bar_height;
+        let width = plot_width * count as f64 / max as f64;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{SVG_MARGIN}" y="{:.1}">{state}</text>"#,
+            y + bar_height / 2.
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{y:.1}" width="{width:.1}" height="{:.1}" fill="{}"></rect>"#,
+            SVG_MARGIN + STATE_LABEL_WIDTH,
+            bar_height * 0.8,
+            state_color(state),
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}">{count}</text>"#,
+            (SVG_MARGIN + STATE_LABEL_WIDTH) as f64 + width + 5.,
+            y + bar_height / 2.
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Draw a heatmap of the uptime of a node in the period from `start` to `end` as SVG, based on the
+/// state changes in that period. Every column is a day, and every row an hour of that day, both in
+/// UTC, matching the heatmap of the node state panel. Hours outside of the period are not drawn.
+pub fn graph_uptime_heatmap_svg(
+    changes: &[NodeStateChange],
+    start: i64,
+    end: i64,
+    options: &ChartOptions,
+) -> String {
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+
+    let (width, height) = options.dimensions;
+    let mut svg = svg_header(options);
+    let cells = hourly_uptime(changes, start, end);
+    let (Some((first, _)), Some((last, _))) = (cells.first(), cells.last()) else {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle">No uptime</text>"#,
+            width / 2,
+            height / 2
+        );
+        svg.push_str("</svg>");
+        return svg;
+    };
+    let first_day = first.div_euclid(DAY);
+    let days = last.div_euclid(DAY) - first_day + 1;
+    let cell_width = width.saturating_sub(2 * SVG_MARGIN) as f64 / days as f64;
+    let cell_height = height.saturating_sub(2 * SVG_MARGIN) as f64 / 24.;
+    for (hour, uptime) in &cells {
+        let uptime = uptime.clamp(0., 1.);
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="{cell_width:.1}" height="{cell_height:.1}" fill="rgb({},{},40)"><title>{}: {:.0}%</title></rect>"#,
+            SVG_MARGIN as f64 + (hour.div_euclid(DAY) - first_day) as f64 * cell_width,
+            SVG_MARGIN as f64 + (hour.rem_euclid(DAY) / HOUR) as f64 * cell_height,
+            (200. * (1. - uptime)) as u8,
+            (180. * uptime) as u8,
+            fmt_utc_time(*hour),
+            uptime * 100.,
+        );
+    }
+    // Label the first and last day, the exact hours are in the tooltips of the cells.
+    let _ = writeln!(
+        svg,
+        r#"<text x="{SVG_MARGIN}" y="{}">{}</text>"#,
+        height.saturating_sub(SVG_MARGIN / 2),
+        fmt_utc_date(*first)
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+        width.saturating_sub(SVG_MARGIN),
+        height.saturating_sub(SVG_MARGIN / 2),
+        fmt_utc_date(*last)
+    );
+    svg.push_str("</svg>");
+    svg
+}
+
+/// A contract of any type, as shown in the contract tables of reports.
+struct ContractRow {
+    contract_id: u64,
+    contract_type: &'static str,
+    node_id: Option<u32>,
+    /// Creation time of the contract, in milliseconds.
+    created_at: i64,
+    state: ContractState,
+}
+
+impl ContractRow {
+    /// The cells of the row, in the order of [`CONTRACT_COLUMNS`].
+    fn cells(&self, billed: &HashMap<u64, u64>) -> [String; 6] {
+        [
+            self.contract_id.to_string(),
+            self.contract_type.to_string(),
+            self.node_id
+                .map_or_else(|| "-".to_string(), |id| id.to_string()),
+            fmt_utc_time(self.created_at / 1000),
+            self.state.to_string(),
+            fmt_tft(billed.get(&self.contract_id).copied().unwrap_or_default()),
+        ]
+    }
+}
+
+/// Titles of the columns of the contract tables of reports.
+const CONTRACT_COLUMNS: [&str; 6] = [
+    "Contract ID",
+    "Type",
+    "Node ID",
+    "Created",
+    "State",
+    "Billed",
+];
+
+/// Collect all contracts in a single list, sorted by contract ID.
+fn contract_rows(contracts: &Contracts) -> Vec<ContractRow> {
+    let mut rows = contracts
+        .node_contracts
+        .iter()
+        .map(|c| ContractRow {
+            contract_id: c.contract_id,
+            contract_type: "Node",
+            node_id: Some(c.node_id),
+            created_at: c.created_at,
+            state: c.state,
+        })
+        .chain(contracts.name_contracts.iter().map(|c| ContractRow {
+            contract_id: c.contract_id,
+            contract_type: "Name",
+            node_id: None,
+            created_at: c.created_at,
+            state: c.state,
+        }))
+        .chain(contracts.rent_contracts.iter().map(|c| ContractRow {
+            contract_id: c.contract_id,
+            contract_type: "Rent",
+            node_id: Some(c.node_id),
+            created_at: c.created_at,
+            state: c.state,
+        }))
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|row| row.contract_id);
+    rows
+}
+
+/// Sum the amount billed per contract.
+fn billed_per_contract(bills: &[ContractBillReport]) -> HashMap<u64, u64> {
+    let mut billed = HashMap::new();
+    for bill in bills {
+        *billed.entry(bill.contract_id).or_default() += bill.amount_billed;
+    }
+    billed
+}
+
+/// Width of SVG charts, in pixels.
+const SVG_WIDTH: u32 = 600;
+/// Height of SVG charts, in pixels.
+const SVG_HEIGHT: u32 = 300;
+/// Space around the plot area of SVG charts for labels, in pixels.
+const SVG_MARGIN: u32 = 40;
+/// Space for the state labels in front of the bars of the state distribution chart, in pixels.
+const STATE_LABEL_WIDTH: u32 = 100;
+
+/// Inline style of the HTML dashboard.
+const DASHBOARD_STYLE: &str = "body { font-family: sans-serif; } \
+    table { border-collapse: collapse; } \
+    th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; } \
+    svg { font-size: 12px; }";
+
+/// Opening tag of an SVG chart, which can be embedded in HTML, followed by the caption and axis
+/// descriptions of the chart, if set.
+fn svg_header(options: &ChartOptions) -> String {
+    let (width, height) = options.dimensions;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">\n"
+    );
+    if !options.title.is_empty() {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-weight="bold">{}</text>"#,
+            width / 2,
+            SVG_MARGIN / 3,
+            escape_html(&options.title)
+        );
+    }
+    if !options.x_desc.is_empty() {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+            width / 2,
+            height.saturating_sub(SVG_MARGIN / 10),
+            escape_html(&options.x_desc)
+        );
+    }
+    if !options.y_desc.is_empty() {
+        // Rotated around the origin, so the x coordinate is the negated vertical position.
+        let _ = writeln!(
+            svg,
+            r#"<text transform="rotate(-90)" x="-{}" y="{}" text-anchor="middle">{}</text>"#,
+            height / 2,
+            SVG_MARGIN / 3,
+            escape_html(&options.y_desc)
+        );
+    }
+    svg
+}
+
+/// Color of the bars of contracts in a state, the same colors as used in the UI.
+fn state_color(state: ContractState) -> &'static str {
+    match state {
+        ContractState::Created => "#006400",
+        ContractState::GracePeriod => "#ffd700",
+        ContractState::OutOfFunds => "#8b0000",
+        ContractState::Deleted => "#a0a0a0",
+    }
+}
+
+/// Escape text so it can be safely included in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format an amount as value in TFT, with all decimals so amounts line up in a table.
+fn fmt_tft(amount: u64) -> String {
+    Denomination::TFT.format(amount)
+}
+
+/// Format an amount of bytes in the largest binary unit in which it is at least 1.
+fn fmt_bytes(value: u64) -> String {
+    const KIB: u64 = 1 << 10;
+    const MIB: u64 = 1 << 20;
+    const GIB: u64 = 1 << 30;
+    const TIB: u64 = 1 << 40;
+    match value {
+        v if v >= TIB => format!("{:.2} TiB", v as f64 / TIB as f64),
+        v if v >= GIB => format!("{:.2} GiB", v as f64 / GIB as f64),
+        v if v >= MIB => format!("{:.2} MiB", v as f64 / MIB as f64),
+        v if v >= KIB => format!("{:.2} KiB", v as f64 / KIB as f64),
+        v => format!("{v} B"),
+    }
+}
+
+/// Format a timestamp as UTC time, so the report does not depend on the local timezone.
+fn fmt_utc_time(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+/// Format a timestamp as the date in UTC.
+fn fmt_utc_date(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        contracts_summary, dashboard_html, graph_bills_svg, graph_state_distribution_svg,
+        graph_uptime_heatmap_svg, twin_report_markdown, ChartOptions,
+    };
+    use crate::{
+        analytics::BillBucket,
+        bill_report::{ContractBillReport, DiscountLevel},
+        contract::{ContractState, NameContract, NodeContract, RentContract, Resources},
+        graphql::Contracts,
+        uptime::{calculate_node_state_changes, UptimeEvent},
+    };
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn twin_report_is_sorted_and_complete() {
+        let contracts = Contracts {
+            node_contracts: Vec::new(),
+            name_contracts: vec![NameContract {
+                contract_id: 7,
+                created_at: 86_400_000,
+                solution_provider_id: None,
+                state: ContractState::Deleted,
+                twin_id: 1,
+                name: "example".to_string(),
+            }],
+            rent_contracts: vec![RentContract {
+                contract_id: 3,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 1,
+            }],
+        };
+        let bill = |contract_id, amount_billed| ContractBillReport {
+            amount_billed,
+            contract_id,
+            timestamp: 0,
+            discount_received: DiscountLevel::None,
+        };
+        let bills = [bill(3, 5_000_000), bill(7, 20_000_000), bill(3, 5_000_000)];
+        let node_uptime = BTreeMap::from([(10, 0.995)]);
+
+        assert_eq!(
+            twin_report_markdown(1, &contracts, &bills, &node_uptime),
+            "# Report for twin 1
+
+## Summary
+
+- Contracts: 2 (0 node, 1 name, 1 rent)
+- Active contracts: 1
+- Total billed: 3.0000000 TFT
+
+## Contracts
+
+| Contract ID | Type | Node ID | Created | State | Billed |
+|---:|---|---:|---|---|---:|
+| 3 | Rent | 10 | 1970-01-01 00:00:00 UTC | Created | 1.0000000 TFT |
+| 7 | Name | - | 1970-01-02 00:00:00 UTC | Deleted | 2.0000000 TFT |
+
+## Top contracts
+
+1. Contract 7: 2.0000000 TFT
+2. Contract 3: 1.0000000 TFT
+
+## Node uptime
+
+| Node ID | Uptime |
+|---:|---:|
+| 10 | 99.50% |
+
+"
+        );
+    }
+
+    #[test]
+    fn summarize_contracts() {
+        let node_contract = |contract_id, resources_used| NodeContract {
+            contract_id,
+            created_at: 0,
+            deployment_data: String::new(),
+            deployment_hash: String::new(),
+            grid_version: 3,
+            node_id: 10,
+            number_of_public_ips: 1,
+            resources_used,
+            solution_provider_id: None,
+            state: ContractState::Created,
+            twin_id: 1,
+        };
+        let contracts = Contracts {
+            node_contracts: vec![
+                node_contract(
+                    1,
+                    Some(Resources {
+                        cru: 2,
+                        hru: 0,
+                        mru: 4 << 30,
+                        sru: 512 << 20,
+                    }),
+                ),
+                node_contract(2, None),
+            ],
+            name_contracts: Vec::new(),
+            rent_contracts: vec![RentContract {
+                contract_id: 3,
+                created_at: 0,
+                node_id: 10,
+                solution_provider_id: None,
+                state: ContractState::Created,
+                twin_id: 1,
+            }],
+        };
+
+        assert_eq!(
+            contracts_summary(&contracts, Some(15_000_000)),
+            "2 node contracts, 0 name contracts, 1 rent contracts
+Public IPs: 2
+Resources used: 2 CRU, 4.00 GiB MRU, 512.00 MiB SRU, 0 B HRU
+The resources of 1 contracts are unknown and not counted
+Total billed: 1.5000000 TFT
+"
+        );
+        assert!(!contracts_summary(&contracts, None).contains("Total billed"));
+    }
+
+    #[test]
+    fn empty_twin_report() {
+        let contracts = Contracts {
+            node_contracts: Vec::new(),
+            name_contracts: Vec::new(),
+            rent_contracts: Vec::new(),
+        };
+
+        let report = twin_report_markdown(1, &contracts, &[], &BTreeMap::new());
+
+        assert!(report.contains("No contracts found."));
+        assert!(report.contains("No contracts were billed."));
+        assert!(!report.contains("## Node uptime"));
+    }
+
+    #[test]
+    fn dashboard_is_self_contained() {
+        let contracts = Contracts {
+            node_contracts: Vec::new(),
+            name_contracts: vec![NameContract {
+                contract_id: 7,
+                created_at: 0,
+                solution_provider_id: None,
+                state: ContractState::GracePeriod,
+                twin_id: 1,
+                name: "example".to_string(),
+            }],
+            rent_contracts: Vec::new(),
+        };
+        let bills = [ContractBillReport {
+            amount_billed: 10_000_000,
+            contract_id: 7,
+            timestamp: 3_600,
+            discount_received: DiscountLevel::None,
+        }];
+
+        let html = dashboard_html(1, &contracts, &bills, BillBucket::Day);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("<td>7</td><td>Name</td><td>-</td>"));
+        assert!(html.contains("<li>Contract 7: 1.0000000 TFT</li>"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("src="));
+        assert!(!html.contains("href="));
+    }
+
+    #[test]
+    fn uptime_heatmap_has_a_cell_per_hour() {
+        // Up for the entire first day, down for the second.
+        let ues = [
+            UptimeEvent::new(3_600, 3_600),
+            UptimeEvent::new(86_400, 86_400),
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 2 * 86_400);
+
+        let svg = graph_uptime_heatmap_svg(&changes, 0, 2 * 86_400, &ChartOptions::default());
+
+        assert_eq!(svg.matches("<rect ").count(), 48);
+        assert!(svg.contains("<title>1970-01-01 00:00:00 UTC: 100%</title>"));
+        assert!(svg.contains("<title>1970-01-02 12:00:00 UTC: 0%</title>"));
+        assert!(svg.contains(">1970-01-02</text>"));
+        assert!(svg.ends_with("</svg>"));
+        assert!(
+            graph_uptime_heatmap_svg(&[], 0, 0, &ChartOptions::default()).contains("No uptime")
+        );
+    }
+
+    #[test]
+    fn chart_caption_and_axis_descriptions() {
+        let aggregated = BTreeMap::from([(0, 10_000_000), (3_600, 20_000_000)]);
+
+        let svg = graph_bills_svg(&aggregated, &ChartOptions::default());
+        assert!(svg.contains(r#"width="600" height="300""#));
+        assert!(!svg.contains("font-weight"));
+
+        let options = ChartOptions {
+            title: "Farm 42 billing <March>".to_string(),
+            x_desc: "Time".to_string(),
+            y_desc: "Billed".to_string(),
+            dimensions: (800, 400),
+        };
+        let svg = graph_bills_svg(&aggregated, &options);
+        assert!(svg.contains(r#"width="800" height="400" viewBox="0 0 800 400""#));
+        assert!(svg.contains(
+            r#"<text x="400" y="13" text-anchor="middle" font-weight="bold">Farm 42 billing &lt;March&gt;</text>"#
+        ));
+        assert!(svg.contains(r#"<text x="400" y="396" text-anchor="middle">Time</text>"#));
+        // The axis description has its own row below the labels of the first and last bucket.
+        assert!(svg.contains(r#"<text x="40" y="380">"#));
+        assert!(svg.contains(r#"x="-200" y="13" text-anchor="middle">Billed</text>"#));
+        // The bars use the full width of the chart.
+        assert!(svg.contains(r#"<rect x="400.0""#));
+    }
+
+    #[test]
+    fn charts_smaller_than_margins() {
+        let options = ChartOptions {
+            dimensions: (10, 10),
+            ..Default::default()
+        };
+        let aggregated = BTreeMap::from([(0, 10_000_000), (3_600, 20_000_000)]);
+        assert!(graph_bills_svg(&aggregated, &options).ends_with("</svg>"));
+        let distribution = HashMap::from([(ContractState::Created, 3)]);
+        assert!(graph_state_distribution_svg(&distribution, &options).ends_with("</svg>"));
+        let changes = calculate_node_state_changes(&[UptimeEvent::new(3_600, 3_600)], 0, 7_200);
+        assert!(graph_uptime_heatmap_svg(&changes, 0, 7_200, &options).ends_with("</svg>"));
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// A solution provider, which receives a part of the payments of the contracts it is set on.
+#[derive(Serialize, Deserialize)]
+pub struct SolutionProvider {
+    #[serde(rename = "solutionProviderID")]
+    pub solution_provider_id: u32,
+    /// Free form description of the provider, usually its name.
+    pub description: String,
+    /// Link to the website of the provider.
+    pub link: String,
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// A twin on the grid, the identity of a user, farmer or node.
+#[derive(Serialize, Deserialize)]
+pub struct Twin {
+    #[serde(rename = "twinID")]
+    pub twin_id: u32,
+    /// The address of the account on chain which owns the twin.
+    #[serde(rename = "accountID")]
+    pub account_id: String,
+    /// The relay through which the twin can be reached, if any.
+    pub relay: Option<String>,
+}
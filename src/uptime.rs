@@ -1,20 +1,25 @@
-use crate::compat::{de_i64, de_u64};
+use crate::compat::{de_i64, de_u64, ser_i64, ser_u64};
 use serde::{Deserialize, Serialize};
 
 /// Allowed difference between an advancement in uptime and an advancement in timestamp between 2
 /// consecutive events. Currently set to 5 minutes in the minting, posed to change to 1 minute.
-const ALLOWED_UPTIME_DRIFT: i64 = 60;
+pub const ALLOWED_UPTIME_DRIFT: i64 = 60;
 
 /// An uptime event on the grid.
 #[derive(Serialize, Deserialize)]
 pub struct UptimeEvent {
-    #[serde(deserialize_with = "de_i64")]
+    #[serde(deserialize_with = "de_i64", serialize_with = "ser_i64")]
     timestamp: i64,
-    #[serde(deserialize_with = "de_u64")]
+    #[serde(deserialize_with = "de_u64", serialize_with = "ser_u64")]
     uptime: u64,
 }
 
 impl UptimeEvent {
+    /// Create a new uptime event, reported at `timestamp` with the given `uptime` in seconds.
+    pub fn new(timestamp: i64, uptime: u64) -> Self {
+        Self { timestamp, uptime }
+    }
+
     /// Return the timestamp of this event.
     pub fn timestamp(&self) -> i64 {
         self.timestamp
@@ -26,7 +31,42 @@ impl UptimeEvent {
     }
 }
 
+/// The power state of a node, as set by the farmer.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerState {
+    /// The node is powered on.
+    Up,
+    /// The node is (being) powered down.
+    Down,
+}
+
+/// A change in the power state of a node.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PowerEvent {
+    #[serde(deserialize_with = "de_i64", serialize_with = "ser_i64")]
+    timestamp: i64,
+    state: PowerState,
+}
+
+impl PowerEvent {
+    /// Create a new power event.
+    pub fn new(timestamp: i64, state: PowerState) -> Self {
+        Self { timestamp, state }
+    }
+
+    /// Return the timestamp of this event.
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// Return the new power state of the node.
+    pub fn state(&self) -> PowerState {
+        self.state
+    }
+}
+
 /// A state change in a node
+#[derive(Serialize)]
 pub struct NodeStateChange {
     timestamp: i64,
     state: NodeState,
@@ -46,10 +86,13 @@ impl NodeStateChange {
 }
 
 /// The state of a node.
-#[derive(Clone, Copy)]
+#[derive(Serialize, Clone, Copy)]
 pub enum NodeState {
     /// Node went offline, enclosed value indicates the timestamp at which point this happened.
     Offline(i64),
+    /// Node was powered down by the farmer, enclosed value indicates the timestamp at which point
+    /// the power down was requested.
+    PoweredDown(i64),
     /// Node came (back) online, enclosed value indicates the timestamp at which point this happened.
     Booted(i64),
     /// A reboot is reported which is not possible
@@ -63,6 +106,22 @@ pub enum NodeState {
     Unknown(i64),
 }
 
+impl NodeState {
+    /// The timestamp at which this state took effect, as enclosed in the state. For
+    /// [`NodeState::ImpossibleReboot`] this is the supposed boot time. [`NodeState::Drift`] does
+    /// not enclose a timestamp, so this is [`None`].
+    pub fn occurred_at(self) -> Option<i64> {
+        match self {
+            NodeState::Offline(ts)
+            | NodeState::PoweredDown(ts)
+            | NodeState::Booted(ts)
+            | NodeState::ImpossibleReboot(ts)
+            | NodeState::Unknown(ts) => Some(ts),
+            NodeState::Drift(_) => None,
+        }
+    }
+}
+
 /// Calculate the state changes in a node in a given period based on a series of [`UptimeEvent`]s.
 /// It is the callers responsibility to make sure all events in the defined period are given.
 ///
@@ -77,37 +136,78 @@ pub enum NodeState {
 /// Calculating node state at the start of the period is done based on the [`UptimeEvent`] at index
 /// 0 (if it exists). Conversely, to known the node state at the end of the period, an
 ///   [`UptimeEvent`] which occurred __after__ the period ended needs to be provided. It is the
-///   caller's responsibility to do this.
+///   caller's responsibility to do this. Events after the end of the period are only used to
+///   determine the state at the end, changes which happened after the end are not reported. A node
+///   which reported its uptime exactly at the end of the period is online at the end.
 pub fn calculate_node_state_changes(
     ues: &[UptimeEvent],
     start: i64,
     end: i64,
+) -> Vec<NodeStateChange> {
+    calculate_node_state_changes_with_power(ues, &[], start, end)
+}
+
+/// Calculate the state changes in a node in a given period based on a series of [`UptimeEvent`]s
+/// and [`PowerEvent`]s. This works the same as [`calculate_node_state_changes`], except that a
+/// node which goes down after the farmer requested a power down is reported as
+/// [`NodeState::PoweredDown`] instead of [`NodeState::Offline`]. Similar to the uptime events, the
+/// power events must be sorted in ascending timestamp order. Power events before the start of the
+/// period should be included, so the power state at the start of the period is known. If no power
+/// events are given, this is the same as [`calculate_node_state_changes`].
+pub fn calculate_node_state_changes_with_power(
+    ues: &[UptimeEvent],
+    power_events: &[PowerEvent],
+    start: i64,
+    end: i64,
 ) -> Vec<NodeStateChange> {
     let mut state_changes = Vec::new();
+    // A node which goes down between `from` and `to` is considered to be powered down if a power
+    // down was requested in that time.
+    let down_state = |from: i64, to: i64, offline_at: i64| match power_events
+        .iter()
+        .skip_while(|pe| pe.timestamp < from)
+        .take_while(|pe| pe.timestamp <= to)
+        .find(|pe| pe.state == PowerState::Down)
+    {
+        Some(pe) => NodeState::PoweredDown(i64::max(pe.timestamp, offline_at)),
+        None => NodeState::Offline(offline_at),
+    };
 
     if ues.is_empty() {
         return state_changes;
     }
 
-    // Calculate starting state
+    // Calculate starting state, the last power event before the start of the period determines
+    // the power state at the start.
+    let power_lookback_start = power_events
+        .iter()
+        .take_while(|pe| pe.timestamp <= start)
+        .last()
+        .map(|pe| pe.timestamp)
+        .unwrap_or(start);
     let boot_time = ues[0].timestamp - ues[0].uptime as i64;
     if boot_time > start {
         state_changes.push(NodeStateChange {
             timestamp: ues[0].timestamp,
-            state: NodeState::Offline(start),
+            state: down_state(power_lookback_start, boot_time, start),
         });
     }
 
-    state_changes.push(NodeStateChange {
-        timestamp: ues[0].timestamp,
-        state: NodeState::Booted(boot_time),
-    });
+    // A node which booted after the end of the period was down for the entire period.
+    if boot_time <= end {
+        state_changes.push(NodeStateChange {
+            timestamp: ues[0].timestamp,
+            state: NodeState::Booted(boot_time),
+        });
+    }
 
     // Calculate state changes
     for window in ues.windows(2) {
         // We expect 1 event to be past the end of the period, and since they are sorted, we know
-        // that if the first 1 is past the end, the second one is as well and thus we break here.
-        if window[0].timestamp > end {
+        // that if the first 1 is at or past the end, the second one is past the end as well and
+        // thus we break here. An event at the end shows the node is online at the end, whatever
+        // happens after it is outside of the period.
+        if window[0].timestamp >= end {
             break;
         }
 
@@ -119,17 +219,30 @@ pub fn calculate_node_state_changes(
 
         if (window[1].uptime as i64) < ts_delta {
             // Node went offline
+            let boot_time = window[1].timestamp - window[1].uptime as i64;
             state_changes.push(NodeStateChange {
                 timestamp: window[1].timestamp,
-                state: NodeState::Offline(window[0].timestamp),
-            });
-            // And booted again
-            state_changes.push(NodeStateChange {
-                timestamp: window[1].timestamp,
-                state: NodeState::Booted(window[1].timestamp - window[1].uptime as i64),
+                state: down_state(
+                    window[0].timestamp,
+                    i64::min(boot_time, end),
+                    window[0].timestamp,
+                ),
             });
+            // And booted again, unless this happened after the end of the period, in which case
+            // the node was down at the end.
+            if boot_time <= end {
+                state_changes.push(NodeStateChange {
+                    timestamp: window[1].timestamp,
+                    state: NodeState::Booted(boot_time),
+                });
+            }
             continue;
         }
+        // The node was online at the end of the period, so conflicts detected after the end are
+        // not part of the period.
+        if window[1].timestamp > end {
+            break;
+        }
         // Uptime of second event is bigger than timestamp delta, which can only happen if the node
         // is powered on the whole time, or lying. In the first case, timestamp and uptime need to
         // increase roughly similar.
@@ -166,16 +279,373 @@ pub fn calculate_node_state_changes(
     // Check if state at end of period is covered.
     let last_datapoint_timestamp = ues[ues.len() - 1].timestamp;
     if last_datapoint_timestamp < end {
+        // A node which was powered down after the last event is not in an unknown state.
+        let state = match down_state(last_datapoint_timestamp, end, last_datapoint_timestamp) {
+            NodeState::PoweredDown(ts) => NodeState::PoweredDown(ts),
+            _ => NodeState::Unknown(last_datapoint_timestamp),
+        };
         state_changes.push(NodeStateChange {
             timestamp: end,
-            state: NodeState::Unknown(last_datapoint_timestamp),
+            state,
         });
     }
 
     state_changes
 }
 
-/// Sorts a series of [`UptimeEvent`] in ascending timestamp order.
+/// Calculate the windows of time in which a node was down, based on a list of
+/// [`NodeStateChange`]s as returned by [`calculate_node_state_changes`]. Every window is returned
+/// as a `(start, end)` tuple of timestamps. A node whose state is unknown is presumed down. If the
+/// node is still down after the last state change, the trailing window is closed at `end`.
+pub fn downtime_windows(changes: &[NodeStateChange], end: i64) -> Vec<(i64, i64)> {
+    let mut windows = Vec::new();
+    let mut down_since = None;
+
+    for change in changes {
+        match change.state {
+            NodeState::Offline(ts) | NodeState::PoweredDown(ts) | NodeState::Unknown(ts) => {
+                down_since.get_or_insert(ts);
+            }
+            NodeState::Booted(ts) => {
+                if let Some(since) = down_since.take() {
+                    windows.push((since, i64::max(since, ts)));
+                }
+            }
+            NodeState::ImpossibleReboot(_) | NodeState::Drift(_) => {}
+        }
+    }
+
+    if let Some(since) = down_since {
+        windows.push((since, i64::max(since, end)));
+    }
+
+    windows
+}
+
+/// Get the state of a node at the given timestamp, based on a list of [`NodeStateChange`]s as
+/// returned by [`calculate_node_state_changes`]. This is the last state which took effect at or
+/// before `ts`. [`NodeState::ImpossibleReboot`] and [`NodeState::Drift`] only flag suspicious
+/// events and don't change the state of the node, so they are skipped. If no state took effect
+/// yet at `ts`, the state is [`NodeState::Unknown`] at `ts`.
+pub fn state_at(changes: &[NodeStateChange], ts: i64) -> NodeState {
+    changes
+        .iter()
+        .filter_map(|change| match change.state {
+            NodeState::Offline(at)
+            | NodeState::PoweredDown(at)
+            | NodeState::Booted(at)
+            | NodeState::Unknown(at) => Some((at, change.state)),
+            NodeState::ImpossibleReboot(_) | NodeState::Drift(_) => None,
+        })
+        .take_while(|(at, _)| *at <= ts)
+        .last()
+        .map(|(_, state)| state)
+        .unwrap_or(NodeState::Unknown(ts))
+}
+
+/// Calculate the jitter between consecutive [`UptimeEvent`]s, i.e. how much more the uptime
+/// advanced than the timestamp. The events must be sorted, see [`sort_uptime_events`]. Every
+/// entry is a `(timestamp, jitter)` tuple at the timestamp of the second event of a pair. A pair
+/// with a reboot in between, where the uptime of the second event is not bigger than the time
+/// between both events, has a jitter of 0.
+pub fn jitter_series(ues: &[UptimeEvent]) -> Vec<(i64, i64)> {
+    ues.windows(2)
+        .map(|window| {
+            let ts_delta = window[1].timestamp - window[0].timestamp;
+            let jitter = if window[1].uptime as i64 > ts_delta {
+                window[1].uptime as i64 - window[0].uptime as i64 - ts_delta
+            } else {
+                0
+            };
+            (window[1].timestamp, jitter)
+        })
+        .collect()
+}
+
+/// Sorts a series of [`UptimeEvent`] in ascending timestamp order. Events with the same timestamp
+/// are sorted in ascending uptime order, so the order of the input does not affect how a reboot
+/// in the same second is interpreted.
 pub fn sort_uptime_events(ue: &mut [UptimeEvent]) {
-    ue.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    ue.sort_by_key(|event| (event.timestamp, event.uptime));
+}
+
+/// The reason a node has no [`UptimeEvent`]s in a period, in which case no state changes can be
+/// calculated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingUptime {
+    /// The node exists, but did not report its uptime in the entire period, so it was down.
+    DownEntirePeriod,
+    /// The node ID is not known, so the node never reported its uptime.
+    UnknownNode,
+}
+
+/// Explain why there are no [`UptimeEvent`]s for a node in a period, based on whether the node is
+/// known. Returns [`None`] if there are uptime events. Since an empty list of events can't tell
+/// the difference between these cases, callers of [`calculate_node_state_changes`] should check
+/// this first.
+pub fn missing_uptime(ues: &[UptimeEvent], node_known: bool) -> Option<MissingUptime> {
+    match (ues.is_empty(), node_known) {
+        (false, _) => None,
+        (true, true) => Some(MissingUptime::DownEntirePeriod),
+        (true, false) => Some(MissingUptime::UnknownNode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        calculate_node_state_changes, calculate_node_state_changes_with_power, downtime_windows,
+        jitter_series, missing_uptime, sort_uptime_events, state_at, MissingUptime, NodeState,
+        PowerEvent, PowerState, UptimeEvent,
+    };
+
+    #[test]
+    fn jitter_of_consecutive_events() {
+        let ues = [
+            UptimeEvent::new(1_000, 500),
+            // Uptime advanced 10 seconds more than time.
+            UptimeEvent::new(2_000, 1_510),
+            // Uptime advanced 20 seconds less than time.
+            UptimeEvent::new(3_000, 2_490),
+            // Rebooted in between, the uptime is lower than the time since the last event.
+            UptimeEvent::new(4_000, 300),
+            // Rebooted right after the previous event, the uptime equals the time since it.
+            UptimeEvent::new(5_000, 1_000),
+            UptimeEvent::new(6_000, 2_000),
+            // Uptime went back, but not far enough for a reboot in between. This must not wrap.
+            UptimeEvent::new(7_000, 1_500),
+        ];
+
+        assert_eq!(
+            jitter_series(&ues),
+            [
+                (2_000, 10),
+                (3_000, -20),
+                (4_000, 0),
+                (5_000, 0),
+                (6_000, 0),
+                (7_000, -1_500)
+            ]
+        );
+        assert!(jitter_series(&ues[..1]).is_empty());
+        assert!(jitter_series(&[]).is_empty());
+    }
+
+    #[test]
+    fn distinguish_unknown_nodes_from_down_nodes() {
+        assert_eq!(missing_uptime(&[UptimeEvent::new(100, 10)], true), None);
+        assert_eq!(
+            missing_uptime(&[], true),
+            Some(MissingUptime::DownEntirePeriod)
+        );
+        assert_eq!(missing_uptime(&[], false), Some(MissingUptime::UnknownNode));
+    }
+
+    #[test]
+    fn downtime_windows_of_rebooting_node() {
+        let ues = [
+            // Booted at 900, after the start of the period at 0.
+            UptimeEvent {
+                timestamp: 1_000,
+                uptime: 100,
+            },
+            UptimeEvent {
+                timestamp: 2_000,
+                uptime: 1_100,
+            },
+            // Went down after 2_000, booted again at 2_900.
+            UptimeEvent {
+                timestamp: 3_000,
+                uptime: 100,
+            },
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 5_000);
+
+        // Last event is before the end of the period, so the node is presumed down.
+        assert_eq!(
+            downtime_windows(&changes, 5_000),
+            [(0, 900), (2_000, 2_900), (3_000, 5_000)]
+        );
+    }
+
+    #[test]
+    fn node_state_at_instant() {
+        let ues = [
+            // Booted at 900, after the start of the period at 0.
+            UptimeEvent::new(1_000, 100),
+            // Went down after 2_000, booted again at 2_900.
+            UptimeEvent::new(2_000, 1_100),
+            UptimeEvent::new(3_000, 100),
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 5_000);
+
+        assert_eq!(changes[1].state().occurred_at(), Some(900));
+        assert_eq!(NodeState::Drift(120).occurred_at(), None);
+        assert!(matches!(state_at(&changes, -10), NodeState::Unknown(-10)));
+        assert!(matches!(state_at(&changes, 0), NodeState::Offline(0)));
+        assert!(matches!(state_at(&changes, 899), NodeState::Offline(0)));
+        assert!(matches!(state_at(&changes, 900), NodeState::Booted(900)));
+        assert!(matches!(
+            state_at(&changes, 2_000),
+            NodeState::Offline(2_000)
+        ));
+        assert!(matches!(
+            state_at(&changes, 2_899),
+            NodeState::Offline(2_000)
+        ));
+        assert!(matches!(
+            state_at(&changes, 2_900),
+            NodeState::Booted(2_900)
+        ));
+        // No uptime reported after 3_000.
+        assert!(matches!(
+            state_at(&changes, 4_000),
+            NodeState::Unknown(3_000)
+        ));
+    }
+
+    #[test]
+    fn downtime_windows_of_stable_node() {
+        let ues = [
+            UptimeEvent {
+                timestamp: 1_000,
+                uptime: 10_000,
+            },
+            UptimeEvent {
+                timestamp: 3_000,
+                uptime: 12_000,
+            },
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 2_000);
+
+        assert!(downtime_windows(&changes, 2_000).is_empty());
+    }
+
+    #[test]
+    fn sort_events_with_equal_timestamps() {
+        let mut ues = [
+            UptimeEvent::new(2_000, 1_100),
+            UptimeEvent::new(1_000, 100),
+            UptimeEvent::new(2_000, 50),
+        ];
+        let mut shuffled = [
+            UptimeEvent::new(2_000, 50),
+            UptimeEvent::new(2_000, 1_100),
+            UptimeEvent::new(1_000, 100),
+        ];
+        sort_uptime_events(&mut ues);
+        sort_uptime_events(&mut shuffled);
+
+        let order = |ues: &[UptimeEvent]| {
+            ues.iter()
+                .map(|ue| (ue.timestamp(), ue.uptime()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&ues), [(1_000, 100), (2_000, 50), (2_000, 1_100)]);
+        assert_eq!(order(&ues), order(&shuffled));
+    }
+
+    #[test]
+    fn event_exactly_at_period_end() {
+        let ues = [
+            UptimeEvent::new(1_000, 10_000),
+            // Online at the end of the period.
+            UptimeEvent::new(2_000, 11_000),
+            // Went down after the end, booted again at 2_900.
+            UptimeEvent::new(3_000, 100),
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 2_000);
+
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(changes[0].state(), NodeState::Booted(-9_000)));
+        assert!(downtime_windows(&changes, 2_000).is_empty());
+    }
+
+    #[test]
+    fn events_just_after_period_end() {
+        let ues = [
+            UptimeEvent::new(1_000, 10_000),
+            // Went down after 1_500, booted again at 2_001, just after the end of the period.
+            UptimeEvent::new(1_500, 10_500),
+            UptimeEvent::new(2_101, 100),
+            // Rebooted again after the end of the period, which is not reported.
+            UptimeEvent::new(3_101, 500),
+        ];
+        let changes = calculate_node_state_changes(&ues, 0, 2_000);
+
+        let states = changes
+            .iter()
+            .map(|change| change.state())
+            .collect::<Vec<_>>();
+        assert_eq!(states.len(), 2);
+        assert!(matches!(states[0], NodeState::Booted(-9_000)));
+        assert!(matches!(states[1], NodeState::Offline(1_500)));
+        assert_eq!(downtime_windows(&changes, 2_000), [(1_500, 2_000)]);
+        assert!(matches!(
+            state_at(&changes, 2_000),
+            NodeState::Offline(1_500)
+        ));
+
+        // A node which only booted after the end of the period was down the entire period.
+        let changes = calculate_node_state_changes(&ues[2..], 0, 2_000);
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(changes[0].state(), NodeState::Offline(0)));
+    }
+
+    #[test]
+    fn powered_down_node_is_not_offline() {
+        let ues = [
+            UptimeEvent {
+                timestamp: 1_000,
+                uptime: 10_000,
+            },
+            // Powered down at 1_500, booted again at 2_900.
+            UptimeEvent {
+                timestamp: 3_000,
+                uptime: 100,
+            },
+            // Crashed after 4_000, booted again at 4_900.
+            UptimeEvent {
+                timestamp: 5_000,
+                uptime: 100,
+            },
+        ];
+        let power_events = [
+            PowerEvent::new(1_500, PowerState::Down),
+            PowerEvent::new(2_800, PowerState::Up),
+            PowerEvent::new(6_000, PowerState::Down),
+        ];
+
+        let states = |power_events| {
+            calculate_node_state_changes_with_power(&ues, power_events, 0, 7_000)
+                .into_iter()
+                .map(|change| change.state())
+                .filter(|state| !matches!(state, NodeState::Booted(_)))
+                .map(|state| match state {
+                    NodeState::PoweredDown(ts) => ("powered down", ts),
+                    NodeState::Offline(ts) => ("offline", ts),
+                    NodeState::Unknown(ts) => ("unknown", ts),
+                    _ => ("other", 0),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            states(&power_events),
+            [
+                ("powered down", 1_500),
+                ("offline", 3_000),
+                ("powered down", 6_000)
+            ]
+        );
+        // Without power events, this is regular uptime based state calculation.
+        assert_eq!(
+            states(&[]),
+            [("offline", 1_000), ("offline", 3_000), ("unknown", 5_000)]
+        );
+        assert_eq!(
+            calculate_node_state_changes(&ues, 0, 7_000).len(),
+            calculate_node_state_changes_with_power(&ues, &[], 0, 7_000).len()
+        );
+    }
 }